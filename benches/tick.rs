@@ -0,0 +1,88 @@
+use brickbonker::game::{
+    ball::{self, SpawnBallEvent},
+    brick::{self, BrickComponent},
+    level, GameState, Vector2d,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use gfx::{sprite::SpriteRegion, window::DeltaTime};
+use shrev::EventChannel;
+
+const DT: DeltaTime = 1.0 / 60.0;
+const BRICK_COLUMNS: u32 = 10;
+const BRICK_ROWS: u32 = 20;
+const BALL_COUNT: usize = 8;
+
+// A level packed with 200 bricks and several balls in flight, with everything already in the physics world
+fn dense_game<'a, 'b>() -> GameState<'a, 'b> {
+    let mut game = GameState::new_seeded(320, 400, 0);
+    let world = &mut game.world;
+
+    let paddle_ent = level::spawn_arena(world, 160.0)[0];
+    for y in 0..BRICK_ROWS {
+        for x in 0..BRICK_COLUMNS {
+            let position = Vector2d::new(
+                x as f64 * brick::BRICK_SPRITE_WIDTH as f64,
+                level::LEVEL_BRICKS_Y_OFFSET + (y as f64 * brick::BRICK_SPRITE_HEIGHT as f64),
+            );
+
+            level::spawn_brick(
+                world,
+                position,
+                BrickComponent::new(brick::BRICK_DEFAULT_HP, brick::BRICK_DEFAULT_POINTS),
+                SpriteRegion {
+                    x: 96,
+                    y: 0,
+                    w: brick::BRICK_SPRITE_WIDTH,
+                    h: brick::BRICK_SPRITE_HEIGHT,
+                },
+            );
+        }
+    }
+
+    world
+        .write_resource::<level::LevelState>()
+        .reset(paddle_ent);
+
+    {
+        let mut spawn_ball_events = world.write_resource::<EventChannel<SpawnBallEvent>>();
+        for i in 0..BALL_COUNT {
+            let angle = (i as f64 / BALL_COUNT as f64) * std::f64::consts::PI * 2.0;
+            spawn_ball_events.single_write(SpawnBallEvent {
+                position: Vector2d::new(40.0 + (i as f64 * 32.0), 370.0),
+                linear_velocity: Vector2d::new(angle.cos(), -1.0 - angle.sin().abs()).normalize()
+                    * ball::BALL_DEFAULT_FORCE,
+                owning_paddle_ent: None,
+                radius: ball::BALL_DEFAULT_RADIUS,
+                owner: None,
+            });
+        }
+    }
+
+    world.maintain();
+
+    // Get the balls spawned and every body and collider sent to the physics world
+    game.run_headless(2, DT);
+
+    game
+}
+
+fn tick_benchmark(c: &mut Criterion) {
+    c.bench_function("dense level tick", |b| {
+        b.iter_batched(
+            || dense_game(),
+            |mut game| game.tick(DT),
+            BatchSize::LargeInput,
+        )
+    });
+
+    c.bench_function("dense level 60 ticks", |b| {
+        b.iter_batched(
+            || dense_game(),
+            |mut game| game.run_headless(60, DT),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(benches, tick_benchmark);
+criterion_main!(benches);
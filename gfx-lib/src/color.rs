@@ -58,9 +58,32 @@ impl Color {
         }
     }
 
+    pub fn from_f32(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color { r, g, b, a }
+    }
+
     pub fn data(&self) -> [f32; 4] {
         [self.r, self.g, self.b, self.a]
     }
+
+    pub fn alpha(&self) -> f32 {
+        self.a
+    }
+
+    pub fn with_alpha(&self, a: f32) -> Color {
+        Color { a, ..*self }
+    }
+
+    // Blends between this color and another. A t of 0 is this color, and 1 is the other.
+    pub fn lerp(&self, other: &Color, t: f32) -> Color {
+        let t = t.max(0.0).min(1.0);
+        Color {
+            r: self.r + (other.r - self.r) * t,
+            g: self.g + (other.g - self.g) * t,
+            b: self.b + (other.b - self.b) * t,
+            a: self.a + (other.a - self.a) * t,
+        }
+    }
 }
 
 impl Default for Color {
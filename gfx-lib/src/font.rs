@@ -0,0 +1,69 @@
+use crate::{renderer::TextureId, sprite::SpriteRegion};
+
+// A monospaced bitmap font. Glyphs are laid out on the texture in ascii order, in rows of `columns` glyphs.
+#[derive(Copy, Clone, Debug)]
+pub struct Font {
+    pub texture_id: TextureId,
+    pub glyph_width: u32,
+    pub glyph_height: u32,
+    pub columns: u32,
+}
+
+impl Font {
+    pub fn new(texture_id: TextureId, glyph_width: u32, glyph_height: u32, columns: u32) -> Self {
+        Font {
+            texture_id,
+            glyph_width,
+            glyph_height,
+            columns,
+        }
+    }
+
+    pub fn glyph_region(&self, c: char) -> SpriteRegion {
+        // Anything outside of ascii is drawn as a '?'
+        let ascii = if c.is_ascii() { c as u32 } else { '?' as u32 };
+
+        SpriteRegion {
+            x: (ascii % self.columns) * self.glyph_width,
+            y: (ascii / self.columns) * self.glyph_height,
+            w: self.glyph_width,
+            h: self.glyph_height,
+        }
+    }
+
+    // Positions and regions of each glyph in the text, starting at (x, y) and advancing one glyph width per char
+    pub fn layout(&self, x: f32, y: f32, scale: f32, text: &str) -> Vec<(f32, f32, SpriteRegion)> {
+        let advance = self.glyph_width as f32 * scale;
+
+        text.chars()
+            .enumerate()
+            .map(|(i, c)| (x + (i as f32 * advance), y, self.glyph_region(c)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(x: u32, y: u32) -> SpriteRegion {
+        SpriteRegion { x, y, w: 8, h: 8 }
+    }
+
+    #[test]
+    fn glyphs_are_found_in_ascii_order() {
+        let font = Font::new(0, 8, 8, 16);
+        assert_eq!(font.glyph_region('0'), region(0, 24));
+        assert_eq!(font.glyph_region('A'), region(8, 32));
+        assert_eq!(font.glyph_region('é'), font.glyph_region('?'));
+    }
+
+    #[test]
+    fn layout_advances_one_glyph_per_char() {
+        let font = Font::new(0, 8, 8, 16);
+        assert_eq!(
+            font.layout(10.0, 5.0, 2.0, "A1"),
+            vec![(10.0, 5.0, region(8, 32)), (26.0, 5.0, region(8, 24))]
+        );
+    }
+}
@@ -1,13 +1,44 @@
 use ::winit::{event::ElementState, event::KeyboardInput};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 pub use ::winit::event::VirtualKeyCode;
 
-#[derive(Default, Clone)]
+// Stick values closer to the center than this are treated as 0, since sticks rarely rest at exactly 0
+pub const GAMEPAD_STICK_DEADZONE: f32 = 0.15;
+
+// Gamepad buttons, named by their position so they mean the same thing on every controller
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    Start,
+    Select,
+}
+
+// Serializable so that replays can record the input for every tick
+#[derive(Default, Clone, Debug, Deserialize, Serialize)]
 pub struct InputState {
     current_keys: HashMap<VirtualKeyCode, bool>,
     pressed_keys: HashMap<VirtualKeyCode, bool>,
     released_keys: HashMap<VirtualKeyCode, bool>,
+    // Cursor position in render space (physical pixels divided by the dpi and render scale)
+    cursor_position: Option<(f32, f32)>,
+    #[serde(default)]
+    current_buttons: HashMap<GamepadButton, bool>,
+    #[serde(default)]
+    pressed_buttons: HashMap<GamepadButton, bool>,
+    #[serde(default)]
+    released_buttons: HashMap<GamepadButton, bool>,
+    // Left stick X, from -1.0 (left) to 1.0 (right)
+    #[serde(default)]
+    gamepad_axis_x: f32,
 }
 
 impl InputState {
@@ -16,44 +47,147 @@ impl InputState {
             current_keys: HashMap::new(),
             pressed_keys: HashMap::new(),
             released_keys: HashMap::new(),
+            cursor_position: None,
+            current_buttons: HashMap::new(),
+            pressed_buttons: HashMap::new(),
+            released_buttons: HashMap::new(),
+            gamepad_axis_x: 0.0,
         }
     }
 
     pub fn clear_pressed_and_released(&mut self) {
         self.pressed_keys.clear();
         self.released_keys.clear();
+        self.pressed_buttons.clear();
+        self.released_buttons.clear();
     }
 
     pub fn handle_keyboard_input(&mut self, input: &KeyboardInput) {
-        let keycode: VirtualKeyCode = input.virtual_keycode.unwrap();
+        // Some keys (media keys, etc) have no virtual keycode, so there is nothing to track for them
+        let keycode: VirtualKeyCode = match input.virtual_keycode {
+            Some(keycode) => keycode,
+            None => return,
+        };
+
+        self.handle_key(keycode, input.state == ElementState::Pressed);
+    }
+
+    // Held keys repeat their pressed events, but they only count as pressed on the first one
+    pub fn handle_key(&mut self, keycode: VirtualKeyCode, is_pressed: bool) {
+        if is_pressed {
+            if !self.is_down(keycode) {
+                self.pressed_keys.insert(keycode, true);
+            }
+
+            self.current_keys.insert(keycode, true);
+        } else {
+            self.released_keys.insert(keycode, true);
+            self.current_keys.insert(keycode, false);
+        }
+    }
 
-        match input.state {
-            ElementState::Pressed => {
-                if !self.is_key_held(keycode) {
-                    self.pressed_keys.insert(keycode, true);
-                }
+    pub fn handle_cursor_moved(&mut self, x: f32, y: f32) {
+        self.cursor_position = Some((x, y));
+    }
 
-                self.current_keys.insert(keycode, true);
+    pub fn handle_gamepad_button(&mut self, button: GamepadButton, is_pressed: bool) {
+        if is_pressed {
+            if !self.is_button_down(button) {
+                self.pressed_buttons.insert(button, true);
             }
-            ElementState::Released => {
-                self.released_keys.insert(keycode, true);
-                self.current_keys.insert(keycode, false);
+
+            self.current_buttons.insert(button, true);
+        } else {
+            self.released_buttons.insert(button, true);
+            self.current_buttons.insert(button, false);
+        }
+    }
+
+    pub fn handle_gamepad_axis_x(&mut self, value: f32) {
+        self.gamepad_axis_x = if value.abs() < GAMEPAD_STICK_DEADZONE {
+            0.0
+        } else {
+            value.max(-1.0).min(1.0)
+        };
+    }
+
+    // Releases every button and centers the stick, for when the gamepad is disconnected
+    pub fn clear_gamepad(&mut self) {
+        for (&button, is_down) in self.current_buttons.iter_mut() {
+            if *is_down {
+                self.released_buttons.insert(button, true);
             }
+
+            *is_down = false;
         }
+
+        self.gamepad_axis_x = 0.0;
     }
 
+    // The last known cursor position in render space, or None if the cursor has never entered the window
     #[allow(dead_code)]
-    pub fn is_key_pressed(&self, keycode: VirtualKeyCode) -> bool {
+    pub fn cursor_position(&self) -> Option<(f32, f32)> {
+        self.cursor_position
+    }
+
+    // True only on the tick the key went down
+    #[allow(dead_code)]
+    pub fn was_pressed(&self, keycode: VirtualKeyCode) -> bool {
         *self.pressed_keys.get(&keycode).unwrap_or(&false)
     }
 
+    // True only on the tick the key went up
     #[allow(dead_code)]
-    pub fn is_key_released(&self, keycode: VirtualKeyCode) -> bool {
+    pub fn was_released(&self, keycode: VirtualKeyCode) -> bool {
         *self.released_keys.get(&keycode).unwrap_or(&false)
     }
 
+    // True for as long as the key is held down
     #[allow(dead_code)]
-    pub fn is_key_held(&self, keycode: VirtualKeyCode) -> bool {
+    pub fn is_down(&self, keycode: VirtualKeyCode) -> bool {
         *self.current_keys.get(&keycode).unwrap_or(&false)
     }
+
+    // True only on the tick the gamepad button went down
+    pub fn was_button_pressed(&self, button: GamepadButton) -> bool {
+        *self.pressed_buttons.get(&button).unwrap_or(&false)
+    }
+
+    // True for as long as the gamepad button is held down
+    pub fn is_button_down(&self, button: GamepadButton) -> bool {
+        *self.current_buttons.get(&button).unwrap_or(&false)
+    }
+
+    // Left stick X, from -1.0 (left) to 1.0 (right). This is 0 when no gamepad is connected.
+    pub fn gamepad_axis_x(&self) -> f32 {
+        self.gamepad_axis_x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_presses_only_trigger_on_the_tick_they_happen() {
+        let mut input = InputState::new();
+
+        // First tick: the key goes down
+        input.handle_key(VirtualKeyCode::Space, true);
+        assert!(input.was_pressed(VirtualKeyCode::Space));
+        assert!(input.is_down(VirtualKeyCode::Space));
+        input.clear_pressed_and_released();
+
+        // Second tick: the key is held, and repeats
+        input.handle_key(VirtualKeyCode::Space, true);
+        assert!(!input.was_pressed(VirtualKeyCode::Space));
+        assert!(input.is_down(VirtualKeyCode::Space));
+
+        input.handle_key(VirtualKeyCode::Space, false);
+        assert!(input.was_released(VirtualKeyCode::Space));
+        assert!(!input.is_down(VirtualKeyCode::Space));
+        input.clear_pressed_and_released();
+
+        assert!(!input.was_released(VirtualKeyCode::Space));
+    }
 }
@@ -13,6 +13,7 @@ extern crate nalgebra_glm as glm;
 extern crate winit;
 
 pub mod color;
+pub mod font;
 pub mod input;
 pub mod mesh;
 pub mod renderer;
@@ -26,8 +26,10 @@ pub fn add_sprite(
     y: f32,
     origin: Point2f,
     scale: Vector2f,
+    rotation: f32,
     color: Color,
     region: SpriteRegion,
+    flip: (bool, bool),
     spritesheet_width: u32,
     spritesheet_height: u32,
 ) {
@@ -37,39 +39,59 @@ pub fn add_sprite(
     // TODO
     // need to pre compute these uvs
 
-    let u: f32 = region.x as f32 / spritesheet_width as f32;
-    let v: f32 = region.y as f32 / spritesheet_height as f32;
-    let u_width: f32 = region.w as f32 / spritesheet_width as f32;
-    let v_height: f32 = region.h as f32 / spritesheet_height as f32;
+    let mut u: f32 = region.x as f32 / spritesheet_width as f32;
+    let mut v: f32 = region.y as f32 / spritesheet_height as f32;
+    let mut u_width: f32 = region.w as f32 / spritesheet_width as f32;
+    let mut v_height: f32 = region.h as f32 / spritesheet_height as f32;
 
-    // Offset the render position based on the sprite origin
-    let x = x - (origin.x as f32 * scale.x);
-    let y = y - (origin.y as f32 * scale.y);
-    let w = region.w as f32 * scale.x;
-    let h = region.h as f32 * scale.y;
+    // Flipping just swaps the uvs, so the sprite stays in the same place relative to its origin
+    if flip.0 {
+        u += u_width;
+        u_width = -u_width;
+    }
+
+    if flip.1 {
+        v += v_height;
+        v_height = -v_height;
+    }
+
+    // Corners of the sprite relative to its origin, which is what it gets rotated about
+    let left = -(origin.x as f32 * scale.x);
+    let top = -(origin.y as f32 * scale.y);
+    let right = left + (region.w as f32 * scale.x);
+    let bottom = top + (region.h as f32 * scale.y);
+
+    let (sin, cos) = rotation.sin_cos();
+    let transform = |corner_x: f32, corner_y: f32| -> [f32; 3] {
+        [
+            x + (corner_x * cos) - (corner_y * sin),
+            y + (corner_x * sin) + (corner_y * cos),
+            0.0,
+        ]
+    };
 
     let new_vertices: [Vertex; 4] = [
         // Top left
         Vertex {
-            position: [x, y, 0.0],
+            position: transform(left, top),
             color,
             uv: [u, v],
         },
         // Top right
         Vertex {
-            position: [x + w, y, 0.0],
+            position: transform(right, top),
             color,
             uv: [u + u_width, v],
         },
         // Bottom right
         Vertex {
-            position: [x + w, y + h, 0.0],
+            position: transform(right, bottom),
             color,
             uv: [u + u_width, v + v_height],
         },
         // Bottom left
         Vertex {
-            position: [x, y + h, 0.0],
+            position: transform(left, bottom),
             color,
             uv: [u, v + v_height],
         },
@@ -138,3 +160,51 @@ pub fn add_quad(
     mesh.vertices.extend_from_slice(&new_vertices);
     mesh.indices.extend_from_slice(&new_indices);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::COLOR_WHITE;
+
+    fn sprite_vertices(flip: (bool, bool)) -> Vec<Vertex> {
+        let mut mesh = Mesh {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+        };
+        add_sprite(
+            &mut mesh,
+            100.0,
+            50.0,
+            Point2f::new(16.0, 8.0),
+            Vector2f::new(2.0, 2.0),
+            0.0,
+            COLOR_WHITE,
+            SpriteRegion {
+                x: 32,
+                y: 0,
+                w: 32,
+                h: 16,
+            },
+            flip,
+            128,
+            64,
+        );
+
+        mesh.vertices
+    }
+
+    #[test]
+    fn flip_x_swaps_the_u_coordinates() {
+        let unflipped = sprite_vertices((false, false));
+        let flipped = sprite_vertices((true, false));
+
+        // Top left with top right, and bottom right with bottom left
+        for (i, j) in [(0, 1), (1, 0), (2, 3), (3, 2)].iter() {
+            assert_eq!(flipped[*i].uv[0], unflipped[*j].uv[0]);
+            assert_eq!(flipped[*i].uv[1], unflipped[*i].uv[1]);
+            assert_eq!(flipped[*i].position, unflipped[*i].position);
+        }
+        assert_eq!(unflipped[0].uv[0], 0.25);
+        assert_eq!(flipped[0].uv[0], 0.5);
+    }
+}
@@ -30,9 +30,12 @@ use gfx_hal::{
     Backend, IndexType, Instance, MemoryTypeId,
 };
 use glm;
+use log::{debug, error};
 use std::{
     cell::RefCell,
     collections::HashMap,
+    error::Error,
+    fmt,
     fs::File,
     io::{Cursor, Read},
     rc::Rc,
@@ -76,6 +79,45 @@ pub type RenderKey = u64;
 pub type ShaderProgramId = u16;
 pub type TextureId = u16;
 
+// Reasons the renderer can fail to start up, or to (re)create its swapchain
+#[derive(Debug)]
+pub enum RendererError {
+    BackendUnsupported,
+    SurfaceCreationFailed(String),
+    AdapterNotFound,
+    QueueFamilyNotFound,
+    DeviceCreationFailed(String),
+    OutOfMemory(String),
+    SwapchainCreationFailed(String),
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RendererError::BackendUnsupported => {
+                write!(f, "The graphics backend is not supported on this machine")
+            }
+            RendererError::SurfaceCreationFailed(e) => {
+                write!(f, "Failed to create a surface for the window: {}", e)
+            }
+            RendererError::AdapterNotFound => write!(f, "No graphics adapter was found"),
+            RendererError::QueueFamilyNotFound => write!(
+                f,
+                "No graphics adapter queue family supports drawing to the window"
+            ),
+            RendererError::DeviceCreationFailed(e) => {
+                write!(f, "Failed to open the graphics device: {}", e)
+            }
+            RendererError::OutOfMemory(e) => write!(f, "Ran out of memory while {}", e),
+            RendererError::SwapchainCreationFailed(e) => {
+                write!(f, "Failed to create the swapchain: {}", e)
+            }
+        }
+    }
+}
+
+impl Error for RendererError {}
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Transparency {
     Opaque = 0,
@@ -102,8 +144,11 @@ pub enum Renderable {
         y: f32,
         origin: Point2f,
         scale: Vector2f,
+        rotation: f32,
         color: Color,
         region: SpriteRegion,
+        flip_x: bool,
+        flip_y: bool,
     },
 }
 
@@ -133,6 +178,109 @@ impl RenderCommand {
     }
 }
 
+/// Sorts the commands by their key, and groups together the ones that can be drawn with a single draw call.
+/// The sort is stable, so commands in the same group stay in the order they were submitted.
+pub fn batch_commands(mut commands: Vec<RenderCommand>) -> Vec<(RenderKey, Vec<RenderCommand>)> {
+    commands.sort_by_key(|command| command.key());
+
+    let mut batches: Vec<(RenderKey, Vec<RenderCommand>)> = Vec::new();
+    for command in commands {
+        let key = command.key();
+        match batches.last_mut() {
+            Some((batch_key, batch)) if *batch_key == key => batch.push(command),
+            _ => batches.push((key, vec![command])),
+        }
+    }
+
+    batches
+}
+
+/// The vertices for one batch of commands, which are all drawn with a single draw call.
+/// Every 4 vertices make up a quad.
+pub struct VertexBatch {
+    pub transparency: Transparency,
+    pub layer: u8,
+    pub shader_program_id: ShaderProgramId,
+    pub tex_id: TextureId,
+    pub vertices: Vec<Vertex>,
+}
+
+/// Batches the commands (see `batch_commands`) and builds the vertex list for each batch.
+/// Sprite uvs depend on the size of their texture, which is looked up with `texture_size`.
+pub fn build_vertex_batches(
+    commands: Vec<RenderCommand>,
+    texture_size: impl Fn(TextureId) -> (u32, u32),
+) -> Vec<VertexBatch> {
+    batch_commands(commands)
+        .into_iter()
+        .map(|(_, commands)| {
+            let first = &commands[0];
+            let (transparency, layer, shader_program_id, tex_id) = (
+                first.transparency,
+                first.layer,
+                first.shader_program_id,
+                first.tex_id,
+            );
+            let (tex_w, tex_h) = texture_size(tex_id);
+
+            let mut mesh = Mesh {
+                vertices: Vec::new(),
+                indices: Vec::new(),
+            };
+            for command in commands {
+                add_command(&mut mesh, command, tex_w, tex_h);
+            }
+
+            VertexBatch {
+                transparency,
+                layer,
+                shader_program_id,
+                tex_id,
+                vertices: mesh.vertices,
+            }
+        })
+        .collect()
+}
+
+fn add_command(mesh: &mut Mesh, command: RenderCommand, tex_w: u32, tex_h: u32) {
+    match command.data {
+        Renderable::Quad {
+            bl,
+            br,
+            tl,
+            tr,
+            color,
+        } => {
+            mesh::add_quad(mesh, bl, br, tl, tr, color);
+        }
+        Renderable::Sprite {
+            x,
+            y,
+            origin,
+            scale,
+            rotation,
+            color,
+            region,
+            flip_x,
+            flip_y,
+        } => {
+            mesh::add_sprite(
+                mesh,
+                x,
+                y,
+                origin,
+                scale,
+                rotation,
+                color,
+                region,
+                (flip_x, flip_y),
+                tex_w,
+                tex_h,
+            );
+        }
+    }
+}
+
 pub struct RenderBatch {
     device: GfxDeviceHandle,
     transparency: Transparency,
@@ -221,37 +369,20 @@ impl RenderBatch {
         mesh
     }
 
-    pub fn process_command(&mut self, command: RenderCommand) {
-        match command.data {
-            Renderable::Quad {
-                bl,
-                br,
-                tl,
-                tr,
-                color,
-            } => {
-                mesh::add_quad(self.batch_mesh.as_mut().unwrap(), bl, br, tl, tr, color);
-            }
-            Renderable::Sprite {
-                x,
-                y,
-                origin,
-                scale,
-                color,
-                region,
-            } => {
-                mesh::add_sprite(
-                    self.batch_mesh.as_mut().unwrap(),
-                    x,
-                    y,
-                    origin,
-                    scale,
-                    color,
-                    region,
-                    self.tex_info.1,
-                    self.tex_info.2,
-                );
-            }
+    /// Adds quads to the batch, from every 4 vertices
+    pub fn add_vertices(&mut self, vertices: &[Vertex]) {
+        let mesh = self.batch_mesh.as_mut().unwrap();
+        for quad in vertices.chunks_exact(4) {
+            let vertex_count = mesh.vertices.len() as u32;
+            mesh.vertices.extend_from_slice(quad);
+            mesh.indices.extend_from_slice(&[
+                vertex_count,
+                vertex_count + 1,
+                vertex_count + 2,
+                vertex_count + 2,
+                vertex_count + 3,
+                vertex_count,
+            ]);
         }
     }
 
@@ -276,7 +407,7 @@ impl RenderBatch {
 
 impl Drop for RenderBatch {
     fn drop(&mut self) {
-        println!("Cleaning up RenderBatch {}", self.key());
+        debug!(target: "renderer", "Cleaning up RenderBatch {}", self.key());
 
         let device = self.device.borrow();
         unsafe {
@@ -309,7 +440,7 @@ pub struct GpuTexture {
 
 impl Drop for GpuTexture {
     fn drop(&mut self) {
-        println!("Cleaning up GpuTexture {}", self.id);
+        debug!(target: "renderer", "Cleaning up GpuTexture {}", self.id);
 
         let device = self.device.borrow_mut();
         unsafe {
@@ -337,7 +468,7 @@ struct RenderProgram {
 
 impl Drop for RenderProgram {
     fn drop(&mut self) {
-        println!("Cleaning up RenderProgram");
+        debug!(target: "renderer", "Cleaning up RenderProgram");
 
         let device = self.device.borrow();
         unsafe {
@@ -385,22 +516,26 @@ pub struct Renderer {
 }
 
 impl Renderer {
-    pub fn new(window: &WinitWindow, render_scale: f32) -> Renderer {
+    pub fn new(window: &WinitWindow, render_scale: f32) -> Result<Renderer, RendererError> {
         // Create an instance, which is the entry point to the graphics API.
         let instance =
-            GfxInstance::create("gfx-rs", 1).expect("Failed to create backend instance!");
+            GfxInstance::create("gfx-rs", 1).map_err(|_| RendererError::BackendUnsupported)?;
 
         // Create a surface, which is an abstraction over the OS's native window.
         let surface = unsafe {
             instance
                 .create_surface(window)
-                .expect("Failed to create window surface!")
+                .map_err(|e| RendererError::SurfaceCreationFailed(format!("{:?}", e)))?
         };
 
         // Grab the first available adapter.
         // An adapter represents a physical device, like a GPU.
         // TODO do we actually need to iterate and grab a proper adapter
-        let adapter = instance.enumerate_adapters().remove(0);
+        let adapter = instance
+            .enumerate_adapters()
+            .into_iter()
+            .next()
+            .ok_or(RendererError::AdapterNotFound)?;
 
         let family = adapter
             .queue_families
@@ -408,18 +543,20 @@ impl Renderer {
             .find(|family| {
                 surface.supports_queue_family(family) && family.queue_type().supports_graphics()
             })
-            .unwrap();
+            .ok_or(RendererError::QueueFamilyNotFound)?;
 
         let mut gpu = unsafe {
             adapter
                 .physical_device
                 .open(&[(family, &[1.0])], gfx_hal::Features::empty())
-                .unwrap()
+                .map_err(|e| RendererError::DeviceCreationFailed(format!("{:?}", e)))?
         };
 
         // The device is a logical device that allows us to perform GPU operations.
         // The queue group contains a set of command queues which we can submit drawing commands to.
-        let queue_group = gpu.queue_groups.pop().unwrap();
+        let queue_group = gpu.queue_groups.pop().ok_or_else(|| {
+            RendererError::DeviceCreationFailed(String::from("No queue group was opened"))
+        })?;
         let device = gpu.device;
 
         let frames_in_flight = 2;
@@ -440,22 +577,22 @@ impl Renderer {
                             queue_group.family,
                             pool::CommandPoolCreateFlags::empty(),
                         )
-                        .expect("Failed to create create command pool for frame!"),
+                        .map_err(|_| {
+                            RendererError::OutOfMemory(String::from("creating a command pool"))
+                        })?,
                 );
             }
         }
 
         for i in 0..frames_in_flight {
-            frame_semaphores.push(
-                device
-                    .create_semaphore()
-                    .expect("Failed to create frame semaphore!"),
-            );
+            frame_semaphores.push(device.create_semaphore().map_err(|_| {
+                RendererError::OutOfMemory(String::from("creating a frame semaphore"))
+            })?);
 
             frame_fences.push(
-                device
-                    .create_fence(true)
-                    .expect("Failed to create frame fence!"),
+                device.create_fence(true).map_err(|_| {
+                    RendererError::OutOfMemory(String::from("creating a frame fence"))
+                })?,
             );
 
             command_buffers.push(unsafe { command_pools[i].allocate_one(command::Level::Primary) });
@@ -463,7 +600,7 @@ impl Renderer {
 
         // Grab the supported image formats for our surface, then decide on a surface color format
         let formats = surface.supported_formats(&adapter.physical_device);
-        println!("Surface supported formats: {:?}", formats);
+        debug!(target: "renderer", "Surface supported formats: {:?}", formats);
         let surface_color_format = formats.map_or(Format::Rgba8Srgb, |formats| {
             formats
                 .iter()
@@ -567,7 +704,7 @@ impl Renderer {
             depth: 0.0..1.0,
         };
 
-        Renderer {
+        Ok(Renderer {
             instance,
             surface: Some(surface),
             adapter,
@@ -591,13 +728,13 @@ impl Renderer {
             batches: HashMap::new(),
             frames_in_flight,
             current_frame: 0,
-        }
+        })
     }
 
-    pub fn resize(&mut self, width: u32, height: u32) {
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), RendererError> {
         self.dimensions = Extent2D { width, height };
 
-        self.rebuild_swapchain();
+        self.rebuild_swapchain()
     }
 
     pub fn create_render_batch(
@@ -631,7 +768,7 @@ impl Renderer {
             let descriptor_set = match unsafe { pool.allocate_set(layout) } {
                 Ok(set) => set,
                 Err(e) => {
-                    eprintln!("Failed to create batch! {:?}", e);
+                    error!(target: "renderer", "Failed to create batch! {:?}", e);
                     panic!();
                 }
             };
@@ -689,7 +826,7 @@ impl Renderer {
         let key = batch.key();
         self.batches.insert(key, batch);
 
-        println!("[GFX] Created render batch with key {}", key);
+        debug!(target: "renderer", "[GFX] Created render batch with key {}", key);
         Ok(key)
     }
 
@@ -727,7 +864,7 @@ impl Renderer {
                     }
                     DescriptorType::Image { ty: ImageDescriptorType::Sampled { .. }, .. } => {
                         if image_descriptor.is_none() {
-                            eprintln!("Failed to write to Sampled Image descriptor binding! Image descriptor was already in use or didn't exist!");
+                            error!(target: "renderer", "Failed to write to Sampled Image descriptor binding! Image descriptor was already in use or didn't exist!");
                             continue;
                         }
 
@@ -740,7 +877,7 @@ impl Renderer {
                     }
                     DescriptorType::Sampler => {
                         if sampler_descriptor.is_none() {
-                            eprintln!("Failed to write to Sampler descriptor binding! Image descriptor was already in use or didn't exist!");
+                            error!(target: "renderer", "Failed to write to Sampler descriptor binding! Image descriptor was already in use or didn't exist!");
                             continue;
                         }
 
@@ -764,72 +901,49 @@ impl Renderer {
     }
 
     /// Process some `RenderCommand`s, sorting them and producing batches that can be rendered.
-    pub fn process_commands(&mut self, mut commands: Vec<RenderCommand>) -> Vec<RenderKey> {
-        commands.sort_by(|a, b| a.key().cmp(&b.key()));
-
-        // Process commands into batches
-        let mut batch_keys: Vec<RenderKey> = Vec::new();
-        let mut batch: Option<&mut RenderBatch> = None;
-
-        for command in commands {
-            let cmd_transparency = command.transparency;
-            let cmd_layer = command.layer;
-            let cmd_tex_id = command.tex_id;
-            let cmd_shader_program_id = command.shader_program_id;
-
-            // Flush the current batch if we are encountering new data
-            if batch.is_some() {
-                let (
-                    batch_transparency,
-                    batch_layer,
-                    batch_shader_program_id,
-                    batch_tex_id,
-                    batch_key,
-                ) = {
-                    let b = batch.as_ref().unwrap();
-                    (
-                        b.transparency,
-                        b.layer,
-                        b.shader_program_id,
-                        b.tex_id(),
-                        b.key(),
-                    )
-                };
-
-                if (batch_transparency != cmd_transparency)
-                    || (batch_layer != cmd_layer)
-                    || (batch_shader_program_id != cmd_shader_program_id)
-                    || (batch_tex_id != cmd_tex_id)
-                {
-                    batch_keys.push(batch_key);
-                    batch = None;
-                }
-            }
-
-            // Begin a new batch if needed
-            if batch.is_none() {
-                let key = self
-                    .create_render_batch(
-                        cmd_transparency,
-                        cmd_layer,
-                        cmd_shader_program_id,
-                        cmd_tex_id,
-                    )
-                    .unwrap();
-                batch = Some(self.batches.get_mut(&key).unwrap());
-            }
+    /// Commands that share a transparency, layer, shader program, and texture all end up in the same
+    /// batch, which is drawn with a single draw call. Since the sort key puts the layer above the shader
+    /// and texture, batches still come out in layer order (opaque batches first, then transparent ones).
+    pub fn process_commands(&mut self, commands: Vec<RenderCommand>) -> Vec<RenderKey> {
+        let textures = &self.textures;
+        let vertex_batches = build_vertex_batches(commands, |tex_id| {
+            textures
+                .get(&tex_id)
+                .map(|tex| (tex.w, tex.h))
+                .unwrap_or((0, 0))
+        });
 
-            if let Some(batch) = batch.as_mut() {
-                batch.process_command(command);
-            }
-        }
+        vertex_batches
+            .iter()
+            .map(|batch| {
+                self.draw_batch(
+                    batch.transparency,
+                    batch.layer,
+                    batch.shader_program_id,
+                    batch.tex_id,
+                    &batch.vertices,
+                )
+            })
+            .collect()
+    }
 
-        // Flush the current batch since we are done with all commands
-        if batch.is_some() {
-            batch_keys.push(batch.unwrap().key());
-        }
+    /// Sets the vertices of the batch with the given transparency, layer, shader program, and texture,
+    /// replacing anything it had this frame. Every 4 vertices make up a quad. Returns the batch key,
+    /// to pass on to `render`.
+    pub fn draw_batch(
+        &mut self,
+        transparency: Transparency,
+        layer: u8,
+        shader_program_id: ShaderProgramId,
+        tex_id: TextureId,
+        vertices: &[Vertex],
+    ) -> RenderKey {
+        let key = self
+            .create_render_batch(transparency, layer, shader_program_id, tex_id)
+            .unwrap();
+        self.batches.get_mut(&key).unwrap().add_vertices(vertices);
 
-        batch_keys
+        key
     }
 
     pub fn render(&mut self, scale_factor: f32, batch_keys: Vec<RenderKey>) {
@@ -841,7 +955,9 @@ impl Renderer {
             match self.surface.as_mut().unwrap().acquire_image(!0) {
                 Ok((image, _)) => image,
                 Err(_) => {
-                    self.rebuild_swapchain();
+                    if let Err(e) = self.rebuild_swapchain() {
+                        error!(target: "renderer", "[Renderer] {}", e);
+                    }
                     return;
                 }
             }
@@ -956,7 +1072,9 @@ impl Renderer {
         }
 
         if result.is_err() {
-            self.rebuild_swapchain();
+            if let Err(e) = self.rebuild_swapchain() {
+                error!(target: "renderer", "[Renderer] {}", e);
+            }
         }
 
         self.current_frame += 1;
@@ -1026,13 +1144,13 @@ impl Renderer {
         }
     }
 
-    pub fn rebuild_swapchain(&mut self) {
+    pub fn rebuild_swapchain(&mut self) -> Result<(), RendererError> {
         if self.surface.is_none() {
             panic!("Failed to rebuild swapchain: Renderer surface was None!");
         }
         let surface = self.surface.as_mut().unwrap();
 
-        println!("Rebuilding swapchain.");
+        debug!(target: "renderer", "Rebuilding swapchain.");
 
         let capabilities = surface.capabilities(&self.adapter.physical_device);
         let swap_config = window::SwapchainConfig::from_caps(
@@ -1040,17 +1158,19 @@ impl Renderer {
             self.surface_color_format,
             self.dimensions,
         );
-        println!("swap_config: {:?}", swap_config);
+        debug!(target: "renderer", "swap_config: {:?}", swap_config);
         let extent = swap_config.extent.to_extent();
 
         unsafe {
             surface
                 .configure_swapchain(&self.device.borrow(), swap_config)
-                .expect("Can't create swapchain");
+                .map_err(|e| RendererError::SwapchainCreationFailed(format!("{:?}", e)))?;
         }
 
         self.viewport.rect.w = extent.width as _;
         self.viewport.rect.h = extent.height as _;
+
+        Ok(())
     }
 
     pub fn create_gpu_texture(&mut self, id: GpuTextureId, w: u32, h: u32, pixels: &Vec<u8>) {
@@ -1216,7 +1336,7 @@ impl Renderer {
 
 impl Drop for Renderer {
     fn drop(&mut self) {
-        println!("Cleaning up Renderer");
+        debug!(target: "renderer", "Cleaning up Renderer");
 
         self.textures.clear();
         self.shader_programs.clear();
@@ -1687,3 +1807,127 @@ fn create_render_program(
         shader_descriptor_bindings,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renderer_errors_describe_what_went_wrong() {
+        let error: Box<dyn Error> = Box::new(RendererError::SurfaceCreationFailed(
+            "no display".to_string(),
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Failed to create a surface for the window: no display"
+        );
+        assert_eq!(
+            RendererError::AdapterNotFound.to_string(),
+            "No graphics adapter was found"
+        );
+        assert_eq!(
+            RendererError::OutOfMemory("creating the vertex buffer".to_string()).to_string(),
+            "Ran out of memory while creating the vertex buffer"
+        );
+    }
+
+    fn quad_command(layer: u8, tex_id: TextureId, x: f32) -> RenderCommand {
+        RenderCommand {
+            transparency: Transparency::Opaque,
+            shader_program_id: 0,
+            tex_id,
+            layer,
+            data: Renderable::Quad {
+                bl: (x, 1.0),
+                br: (x + 1.0, 1.0),
+                tl: (x, 0.0),
+                tr: (x + 1.0, 0.0),
+                color: Color::from_f32(1.0, 1.0, 1.0, 1.0),
+            },
+        }
+    }
+
+    fn sprite_command(layer: u8, tex_id: TextureId) -> RenderCommand {
+        RenderCommand {
+            transparency: Transparency::Opaque,
+            shader_program_id: 1,
+            tex_id,
+            layer,
+            data: Renderable::Sprite {
+                x: 0.0,
+                y: 0.0,
+                origin: Point2f::origin(),
+                scale: Vector2f::new(1.0, 1.0),
+                rotation: 0.0,
+                color: Color::from_f32(1.0, 1.0, 1.0, 1.0),
+                region: SpriteRegion {
+                    x: 0,
+                    y: 0,
+                    w: 16,
+                    h: 16,
+                },
+                flip_x: false,
+                flip_y: false,
+            },
+        }
+    }
+
+    #[test]
+    fn sprites_on_two_textures_make_one_vertex_batch_per_texture() {
+        let commands = vec![
+            sprite_command(2, 1),
+            sprite_command(2, 2),
+            sprite_command(2, 1),
+            sprite_command(2, 1),
+            sprite_command(2, 2),
+        ];
+
+        let batches = build_vertex_batches(commands, |_| (64, 64));
+
+        let summary: Vec<(TextureId, usize)> = batches
+            .iter()
+            .map(|batch| (batch.tex_id, batch.vertices.len()))
+            .collect();
+        assert_eq!(summary, vec![(1, 3 * 4), (2, 2 * 4)]);
+        // The uvs come from the texture size
+        assert_eq!(batches[0].vertices[2].uv, [0.25, 0.25]);
+    }
+
+    #[test]
+    fn commands_are_batched_by_layer_then_texture() {
+        let commands = vec![
+            quad_command(1, 2, 0.0),
+            quad_command(0, 1, 1.0),
+            quad_command(1, 1, 2.0),
+            quad_command(0, 2, 3.0),
+            quad_command(0, 1, 4.0),
+            quad_command(1, 2, 5.0),
+        ];
+
+        let batches = batch_commands(commands);
+
+        let summary: Vec<(u8, TextureId, Vec<f32>)> = batches
+            .iter()
+            .map(|(key, commands)| {
+                assert!(commands.iter().all(|command| command.key() == *key));
+                let xs = commands
+                    .iter()
+                    .map(|command| match command.data {
+                        Renderable::Quad { bl, .. } => bl.0,
+                        _ => unreachable!(),
+                    })
+                    .collect();
+                (commands[0].layer, commands[0].tex_id, xs)
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, 1, vec![1.0, 4.0]),
+                (0, 2, vec![3.0]),
+                (1, 1, vec![2.0]),
+                (1, 2, vec![0.0, 5.0]),
+            ]
+        );
+    }
+}
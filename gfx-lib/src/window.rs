@@ -1,68 +1,422 @@
-use crate::{input::InputState, renderer::Renderer};
+use crate::{
+    input::{GamepadButton, InputState, VirtualKeyCode},
+    renderer::Renderer,
+};
 use ::winit::{
     dpi::LogicalSize,
     event::Event as WinitEvent,
     event::WindowEvent as WinitWindowEvent,
     event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    monitor::MonitorHandle,
+    window::{Fullscreen, WindowBuilder},
+};
+use gilrs::{Axis, Button, EventType as GamepadEventType, Gilrs};
+use log::{debug, error, info, warn};
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
-use std::time::{Duration, Instant};
 
 pub use ::winit::window::Window as WinitWindow;
 
-const SIXTY_FPS_DT: f64 = 1.0 / 60.0;
+pub const DEFAULT_TICK_RATE: u32 = 60;
+pub const DEFAULT_MAX_TICKS_PER_FRAME: u32 = 5;
+pub const DEFAULT_FULLSCREEN_KEY: VirtualKeyCode = VirtualKeyCode::F11;
+// Frame times within this fraction of the target dt are snapped to it exactly
+pub const FRAME_TIME_SNAP_TOLERANCE: f64 = 0.1;
+
+// How the game's base resolution is scaled up to fill the window
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalingMode {
+    // Scales up as far as the window allows, keeping the aspect ratio
+    Stretch,
+    // Scales up by the largest whole number that fits, so pixel art stays crisp. The rest is letterboxed.
+    IntegerNearest,
+}
+
+// Largest whole number the base size can be scaled up by and still fit in the window.
+// This is never less than 1, even if the window is smaller than the base size.
+pub fn integer_scale(
+    base_width: u32,
+    base_height: u32,
+    window_width: u32,
+    window_height: u32,
+) -> u32 {
+    if base_width == 0 || base_height == 0 {
+        return 1;
+    }
+
+    (window_width / base_width)
+        .min(window_height / base_height)
+        .max(1)
+}
+
+pub struct RunConfig {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    pub render_scale: f32,
+    // Number of simulation ticks per second. Every tick is passed the same fixed dt (1 / tick_rate),
+    // which is what keeps the physics simulation deterministic, so this should not change while running.
+    pub tick_rate: u32,
+    // Upper limit on ticks run in a single frame. Any time beyond that is dropped, so a long stall
+    // (like sitting at a breakpoint) can't leave the loop trying to catch up forever.
+    pub max_ticks_per_frame: u32,
+    // Key that switches between windowed and borderless fullscreen, if any
+    pub fullscreen_key: Option<VirtualKeyCode>,
+    // Appends the current fps to the window title. Off by default, so release builds keep a clean title.
+    pub show_fps_in_title: bool,
+    pub scaling_mode: ScalingMode,
+    // Stops ticking while the window is unfocused. Off by default, so the simulation keeps running in the background.
+    pub pause_when_unfocused: bool,
+}
+
+impl RunConfig {
+    pub fn new(title: &str, width: u32, height: u32, render_scale: f32) -> Self {
+        RunConfig {
+            title: title.to_string(),
+            width,
+            height,
+            render_scale,
+            tick_rate: DEFAULT_TICK_RATE,
+            max_ticks_per_frame: DEFAULT_MAX_TICKS_PER_FRAME,
+            fullscreen_key: Some(DEFAULT_FULLSCREEN_KEY),
+            show_fps_in_title: false,
+            scaling_mode: ScalingMode::Stretch,
+            pause_when_unfocused: false,
+        }
+    }
+
+    pub fn target_dt(&self) -> f64 {
+        1.0 / self.tick_rate as f64
+    }
+}
 
 pub struct WindowState {
+    // Frames rendered, and simulation ticks run, over the last second
     pub fps: u32,
+    pub tps: u32,
     pub window_scale: f32,
     pub dpi_scale_factor: f32,
+    // Current inner size of the window, in physical pixels
+    pub width: u32,
+    pub height: u32,
+    pub scaling_mode: ScalingMode,
+    pub focused: bool,
+    // Set by the tick callback to grab and hide the cursor. It's always released while the window is unfocused.
+    pub cursor_grabbed: bool,
+}
+
+impl WindowState {
+    // Size of the space that render commands are drawn in
+    pub fn render_size(&self) -> (f32, f32) {
+        let scale = self.pixels_per_render_unit();
+        (self.width as f32 / scale, self.height as f32 / scale)
+    }
+
+    // Physical pixels covered by one unit of render space
+    pub fn pixels_per_render_unit(&self) -> f32 {
+        self.dpi_scale_factor * self.window_scale
+    }
 }
 
 pub type DeltaTime = f64;
 
+// Measured frame times jitter a little around the display refresh interval, which slowly drifts the accumulator
+// and makes ticks land unevenly between frames. Frame times close to the target dt are snapped to it.
+// Both times are in seconds.
+pub fn snap_frame_time(frame_time: f64, target_dt: f64) -> f64 {
+    if (frame_time - target_dt).abs() <= target_dt * FRAME_TIME_SNAP_TOLERANCE {
+        target_dt
+    } else {
+        frame_time
+    }
+}
+
+// Adds the frame time to the accumulator and returns how many fixed ticks should be run for it,
+// consuming that time from the accumulator. If more than max_ticks are due, the extra time is discarded.
+pub fn ticks_for_frame(
+    accumulator: &mut f64,
+    frame_time: f64,
+    target_dt: f64,
+    max_ticks: u32,
+) -> u32 {
+    *accumulator += frame_time;
+
+    let due_ticks = (*accumulator / target_dt).floor() as u32;
+    let ticks = due_ticks.min(max_ticks);
+    if due_ticks > max_ticks {
+        warn_falling_behind(due_ticks - max_ticks);
+        *accumulator %= target_dt;
+    } else {
+        *accumulator -= ticks as f64 * target_dt;
+    }
+
+    ticks
+}
+
+// Wall clock second of the last falling behind warning. A slow machine falls behind on every frame, so this keeps
+// the warning down to one a second.
+static LAST_FALLING_BEHIND_WARNING_SECS: AtomicU64 = AtomicU64::new(0);
+
+fn warn_falling_behind(skipped_ticks: u32) {
+    let now_secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or(0);
+    if LAST_FALLING_BEHIND_WARNING_SECS.swap(now_secs, Ordering::Relaxed) != now_secs {
+        warn!(
+            target: "window",
+            "[Window] Simulation is falling behind! Skipping {} ticks.",
+            skipped_ticks
+        );
+    }
+}
+
+// Like ticks_for_frame, but no ticks run while the simulation is suspended. The accumulator is emptied instead of
+// building up, so there is no burst of catch-up ticks once it resumes.
+pub fn ticks_for_frame_unless_suspended(
+    accumulator: &mut f64,
+    frame_time: f64,
+    target_dt: f64,
+    max_ticks: u32,
+    suspended: bool,
+) -> u32 {
+    if suspended {
+        *accumulator = 0.0;
+        return 0;
+    }
+
+    ticks_for_frame(accumulator, frame_time, target_dt, max_ticks)
+}
+
+// Counts ticks and rendered frames separately, since several ticks (or none) can run per frame.
+// The counts are reported once every second.
+pub struct RateCounter {
+    tick_count: u32,
+    frame_count: u32,
+    timer: Duration,
+    pub tps: u32,
+    pub fps: u32,
+}
+
+impl RateCounter {
+    pub fn new() -> Self {
+        RateCounter {
+            tick_count: 0,
+            frame_count: 0,
+            timer: Duration::from_secs(0),
+            tps: 0,
+            fps: 0,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        self.tick_count += 1;
+    }
+
+    pub fn frame(&mut self) {
+        self.frame_count += 1;
+    }
+
+    // Advances the timer. Returns true if a second has passed, and tps/fps were updated.
+    pub fn update(&mut self, elapsed: Duration) -> bool {
+        self.timer += elapsed;
+        if self.timer < Duration::from_secs(1) {
+            return false;
+        }
+
+        self.timer = Duration::from_secs(0);
+        self.tps = self.tick_count;
+        self.fps = self.frame_count;
+        self.tick_count = 0;
+        self.frame_count = 0;
+
+        true
+    }
+}
+
+// The window title with the fps appended. This always starts from the base title, so nothing accumulates.
+pub fn fps_title(base_title: &str, fps: u32) -> String {
+    format!("{} - {} FPS", base_title, fps)
+}
+
+// The fullscreen mode to switch to when fullscreen is toggled, given the current mode.
+pub fn toggled_fullscreen(
+    current: Option<Fullscreen>,
+    monitor: Option<MonitorHandle>,
+) -> Option<Fullscreen> {
+    fullscreen_monitor(current.is_some(), monitor).map(Fullscreen::Borderless)
+}
+
+// The monitor to fill when fullscreen is toggled, or None to go back to windowed.
+// Going fullscreen needs a monitor to fill, so this stays windowed if there isn't one.
+pub fn fullscreen_monitor<M>(is_fullscreen: bool, monitor: Option<M>) -> Option<M> {
+    if is_fullscreen {
+        None
+    } else {
+        monitor
+    }
+}
+
+// Not every platform can grab the cursor. If it can't, the cursor is still hidden, and the game carries on.
+fn set_cursor_grab(window: &WinitWindow, grab: bool) {
+    if let Err(e) = window.set_cursor_grab(grab) {
+        warn!(
+            target: "window",
+            "[Window] Failed to set cursor grab to {}! Error: {}",
+            grab, e
+        );
+    }
+
+    window.set_cursor_visible(!grab);
+}
+
+fn toggle_fullscreen(window: &WinitWindow) {
+    // Prefer the monitor the window is on, falling back to any monitor that is available
+    let current_monitor = window.current_monitor();
+    let monitor = window
+        .available_monitors()
+        .find(|monitor| *monitor == current_monitor)
+        .or_else(|| window.available_monitors().next());
+
+    let was_fullscreen = window.fullscreen().is_some();
+    let fullscreen = toggled_fullscreen(window.fullscreen(), monitor);
+    if !was_fullscreen && fullscreen.is_none() {
+        warn!(target: "window", "[Window] Failed to go fullscreen! No monitor could be found.");
+        return;
+    }
+
+    debug!(target: "window", "[Window] Fullscreen = {}", fullscreen.is_some());
+
+    // The window gets a Resized event for its new size, which resizes the renderer
+    window.set_fullscreen(fullscreen);
+}
+
+fn gamepad_button(button: Button) -> Option<GamepadButton> {
+    match button {
+        Button::South => Some(GamepadButton::South),
+        Button::East => Some(GamepadButton::East),
+        Button::North => Some(GamepadButton::North),
+        Button::West => Some(GamepadButton::West),
+        Button::DPadUp => Some(GamepadButton::DPadUp),
+        Button::DPadDown => Some(GamepadButton::DPadDown),
+        Button::DPadLeft => Some(GamepadButton::DPadLeft),
+        Button::DPadRight => Some(GamepadButton::DPadRight),
+        Button::Start => Some(GamepadButton::Start),
+        Button::Select => Some(GamepadButton::Select),
+        _ => None,
+    }
+}
+
+// Feeds any gamepad events since the last frame into the input state
+fn poll_gamepads(gilrs: &mut Gilrs, input_state: &mut InputState) {
+    while let Some(event) = gilrs.next_event() {
+        match event.event {
+            GamepadEventType::ButtonPressed(button, _) => {
+                if let Some(button) = gamepad_button(button) {
+                    input_state.handle_gamepad_button(button, true);
+                }
+            }
+            GamepadEventType::ButtonReleased(button, _) => {
+                if let Some(button) = gamepad_button(button) {
+                    input_state.handle_gamepad_button(button, false);
+                }
+            }
+            GamepadEventType::AxisChanged(Axis::LeftStickX, value, _) => {
+                input_state.handle_gamepad_axis_x(value);
+            }
+            GamepadEventType::Connected => {
+                info!(
+                    target: "window",
+                    "[Window] Gamepad connected: {}",
+                    gilrs.gamepad(event.id).name()
+                );
+            }
+            GamepadEventType::Disconnected => {
+                info!(target: "window", "[Window] Gamepad disconnected");
+                input_state.clear_gamepad();
+            }
+            _ => {}
+        }
+    }
+}
+
 pub fn run<T>(
-    title: &str,
-    width: u32,
-    height: u32,
-    render_scale: f32,
+    config: RunConfig,
     app_state: T,
     init_callback: impl FnMut(&mut T, &mut Renderer) + 'static,
-    tick_callback: impl FnMut(&mut T, &WindowState, &InputState, DeltaTime) + 'static,
-    render_callback: impl FnMut(&T, u128, f64, &WindowState, &mut Renderer) + 'static,
+    tick_callback: impl FnMut(&mut T, &mut WindowState, &InputState, DeltaTime) + 'static,
+    render_callback: impl FnMut(&mut T, u128, f64, &WindowState, &mut Renderer) + 'static,
 ) where
     T: 'static,
 {
+    assert!(
+        config.tick_rate > 0,
+        "RunConfig tick_rate must be greater than 0!"
+    );
+
+    let render_scale = config.render_scale;
     let event_loop = EventLoop::new();
     let window_size = LogicalSize::new(
-        (width as f32 * render_scale) as u32,
-        (height as f32 * render_scale) as u32,
+        (config.width as f32 * render_scale) as u32,
+        (config.height as f32 * render_scale) as u32,
     );
     let window: WinitWindow = WindowBuilder::new()
-        .with_title(title)
+        .with_title(&config.title)
         .with_min_inner_size(window_size)
         .with_inner_size(window_size)
         .with_resizable(false)
         .build(&event_loop)
-        .expect("Failed to create window!");
+        .unwrap_or_else(|e| {
+            error!(target: "window", "[Window] Failed to create window! Error: {}", e);
+            std::process::exit(1);
+        });
 
     let mut init_callback = Box::new(init_callback);
     let mut tick_callback = Box::new(tick_callback);
     let mut render_callback = Box::new(render_callback);
 
     let mut app_state: T = app_state;
-    let mut renderer: Renderer = Renderer::new(&window, render_scale);
+    let mut renderer: Renderer = match Renderer::new(&window, render_scale) {
+        Ok(renderer) => renderer,
+        Err(e) => {
+            error!(target: "window", "[Window] Failed to initialize the renderer! Error: {}", e);
+            std::process::exit(1);
+        }
+    };
     let mut input_state: InputState = InputState::new();
+
+    // Gamepads are optional, so the game still runs if they can't be used at all
+    let mut gilrs: Option<Gilrs> = match Gilrs::new() {
+        Ok(gilrs) => Some(gilrs),
+        Err(e) => {
+            warn!(
+                target: "window",
+                "[Window] Failed to initialize gamepad support! Error: {}",
+                e
+            );
+            None
+        }
+    };
+
+    let inner_size = window.inner_size();
     let mut window_state = WindowState {
         fps: 0,
+        tps: 0,
         window_scale: render_scale,
         dpi_scale_factor: window.scale_factor() as f32,
+        width: inner_size.width,
+        height: inner_size.height,
+        scaling_mode: config.scaling_mode,
+        focused: true,
+        cursor_grabbed: false,
     };
+    let mut cursor_grab_applied = false;
 
-    let one_second: Duration = Duration::from_secs(1);
-    let mut fps_timer: Duration = Duration::from_secs(0);
-    let mut fps_counter: u32 = 0;
+    let mut rate_counter = RateCounter::new();
 
-    let target_dt: f64 = SIXTY_FPS_DT;
+    let target_dt: f64 = config.target_dt();
     let mut time: f64 = 0.0;
     let mut current_time = Instant::now();
     let mut accumulator: f64 = 0.0;
@@ -71,7 +425,10 @@ pub fn run<T>(
     let mut ticks: u128 = 0;
 
     init_callback(&mut app_state, &mut renderer);
-    renderer.rebuild_swapchain();
+    if let Err(e) = renderer.rebuild_swapchain() {
+        error!(target: "window", "[Window] Failed to initialize the renderer! Error: {}", e);
+        std::process::exit(1);
+    }
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
@@ -84,22 +441,31 @@ pub fn run<T>(
                     }
                 }
                 WinitWindowEvent::Resized(size) => {
-                    println!("[Window] Resized to ({}, {})", size.width, size.height);
+                    debug!(target: "window", "[Window] Resized to ({}, {})", size.width, size.height);
 
-                    renderer.resize(size.width, size.height);
+                    window_state.width = size.width;
+                    window_state.height = size.height;
+                    if let Err(e) = renderer.resize(size.width, size.height) {
+                        error!(target: "window", "[Window] Failed to resize the renderer! Error: {}", e);
+                    }
                     window.request_redraw();
                 }
                 WinitWindowEvent::ScaleFactorChanged {
                     scale_factor,
                     new_inner_size,
                 } => {
-                    println!(
+                    debug!(
+                        target: "window",
                         "[Window] Scale factor changed to {}. New inner size = {:?}",
                         scale_factor, new_inner_size
                     );
 
                     window_state.dpi_scale_factor = scale_factor as f32;
-                    renderer.resize(new_inner_size.width, new_inner_size.height);
+                    window_state.width = new_inner_size.width;
+                    window_state.height = new_inner_size.height;
+                    if let Err(e) = renderer.resize(new_inner_size.width, new_inner_size.height) {
+                        error!(target: "window", "[Window] Failed to resize the renderer! Error: {}", e);
+                    }
                     window.request_redraw();
                 }
                 WinitWindowEvent::KeyboardInput {
@@ -116,6 +482,17 @@ pub fn run<T>(
 
                     input_state.handle_keyboard_input(&input);
                 }
+                WinitWindowEvent::Focused(focused) => {
+                    debug!(target: "window", "[Window] Focused = {}", focused);
+                    window_state.focused = focused;
+                }
+                WinitWindowEvent::CursorMoved { position, .. } => {
+                    // Convert from physical pixels to render space, so the position lines up with what is drawn.
+                    // This is recomputed from the current scales on every move, so it stays correct after a resize.
+                    let scale = window_state.dpi_scale_factor * window_state.window_scale;
+                    input_state
+                        .handle_cursor_moved(position.x as f32 / scale, position.y as f32 / scale);
+                }
                 _ => {}
             },
             WinitEvent::MainEventsCleared => {
@@ -124,30 +501,176 @@ pub fn run<T>(
                 frame_time = frame_time.min(std::time::Duration::from_secs_f64(0.1));
                 current_time = new_time;
 
-                let dt = frame_time.as_secs_f64();
-                accumulator += dt;
-                while accumulator >= target_dt {
-                    tick_callback(&mut app_state, &window_state, &input_state, dt);
+                if let Some(gilrs) = gilrs.as_mut() {
+                    poll_gamepads(gilrs, &mut input_state);
+                }
+
+                let ticks_this_frame = ticks_for_frame_unless_suspended(
+                    &mut accumulator,
+                    snap_frame_time(frame_time.as_secs_f64(), target_dt),
+                    target_dt,
+                    config.max_ticks_per_frame,
+                    config.pause_when_unfocused && !window_state.focused,
+                );
+                for _ in 0..ticks_this_frame {
+                    if let Some(fullscreen_key) = config.fullscreen_key {
+                        if input_state.was_pressed(fullscreen_key) {
+                            toggle_fullscreen(&window);
+                        }
+                    }
+
+                    tick_callback(&mut app_state, &mut window_state, &input_state, target_dt);
                     input_state.clear_pressed_and_released();
 
-                    accumulator -= target_dt;
                     time += target_dt;
                     ticks += 1;
-                    fps_counter += 1;
+                    rate_counter.tick();
                 }
 
-                fps_timer = fps_timer + frame_time;
-                if fps_timer >= one_second {
-                    fps_timer = std::time::Duration::from_secs(0);
-                    window_state.fps = fps_counter;
-                    fps_counter = 0;
+                let wants_cursor_grab = window_state.cursor_grabbed && window_state.focused;
+                if wants_cursor_grab != cursor_grab_applied {
+                    set_cursor_grab(&window, wants_cursor_grab);
+                    cursor_grab_applied = wants_cursor_grab;
                 }
 
-                let lerp = accumulator / target_dt;
-                render_callback(&app_state, ticks, lerp, &window_state, &mut renderer);
+                if rate_counter.update(frame_time) {
+                    window_state.fps = rate_counter.fps;
+                    window_state.tps = rate_counter.tps;
+
+                    if config.show_fps_in_title {
+                        window.set_title(&fps_title(&config.title, window_state.fps));
+                    }
+                }
+
+                // Rendering happens here too, on the same thread and only after every tick for this frame is done,
+                // so the render callback never sees a half-updated world.
+                // How far we are between the last tick and the next one, for interpolating rendered positions
+                let lerp = nalgebra::clamp(accumulator / target_dt, 0.0, 1.0);
+                render_callback(&mut app_state, ticks, lerp, &window_state, &mut renderer);
+                rate_counter.frame();
                 window.request_redraw();
             }
             _ => (),
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tick_rate_sets_the_ticks_run_per_frame() {
+        for &(tick_rate, expected_ticks) in [(30, 3), (120, 12)].iter() {
+            let mut config = RunConfig::new("test", 320, 240, 1.0);
+            config.tick_rate = tick_rate;
+
+            let mut accumulator = 0.0;
+            let ticks = ticks_for_frame(&mut accumulator, 0.105, config.target_dt(), 100);
+
+            assert_eq!(ticks, expected_ticks);
+            assert!((accumulator - 0.005).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn long_frames_run_at_most_max_ticks() {
+        let target_dt = 1.0 / 60.0;
+        let mut accumulator = 0.0;
+        let ticks = ticks_for_frame(
+            &mut accumulator,
+            10.0,
+            target_dt,
+            DEFAULT_MAX_TICKS_PER_FRAME,
+        );
+
+        assert_eq!(ticks, DEFAULT_MAX_TICKS_PER_FRAME);
+        // The time that couldn't be caught up on is dropped
+        assert!(accumulator < target_dt);
+    }
+
+    #[test]
+    fn fullscreen_toggles_between_windowed_and_borderless() {
+        // Monitors can't be made up, so any value stands in for one
+        assert_eq!(fullscreen_monitor(false, Some(1)), Some(1));
+        assert_eq!(fullscreen_monitor(true, Some(1)), None);
+        assert_eq!(fullscreen_monitor::<u32>(false, None), None);
+        assert!(toggled_fullscreen(None, None).is_none());
+    }
+
+    #[test]
+    fn fps_title_keeps_the_base_title() {
+        let title = fps_title("Brick Bonker", 60);
+        assert_eq!(title, "Brick Bonker - 60 FPS");
+        assert_eq!(fps_title("Brick Bonker", 144), "Brick Bonker - 144 FPS");
+    }
+
+    #[test]
+    fn rate_counter_counts_ticks_and_frames_apart() {
+        let mut counter = RateCounter::new();
+
+        // 60 ticks and 100 frames over one second, a frame at a time
+        for frame in 0..100 {
+            if frame % 5 < 3 {
+                counter.tick();
+            }
+            counter.frame();
+
+            let is_last_frame = frame == 99;
+            assert_eq!(counter.update(Duration::from_millis(10)), is_last_frame);
+        }
+        assert_eq!(counter.tps, 60);
+        assert_eq!(counter.fps, 100);
+
+        // Both start counting again for the next second
+        counter.frame();
+        assert!(counter.update(Duration::from_secs(1)));
+        assert_eq!(counter.tps, 0);
+        assert_eq!(counter.fps, 1);
+    }
+
+    #[test]
+    fn frame_times_near_the_target_snap_to_it() {
+        let target_dt = 1.0 / 60.0;
+
+        assert_eq!(snap_frame_time(0.01666, target_dt), target_dt);
+        assert_eq!(snap_frame_time(0.033, target_dt), 0.033);
+    }
+
+    #[test]
+    fn integer_scale_is_the_largest_whole_multiple_that_fits() {
+        // The rest of the 800x600 window is letterboxed
+        assert_eq!(integer_scale(640, 480, 800, 600), 1);
+        assert_eq!(integer_scale(320, 240, 1280, 720), 3);
+        assert_eq!(integer_scale(320, 240, 1920, 1080), 4);
+        // Windows smaller than the base size still draw at 1x
+        assert_eq!(integer_scale(640, 480, 320, 240), 1);
+    }
+
+    #[test]
+    fn no_ticks_run_while_suspended_and_none_are_caught_up_after() {
+        let target_dt = 1.0 / 60.0;
+        let mut accumulator = 0.01;
+        for _ in 0..100 {
+            let ticks = ticks_for_frame_unless_suspended(
+                &mut accumulator,
+                0.5,
+                target_dt,
+                DEFAULT_MAX_TICKS_PER_FRAME,
+                true,
+            );
+            assert_eq!(ticks, 0);
+        }
+        assert_eq!(accumulator, 0.0);
+
+        let ticks = ticks_for_frame_unless_suspended(
+            &mut accumulator,
+            target_dt,
+            target_dt,
+            DEFAULT_MAX_TICKS_PER_FRAME,
+            false,
+        );
+        assert_eq!(ticks, 1);
+        assert!(accumulator < target_dt);
+    }
+}
@@ -0,0 +1,73 @@
+use gfx::renderer::TextureId;
+use log::warn;
+use std::collections::HashMap;
+
+// Textures, by name. Ids are handed out in the order textures are loaded and never change after that,
+// so gameplay code can look textures up by name instead of hardcoding their ids.
+#[derive(Default)]
+pub struct Assets {
+    textures: HashMap<String, TextureId>,
+    texture_paths: Vec<(TextureId, String)>,
+}
+
+impl Assets {
+    pub fn new() -> Self {
+        Assets::default()
+    }
+
+    // Registers the texture at the given path under the given name, and returns its id.
+    // Loading a name that was already loaded just returns the existing id.
+    pub fn load_texture(&mut self, name: &str, path: &str) -> TextureId {
+        if let Some(&id) = self.textures.get(name) {
+            return id;
+        }
+
+        // Id 0 is left for untextured quads
+        let id = self.texture_paths.len() as TextureId + 1;
+        self.textures.insert(name.to_string(), id);
+        self.texture_paths.push((id, path.to_string()));
+
+        id
+    }
+
+    // Id of the texture with the given name. Unknown names get id 0, which draws nothing.
+    pub fn texture(&self, name: &str) -> TextureId {
+        match self.textures.get(name) {
+            Some(&id) => id,
+            None => {
+                warn!(target: "assets", "[Assets] No texture named {} has been loaded!", name);
+                0
+            }
+        }
+    }
+
+    // The id and path of every loaded texture, so they can be uploaded to the renderer
+    pub fn texture_paths(&self) -> impl Iterator<Item = (TextureId, &str)> {
+        self.texture_paths
+            .iter()
+            .map(|(id, path)| (*id, path.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_name_gets_the_same_id_and_new_names_get_new_ids() {
+        let mut assets = Assets::new();
+        let bricks = assets.load_texture("bricks", "res/textures/bricks.png");
+        let paddle = assets.load_texture("paddle", "res/textures/paddle.png");
+
+        assert_eq!(
+            assets.load_texture("bricks", "res/textures/bricks.png"),
+            bricks
+        );
+        assert_eq!(assets.texture("bricks"), bricks);
+        assert_eq!(assets.texture("paddle"), paddle);
+        assert_ne!(bricks, paddle);
+        assert_ne!(bricks, 0);
+        assert_ne!(paddle, 0);
+        assert_eq!(assets.texture_paths().count(), 2);
+    }
+}
@@ -1,22 +1,178 @@
-use std::{io::{Cursor, Read}, collections::HashMap};
-use rodio::Source;
+use crate::game::{
+    ball::{BallComponent, BallLostEvent},
+    brick::{BrickComponent, ExplosionEvent},
+    level::{LevelConfig, WallComponent},
+    paddle::PlayerPaddleComponent,
+    physics::CollisionEvent,
+};
+use log::warn;
+use rodio::{source::ChannelVolume, Source};
+use shrev::EventChannel;
+use specs::prelude::*;
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read},
+};
 
-pub fn play(id: AudioAssetId, audio_db: &AudioAssetDb, is_looping: bool) {
-    let device = rodio::default_output_device().unwrap();
+pub const MUSIC_LEVEL_PATH: &str = "res/audio/tha-bounce-life.wav";
 
-    if let Some(clip) = audio_db.asset(&id).cloned() {
-        let s = rodio::Decoder::new(std::io::BufReader::new(Cursor::new(clip))).unwrap();
+pub struct AudioState {
+    // Volume multiplier applied to every sound effect, from 0.0 (silent) to 1.0 (full volume)
+    pub master_volume: f32,
+    // Music volume, independent of master_volume
+    music_volume: f32,
+    music_sink: Option<rodio::Sink>,
+    is_device_available: bool,
+}
+
+impl AudioState {
+    pub fn new() -> Self {
+        let is_device_available = rodio::default_output_device().is_some();
+        if !is_device_available {
+            warn!(target: "audio", "[AudioState] No audio output device found! Audio will be disabled.");
+        }
+
+        AudioState {
+            master_volume: 1.0,
+            music_volume: 1.0,
+            music_sink: None,
+            is_device_available,
+        }
+    }
+
+    // Streams the music track at the given path, stopping whatever track was playing before.
+    pub fn play_music(&mut self, path: &str, is_looping: bool) {
+        self.stop_music();
+
+        if !self.is_device_available {
+            return;
+        }
+
+        let device = match rodio::default_output_device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let source = match std::fs::File::open(path)
+            .map_err(|e| e.to_string())
+            .and_then(|file| {
+                rodio::Decoder::new(std::io::BufReader::new(file)).map_err(|e| e.to_string())
+            }) {
+            Ok(source) => source,
+            Err(e) => {
+                warn!(target: "audio", "Failed to play music {}! Error: {}", path, e);
+                return;
+            }
+        };
+
+        let sink = rodio::Sink::new(&device);
+        sink.set_volume(self.music_volume);
         if is_looping {
-            rodio::play_raw(&device, s.convert_samples().repeat_infinite());
+            sink.append(source.repeat_infinite());
+        } else {
+            sink.append(source);
+        }
+
+        self.music_sink = Some(sink);
+    }
+
+    pub fn stop_music(&mut self) {
+        if let Some(sink) = self.music_sink.take() {
+            sink.stop();
+        }
+    }
+
+    pub fn is_music_playing(&self) -> bool {
+        self.music_sink.is_some()
+    }
+
+    pub fn music_volume(&self) -> f32 {
+        self.music_volume
+    }
+
+    pub fn set_music_volume(&mut self, volume: f32) {
+        self.music_volume = nalgebra::clamp(volume, 0.0, 1.0);
+        if let Some(sink) = self.music_sink.as_ref() {
+            sink.set_volume(self.music_volume);
+        }
+    }
+
+    // Starts playing the given clip in the background and returns right away.
+    pub fn play(&self, id: AudioAssetId, audio_db: &AudioAssetDb, is_looping: bool) {
+        if !self.is_device_available {
+            return;
+        }
+
+        let device = match rodio::default_output_device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        if let Some(clip) = audio_db.asset(&id).cloned() {
+            let s = match rodio::Decoder::new(std::io::BufReader::new(Cursor::new(clip))) {
+                Ok(s) => s.convert_samples::<f32>().amplify(self.master_volume),
+                Err(e) => {
+                    warn!(target: "audio", "Failed to decode audio asset with id {:?}! Error: {}", id, e);
+                    return;
+                }
+            };
+
+            if is_looping {
+                rodio::play_raw(&device, s.repeat_infinite());
+            } else {
+                rodio::play_raw(&device, s);
+            }
+        } else {
+            warn!(target: "audio", "Failed to play audio file! Audio asset with id {:?} did not exist!", id);
+        }
+    }
+
+    // Plays the given clip once, panned between the left (-1.0) and right (1.0) speakers.
+    pub fn play_panned(&self, id: AudioAssetId, audio_db: &AudioAssetDb, pan: f32) {
+        if !self.is_device_available {
+            return;
+        }
+
+        let device = match rodio::default_output_device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        if let Some(clip) = audio_db.asset(&id).cloned() {
+            let s = match rodio::Decoder::new(std::io::BufReader::new(Cursor::new(clip))) {
+                Ok(s) => s.convert_samples::<f32>().amplify(self.master_volume),
+                Err(e) => {
+                    warn!(target: "audio", "Failed to decode audio asset with id {:?}! Error: {}", id, e);
+                    return;
+                }
+            };
+
+            let (left, right) = pan_volumes(pan);
+            rodio::play_raw(&device, ChannelVolume::new(s, vec![left, right]));
         } else {
-            rodio::play_raw(&device, s.convert_samples());
+            warn!(target: "audio", "Failed to play audio file! Audio asset with id {:?} did not exist!", id);
         }
-    } else {
-        eprintln!("Failed to play audio file! Audio asset with id {:?} did not exist!", id);
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+// Maps a horizontal position in the play field to a pan value, from -1.0 (left) to 1.0 (right).
+// Positions outside of the field are clamped to the nearest edge.
+pub fn impact_pan(x: f64, field_width: f64) -> f32 {
+    if field_width <= 0.0 {
+        return 0.0;
+    }
+
+    nalgebra::clamp((x / field_width) * 2.0 - 1.0, -1.0, 1.0) as f32
+}
+
+// Left and right channel volumes for the given pan value. This is an equal power pan, so sounds
+// don't get quieter in the middle.
+pub fn pan_volumes(pan: f32) -> (f32, f32) {
+    let angle = (nalgebra::clamp(pan, -1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+    (angle.cos(), angle.sin())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum AudioAssetId {
     MusicBackground = 0,
     SfxBallBounce0 = 1,
@@ -28,6 +184,51 @@ pub enum AudioAssetId {
     SfxBallDeath0 = 7,
 }
 
+// What a ball hit, which decides the sound it makes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImpactKind {
+    Wall,
+    Paddle,
+    BrickHit,
+    BrickBreak,
+}
+
+impl ImpactKind {
+    // Works out what the given entity is. Anything else makes no sound.
+    // This runs after the BrickSystem, so a brick with no hp left was just broken by this hit.
+    pub fn classify(
+        ent: Entity,
+        walls: &ReadStorage<WallComponent>,
+        paddles: &ReadStorage<PlayerPaddleComponent>,
+        bricks: &ReadStorage<BrickComponent>,
+    ) -> Option<ImpactKind> {
+        if let Some(brick) = bricks.get(ent) {
+            if !brick.indestructible && brick.hp <= 0 {
+                Some(ImpactKind::BrickBreak)
+            } else {
+                Some(ImpactKind::BrickHit)
+            }
+        } else if paddles.get(ent).is_some() {
+            Some(ImpactKind::Paddle)
+        } else if walls.get(ent).is_some() {
+            Some(ImpactKind::Wall)
+        } else {
+            None
+        }
+    }
+
+    // The clips to pick from when a ball hits this
+    pub fn sounds(self) -> &'static [AudioAssetId] {
+        match self {
+            ImpactKind::Wall | ImpactKind::BrickHit => {
+                &[AudioAssetId::SfxBallWallHit0, AudioAssetId::SfxBallWallHit1]
+            }
+            ImpactKind::Paddle => &[AudioAssetId::SfxBallBounce0, AudioAssetId::SfxBallBounce1],
+            ImpactKind::BrickBreak => &[AudioAssetId::SfxBrickBreak0, AudioAssetId::SfxBrickBreak1],
+        }
+    }
+}
+
 pub struct AudioAssetDb {
     assets: HashMap<AudioAssetId, Vec<u8>>,
 }
@@ -53,3 +254,214 @@ impl AudioAssetDb {
         self.assets.get(id)
     }
 }
+
+// Plays the sounds for balls hitting things, for explosions, and for lost balls
+#[derive(Default)]
+pub struct AudioSystem {
+    collision_event_reader: Option<ReaderId<CollisionEvent>>,
+    explosion_event_reader: Option<ReaderId<ExplosionEvent>>,
+    ball_lost_event_reader: Option<ReaderId<BallLostEvent>>,
+}
+
+impl<'a> System<'a> for AudioSystem {
+    type SystemData = (
+        ReadExpect<'a, AudioState>,
+        ReadExpect<'a, AudioAssetDb>,
+        ReadExpect<'a, LevelConfig>,
+        Read<'a, EventChannel<CollisionEvent>>,
+        Read<'a, EventChannel<ExplosionEvent>>,
+        Read<'a, EventChannel<BallLostEvent>>,
+        ReadStorage<'a, WallComponent>,
+        ReadStorage<'a, PlayerPaddleComponent>,
+        ReadStorage<'a, BrickComponent>,
+        ReadStorage<'a, BallComponent>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.collision_event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<CollisionEvent>>()
+                .register_reader(),
+        );
+        self.explosion_event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<ExplosionEvent>>()
+                .register_reader(),
+        );
+        self.ball_lost_event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<BallLostEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(
+        &mut self,
+        (
+            audio,
+            audio_db,
+            config,
+            collision_events,
+            explosion_events,
+            ball_lost_events,
+            walls,
+            paddles,
+            bricks,
+            balls,
+        ): Self::SystemData,
+    ) {
+        let field_width = config.field_width as f64;
+
+        // Explosions all go off at once, so one sound from their average position is enough
+        let explosion_xs: Vec<f64> = explosion_events
+            .read(&mut self.explosion_event_reader.as_mut().unwrap())
+            .map(|event| event.position.x)
+            .collect();
+        if !explosion_xs.is_empty() {
+            let x = explosion_xs.iter().sum::<f64>() / explosion_xs.len() as f64;
+            audio.play_panned(AudioAssetId::SfxBrickBreak0, &audio_db, impact_pan(x, field_width));
+        }
+
+        for event in ball_lost_events.read(&mut self.ball_lost_event_reader.as_mut().unwrap()) {
+            let pan = impact_pan(event.position.x, field_width);
+            audio.play_panned(AudioAssetId::SfxBallDeath0, &audio_db, pan);
+        }
+
+        for event in collision_events.read(&mut self.collision_event_reader.as_mut().unwrap()) {
+            let (entity_a, entity_b) = match (event.entity_a, event.entity_b) {
+                (Some(a), Some(b)) => (a, b),
+                _ => continue,
+            };
+
+            if balls.get(entity_a).is_none() {
+                continue;
+            }
+
+            let kind = match ImpactKind::classify(entity_b, &walls, &paddles, &bricks) {
+                Some(kind) => kind,
+                None => continue,
+            };
+
+            // Pick one of the clips at random, so repeated hits don't all sound the same
+            let clip_id = {
+                use rand::seq::SliceRandom;
+                match kind.sounds().choose(&mut rand::thread_rng()) {
+                    Some(&clip_id) => clip_id,
+                    None => continue,
+                }
+            };
+
+            // Impacts with no contact point play centered
+            let pan = event
+                .collision_point
+                .map(|point| impact_pan(point.x, field_width))
+                .unwrap_or(0.0);
+
+            audio.play_panned(clip_id, &audio_db, pan);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(world: &World, ent: Entity) -> Option<ImpactKind> {
+        ImpactKind::classify(
+            ent,
+            &world.read_storage(),
+            &world.read_storage(),
+            &world.read_storage(),
+        )
+    }
+
+    #[test]
+    fn breaking_a_brick_plays_a_break_sound() {
+        let mut world = World::new();
+        world.register::<WallComponent>();
+        world.register::<PlayerPaddleComponent>();
+        world.register::<BrickComponent>();
+
+        // The BrickSystem has already taken the last hp from a brick the ball just broke
+        let broken_ent = world
+            .create_entity()
+            .with(BrickComponent::new(0, 100))
+            .build();
+        let damaged_ent = world
+            .create_entity()
+            .with(BrickComponent::new(1, 100))
+            .build();
+
+        assert_eq!(classify(&world, broken_ent), Some(ImpactKind::BrickBreak));
+        assert_eq!(
+            ImpactKind::BrickBreak.sounds(),
+            &[AudioAssetId::SfxBrickBreak0, AudioAssetId::SfxBrickBreak1]
+        );
+        assert_eq!(classify(&world, damaged_ent), Some(ImpactKind::BrickHit));
+    }
+
+    #[test]
+    fn music_volume_is_stored_apart_from_the_sfx_volume() {
+        let mut audio = AudioState::new();
+        audio.set_music_volume(0.25);
+        assert_eq!(audio.music_volume(), 0.25);
+        assert_eq!(audio.master_volume, 1.0);
+
+        audio.set_music_volume(1.5);
+        assert_eq!(audio.music_volume(), 1.0);
+    }
+
+    #[test]
+    fn stopping_the_music_drops_the_track() {
+        // Without an audio device nothing starts playing, but stopping has to work either way
+        let mut audio = AudioState::new();
+        audio.play_music(MUSIC_LEVEL_PATH, true);
+        audio.stop_music();
+        assert!(!audio.is_music_playing());
+    }
+
+    #[test]
+    fn impacts_are_panned_by_their_x_position() {
+        let field_width = 320.0;
+
+        assert_eq!(impact_pan(0.0, field_width), -1.0);
+        assert_eq!(impact_pan(80.0, field_width), -0.5);
+        assert_eq!(impact_pan(160.0, field_width), 0.0);
+        assert_eq!(impact_pan(320.0, field_width), 1.0);
+        assert_eq!(impact_pan(-50.0, field_width), -1.0);
+        assert_eq!(impact_pan(400.0, field_width), 1.0);
+
+        // Centered sounds are even, and far left ones are (almost) only in the left channel
+        let (left, right) = pan_volumes(0.0);
+        assert!((left - right).abs() < 1e-6);
+        let (left, right) = pan_volumes(impact_pan(8.0, field_width));
+        assert!(left > 0.99 && right < 0.1);
+    }
+
+    #[test]
+    fn walls_and_paddles_each_have_their_own_sounds() {
+        let mut world = World::new();
+        world.register::<WallComponent>();
+        world.register::<PlayerPaddleComponent>();
+        world.register::<BrickComponent>();
+        let wall_ent = world.create_entity().with(WallComponent).build();
+        let paddle_ent = world
+            .create_entity()
+            .with(PlayerPaddleComponent::new())
+            .build();
+        let unknown_ent = world.create_entity().build();
+
+        assert_eq!(classify(&world, wall_ent), Some(ImpactKind::Wall));
+        assert_eq!(
+            ImpactKind::Wall.sounds(),
+            &[AudioAssetId::SfxBallWallHit0, AudioAssetId::SfxBallWallHit1]
+        );
+        assert_eq!(classify(&world, paddle_ent), Some(ImpactKind::Paddle));
+        assert_eq!(
+            ImpactKind::Paddle.sounds(),
+            &[AudioAssetId::SfxBallBounce0, AudioAssetId::SfxBallBounce1]
+        );
+        assert_eq!(classify(&world, unknown_ent), None);
+    }
+}
@@ -1,28 +1,115 @@
 use crate::game::{
+    assets::Assets,
     brick::BrickComponent,
-    paddle::{PlayerPaddleComponent, PADDLE_HIT_BOX_WIDTH},
-    physics::{ColliderComponent, CollisionEvent, RigidbodyComponent},
+    collision::{self, CollisionSettings},
+    paddle::{PlayerId, PlayerPaddleComponent},
+    physics::{
+        ColliderComponent, CollisionEvent, ProximityEvent, ProximityType, RigidbodyComponent,
+    },
     render::SpriteComponent,
+    trail::{BallTrailComponent, BALL_TRAIL_DEFAULT_LENGTH},
     transform::TransformComponent,
-    audio::{self, AudioAssetId, AudioAssetDb},
-    LevelState, Point2f, Vector2d, Vector2f,
+    level::{BottomWallComponent, GamePhase},
+    rng::RngResource,
+    Point2f, TimeScale, Vector2d, Vector2f,
 };
 use gfx::{color::*, renderer::Transparency, sprite::SpriteRegion};
+use log::{debug, warn};
 use nalgebra::Vector2;
 use ncollide2d::shape::Ball;
 use nphysics2d::{math::Velocity, object::BodyStatus};
 use shrev::EventChannel;
 use specs::prelude::*;
 
-pub const BALL_COLLIDER_RADIUS: f64 = 3.0;
+// Radius of a ball, in pixels. Both the collider and the sprite are sized from this.
+pub const BALL_DEFAULT_RADIUS: f64 = 3.0;
+// Radius of the ball drawn on the spritesheet, at a scale of 1
+pub const BALL_SPRITE_RADIUS: f64 = 3.0;
+// In world units per second. Balls have CCD, but it isn't reliable (see ColliderComponent), so a ball should still
+// move less than the thinnest collider plus its own diameter in one step. The paddle is the thinnest,
+// at 9px, so that is 15px (~0.47 units) per step, or ~28 units/s at 60 ticks per second.
+// This cap moves 8px per step, which leaves room for time scales up to ~1.8.
 pub const BALL_MAX_LINEAR_VELOCITY: f64 = 15.0;
 pub const BALL_DEFAULT_FORCE: f64 = 6.0;
+pub const BALL_DEFAULT_MIN_SPEED: f64 = 4.0;
+pub const MAX_ACTIVE_BALLS: usize = 10;
+pub const BALL_DEFAULT_MIN_VERTICAL_ANGLE: f64 = 15.0 * (std::f64::consts::PI / 180.0);
+pub const BALL_DEFAULT_STALL_NUDGE_ANGLE: f64 = 5.0 * (std::f64::consts::PI / 180.0);
+pub const BALL_SPIN_DEFAULT_CURVE_STRENGTH: f64 = 0.01;
+pub const BALL_SPIN_DEFAULT_DECAY: f64 = 0.98;
+pub const BALL_SPIN_DEFAULT_PADDLE_FACTOR: f64 = 0.1;
+pub const BALL_MAX_SPIN: f64 = 1.0;
+pub const BALL_DEFAULT_SPAWN_PROTECTION_TICKS: u32 = 10;
 
+// Controls how much spin balls pick up from the paddle, and how much it curves them.
+// Spin is off by default, for the classic feel.
+pub struct BallSpinSettings {
+    pub enabled: bool,
+    // Sideways acceleration per tick, per unit of spin and speed
+    pub curve_strength: f64,
+    // Multiplier applied to the spin every tick
+    pub decay: f64,
+    // Spin added per pixel of tangential velocity between the paddle and the ball
+    pub paddle_factor: f64,
+}
+
+impl Default for BallSpinSettings {
+    fn default() -> Self {
+        BallSpinSettings {
+            enabled: false,
+            curve_strength: BALL_SPIN_DEFAULT_CURVE_STRENGTH,
+            decay: BALL_SPIN_DEFAULT_DECAY,
+            paddle_factor: BALL_SPIN_DEFAULT_PADDLE_FACTOR,
+        }
+    }
+}
+
+// Returns a velocity with the given speed, pointing up at a random angle (within 45 degrees of vertical)
+pub fn random_ball_velocity(rng: &mut RngResource, speed: f64) -> Vector2d {
+    use rand::Rng;
+    let max_angle = std::f64::consts::FRAC_PI_4;
+    let angle: f64 = rng.rng.gen_range(-max_angle, max_angle);
+
+    Vector2d::new(angle.sin(), -angle.cos()) * speed
+}
+
+// Reflects the velocity off of whichever face (horizontal or vertical) the normal is closest to, keeping its speed.
+// The component across that face is flipped, and the other is left exactly as it was.
+pub fn reflect_axis_aligned(velocity: Vector2d, normal: Vector2d) -> Vector2d {
+    if normal.y.abs() >= normal.x.abs() {
+        Vector2d::new(velocity.x, -velocity.y)
+    } else {
+        Vector2d::new(-velocity.x, velocity.y)
+    }
+}
+
+// Every ball in play, including held ones
+pub fn count_active_balls(ents: &Entities, balls: &ReadStorage<BallComponent>) -> usize {
+    (ents, balls).join().count()
+}
+
+// Everything about the spawned ball is decided by the event, so anything random (like a launch angle) has to be
+// rolled by whoever writes it, from the seeded RngResource.
 #[derive(Clone, Debug)]
 pub struct SpawnBallEvent {
     pub position: Vector2d,
     pub linear_velocity: Vector2d,
     pub owning_paddle_ent: Option<Entity>,
+    pub radius: f64,
+    pub owner: Option<PlayerId>,
+}
+
+// Sent once for each ball that falls into the bottom wall. The ball entity has already been deleted.
+#[derive(Clone, Copy, Debug)]
+pub struct BallLostEvent {
+    pub ball_ent: Entity,
+    // Where the ball was when it was lost
+    pub position: Vector2d,
+}
+
+// Sprite scale that draws the ball at the given radius
+pub fn ball_sprite_scale(radius: f64) -> f32 {
+    (radius / BALL_SPRITE_RADIUS) as f32
 }
 
 #[derive(Debug)]
@@ -30,6 +117,22 @@ pub struct BallComponent {
     pub last_pos: Point2f,
     pub holding_paddle_ent: Option<Entity>,
     pub velocity: Velocity<f64>,
+    // Smallest angle (in radians, away from horizontal) the ball is allowed to travel at.
+    // Shallower balls get nudged, so they can't bounce between the side walls forever.
+    pub min_vertical_angle: f64,
+    // How much steeper (in radians) a stalling ball is made, on top of the minimum angle
+    pub stall_nudge_angle: f64,
+    // Slower balls are sped back up to this speed, so they never crawl. 0 disables it.
+    pub min_speed: f64,
+    pub radius: f64,
+    // Ticks that a ball ignores the paddle for after being launched from it, so it can't get stuck
+    // jittering against the paddle it just left. 0 disables it.
+    pub spawn_protection: u32,
+    // Ticks of spawn protection left
+    pub spawn_protection_ticks: u32,
+    // Player that gets the points for bricks this ball breaks. This is the last player to launch it,
+    // or the owner of the ball it was split from.
+    pub owner: Option<PlayerId>,
 }
 
 impl BallComponent {
@@ -38,8 +141,79 @@ impl BallComponent {
             last_pos: Point2f::origin(),
             velocity: Velocity::new(linear_velocity, 0.0),
             holding_paddle_ent,
+            min_vertical_angle: BALL_DEFAULT_MIN_VERTICAL_ANGLE,
+            stall_nudge_angle: BALL_DEFAULT_STALL_NUDGE_ANGLE,
+            min_speed: BALL_DEFAULT_MIN_SPEED,
+            radius: BALL_DEFAULT_RADIUS,
+            spawn_protection: BALL_DEFAULT_SPAWN_PROTECTION_TICKS,
+            spawn_protection_ticks: 0,
+            owner: None,
         }
     }
+
+    pub fn with_radius(mut self, radius: f64) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    pub fn with_owner(mut self, owner: Option<PlayerId>) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    // Balls are launched once they are no longer being held by a paddle
+    pub fn is_launched(&self) -> bool {
+        self.holding_paddle_ent.is_none()
+    }
+
+    // Lets go of the ball, sending it off with the given velocity. The launching player takes ownership of it.
+    pub fn launch(&mut self, linear_velocity: Vector2d, owner: Option<PlayerId>) {
+        self.holding_paddle_ent = None;
+        self.owner = owner;
+        self.velocity.linear = linear_velocity;
+        self.spawn_protection_ticks = self.spawn_protection;
+    }
+
+    // Spawn protected balls pass through paddles instead of bouncing off of them. The physics world still reports
+    // their contacts with paddles, so every system that reacts to paddle hits (the bounce in the BallSystem, and
+    // the combo reset in the BrickSystem) has to check this and skip them itself.
+    pub fn is_spawn_protected(&self) -> bool {
+        self.spawn_protection_ticks > 0
+    }
+
+    // Speeds the ball up to the minimum speed if it's any slower, keeping the same direction.
+    // Stopped balls have no direction to keep, so they are left alone.
+    pub fn enforce_min_speed(&mut self) {
+        let speed = self.velocity.linear.magnitude();
+        if self.min_speed <= 0.0 || speed >= self.min_speed || speed <= std::f64::EPSILON {
+            return;
+        }
+
+        self.velocity.linear *= self.min_speed / speed;
+    }
+
+    // Steepens the velocity if it is too close to horizontal, keeping the same speed and general direction
+    pub fn unstall_velocity(&mut self) {
+        let linear = self.velocity.linear;
+        let speed = linear.magnitude();
+        if speed <= std::f64::EPSILON {
+            return;
+        }
+
+        let angle = linear.y.abs().atan2(linear.x.abs());
+        if angle >= self.min_vertical_angle {
+            return;
+        }
+
+        let new_angle = self.min_vertical_angle + self.stall_nudge_angle;
+        let x_sign = if linear.x < 0.0 { -1.0 } else { 1.0 };
+        // Perfectly horizontal balls get sent up towards the bricks
+        let y_sign = if linear.y > 0.0 { 1.0 } else { -1.0 };
+        self.velocity.linear = Vector2d::new(
+            new_angle.cos() * speed * x_sign,
+            new_angle.sin() * speed * y_sign,
+        );
+    }
 }
 
 impl Component for BallComponent {
@@ -49,19 +223,23 @@ impl Component for BallComponent {
 #[derive(Default)]
 pub struct BallSystem {
     collision_event_reader: Option<ReaderId<CollisionEvent>>,
+    proximity_event_reader: Option<ReaderId<ProximityEvent>>,
 }
 
 impl<'a> System<'a> for BallSystem {
     type SystemData = (
         Entities<'a>,
-        Write<'a, LevelState>,
-        ReadExpect<'a, AudioAssetDb>,
+        Read<'a, BallSpinSettings>,
+        Read<'a, CollisionSettings>,
+        Read<'a, GamePhase>,
         Read<'a, EventChannel<CollisionEvent>>,
-        Write<'a, EventChannel<SpawnBallEvent>>,
+        Read<'a, EventChannel<ProximityEvent>>,
+        Write<'a, EventChannel<BallLostEvent>>,
         WriteStorage<'a, TransformComponent>,
         WriteStorage<'a, BallComponent>,
         ReadStorage<'a, BrickComponent>,
         ReadStorage<'a, PlayerPaddleComponent>,
+        ReadStorage<'a, BottomWallComponent>,
         WriteStorage<'a, RigidbodyComponent>,
     );
 
@@ -72,24 +250,33 @@ impl<'a> System<'a> for BallSystem {
                 .fetch_mut::<EventChannel<CollisionEvent>>()
                 .register_reader(),
         );
+        self.proximity_event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<ProximityEvent>>()
+                .register_reader(),
+        );
     }
 
     fn run(
         &mut self,
         (
             ents,
-            mut level,
-            audio_db,
+            spin_settings,
+            collision_settings,
+            phase,
             collision_events,
-            mut spawn_ball_events,
+            proximity_events,
+            mut ball_lost_events,
             mut transforms,
             mut balls,
             bricks,
             paddles,
+            bottom_walls,
             mut rigidbodies,
         ): Self::SystemData,
     ) {
         let mut balls_bounced_this_tick: BitSet = BitSet::new();
+        let mut balls_lost_this_tick: BitSet = BitSet::new();
         for event in collision_events.read(&mut self.collision_event_reader.as_mut().unwrap()) {
             // Get the entities involved in the event, ignoring it entirely if either of them are not an entity
             let (entity_a, entity_b) = {
@@ -101,12 +288,16 @@ impl<'a> System<'a> for BallSystem {
             };
 
             if let Some(ball) = balls.get_mut(entity_a) {
-                if let Some(_) = paddles.get(entity_b) {
+                if let Some(paddle) = paddles.get(entity_b) {
+                    if ball.is_spawn_protected() {
+                        continue;
+                    }
+
                     let paddle_transform = transforms.get(entity_b).unwrap();
                     let hit_x = match event.collision_point {
                         Some(p) => p.x,
                         None => {
-                            println!("Ball collision had no collision_point! ball ent = {}, other ent = {}", entity_a.id(), entity_b.id());
+                            warn!(target: "ball", "Ball collision had no collision_point! ball ent = {}, other ent = {}", entity_a.id(), entity_b.id());
 
                             // If there was no concrete collision point calculated, just use the balls current x position
                             let ball_transform = transforms.get(entity_a).unwrap();
@@ -115,35 +306,43 @@ impl<'a> System<'a> for BallSystem {
                     };
 
                     // Get the x hit value, relative to the paddle hit box width. -1.0 means the ball hit the far left side of the paddle, while 1.0 means it hit the far right.
-                    let hit_x_ratio =
-                        (hit_x - paddle_transform.position.x) / (PADDLE_HIT_BOX_WIDTH / 2.0);
-
-                    let mut vel = ball.velocity.linear;
-                    vel.y = ((vel.x.abs() * 0.25) + vel.y) * -0.97;
-                    vel.x = hit_x_ratio * BALL_DEFAULT_FORCE;
-
-                    vel = vel.normalize()
-                        * nalgebra::clamp(vel.magnitude(), 0.0, BALL_MAX_LINEAR_VELOCITY);
-                    ball.velocity = Velocity::new(vel, 0.0);
-                    println!("reflected off paddle: {:?}", ball.velocity);
-
-                    // Pick and play one of the ball paddle bounce audio clips
-                    let clip_id = {
-                        use rand::Rng;
-                        let roll: f32 = rand::thread_rng().gen();
-
-                        if roll <= 0.5 {
-                            AudioAssetId::SfxBallBounce0
-                        } else {
-                            AudioAssetId::SfxBallBounce1
-                        }
+                    let hit_x_ratio = nalgebra::clamp(
+                        (hit_x - paddle_transform.position.x) / (paddle.width / 2.0),
+                        -1.0,
+                        1.0,
+                    );
+
+                    // Send the ball off at an angle from vertical based on where it hit the paddle
+                    let speed = if paddle.preserve_ball_speed {
+                        ball.velocity.linear.magnitude().max(BALL_DEFAULT_FORCE)
+                    } else {
+                        BALL_DEFAULT_FORCE
                     };
+                    let angle = hit_x_ratio * paddle.max_bounce_angle;
+                    let vel = Vector2d::new(angle.sin(), -angle.cos())
+                        * nalgebra::clamp(speed, 0.0, BALL_MAX_LINEAR_VELOCITY);
 
-                    audio::play(clip_id, &audio_db, false);
+                    // Glancing hits spin the ball, based on how fast the paddle slid along it
+                    let spin = if spin_settings.enabled {
+                        let tangential_velocity =
+                            paddle.movement_velocity().x - ball.velocity.linear.x;
+                        let added_spin = tangential_velocity * spin_settings.paddle_factor;
+                        nalgebra::clamp(
+                            ball.velocity.angular + added_spin,
+                            -BALL_MAX_SPIN,
+                            BALL_MAX_SPIN,
+                        )
+                    } else {
+                        0.0
+                    };
+
+                    ball.velocity = Velocity::new(vel, spin);
+                    debug!(target: "ball", "reflected off paddle: {:?}", ball.velocity);
 
                     continue;
                 }
 
+                // A solid bottom wall bounces balls like any other wall
                 if let Some(normal) = event.normal {
                     let ent_b_is_brick = bricks.get(entity_b).is_some();
                     // If the ball already bounced this tick, and this is a brick, just ignore it
@@ -156,6 +355,21 @@ impl<'a> System<'a> for BallSystem {
                     }
 
                     let vel = ball.velocity;
+                    if ent_b_is_brick && collision_settings.axis_aligned_brick_bounces {
+                        ball.velocity = Velocity::new(
+                            reflect_axis_aligned(vel.linear, normal),
+                            vel.angular,
+                        );
+
+                        debug!(
+                            target: "ball",
+                            "[BallSystem] Reflected off brick face: {:?}",
+                            ball.velocity
+                        );
+                        continue;
+                    }
+
+                    // The event normal points from the ball into the wall or brick, so flip it to get the surface normal
                     let normal = -normal.normalize();
                     let dot = vel.linear.dot(&normal);
 
@@ -164,26 +378,14 @@ impl<'a> System<'a> for BallSystem {
                         * nalgebra::clamp(reflected_vel.magnitude(), 0.0, BALL_MAX_LINEAR_VELOCITY);
                     ball.velocity = Velocity::new(reflected_vel, vel.angular);
 
-                    println!(
+                    debug!(
+                        target: "ball",
                         "reflected off wall/brick: {:?}, normal was {:?}",
                         ball.velocity, normal
                     );
-
-                    // Pick and play one of the ball hit audio clips
-                    let clip_id = {
-                        use rand::Rng;
-                        let roll: f32 = rand::thread_rng().gen();
-
-                        if roll <= 0.5 {
-                            AudioAssetId::SfxBallWallHit0
-                        } else {
-                            AudioAssetId::SfxBallWallHit1
-                        }
-                    };
-
-                    audio::play(clip_id, &audio_db, false);
                 } else {
-                    println!(
+                    warn!(
+                        target: "ball",
                         "Ball collision had no normal! ball ent = {}, other ent = {}",
                         entity_a.id(),
                         entity_b.id()
@@ -192,12 +394,36 @@ impl<'a> System<'a> for BallSystem {
             }
         }
 
+        // Balls that fall into the bottom wall are lost. It's only a sensor while it isn't solid.
+        for event in proximity_events.read(&mut self.proximity_event_reader.as_mut().unwrap()) {
+            if event.ty != ProximityType::Entered {
+                continue;
+            }
+
+            if let (Some(entity_a), Some(entity_b)) = (event.entity_a, event.entity_b) {
+                if balls.get(entity_a).is_some() && bottom_walls.get(entity_b).is_some() {
+                    balls_lost_this_tick.add(entity_a.id());
+                }
+            }
+        }
+
         for (ent, mut transform, rigidbody, ball) in
             (&ents, &mut transforms, &mut rigidbodies, &mut balls).join()
         {
             if let Some(holding_paddle_ent) = ball.holding_paddle_ent {
+                // Held balls ride along with the paddle until they are launched
                 let paddle = paddles.get(holding_paddle_ent).unwrap();
                 transform.position = paddle.held_ball_position;
+                ball.velocity = Velocity::zero();
+                rigidbody.velocity = Velocity::zero();
+                rigidbody.status = BodyStatus::Disabled;
+                continue;
+            }
+
+            // Freeze all balls once the level is complete
+            if phase.is_level_won() {
+                ball.velocity = Velocity::zero();
+                rigidbody.velocity = Velocity::zero();
                 rigidbody.status = BodyStatus::Disabled;
                 continue;
             }
@@ -207,35 +433,71 @@ impl<'a> System<'a> for BallSystem {
                 transform.position = transform.last_position;
             }
 
+            ball.spawn_protection_ticks = ball.spawn_protection_ticks.saturating_sub(1);
+            ball.enforce_min_speed();
+            ball.unstall_velocity();
+
             // Directly set the ball velocity every tick to keep the physics engine from affecting it
             rigidbody.status = BodyStatus::Dynamic;
             rigidbody.velocity = ball.velocity;
 
-            // TODO replace this with a sensor collider?
-            if transform.position.y > 235.0 {
+            if balls_lost_this_tick.contains(ent.id()) {
                 ents.delete(ent).expect("Failed to delete ball ent!");
+                ball_lost_events.single_write(BallLostEvent {
+                    ball_ent: ent,
+                    position: transform.position,
+                });
 
-                audio::play(AudioAssetId::SfxBallDeath0, &audio_db, false);
+                continue;
+            }
+        }
+    }
+}
 
-                level.lives -= 1;
-                println!("{} balls remaining.", level.lives);
-                if level.lives == 0 {
-                    println!("Game over!");
-                } else {
-                    // Spawn another ball
-                    spawn_ball_events.single_write(SpawnBallEvent {
-                        position: Vector2d::zeros(),
-                        linear_velocity: Vector2d::zeros(),
-                        owning_paddle_ent: level.player_paddle_ent,
-                    });
-                }
+// Curves spinning balls sideways (a simplified Magnus effect), and slowly winds their spin down.
+// This runs before the BallSystem, which passes the new velocity on to the physics world.
+pub struct BallSpinSystem;
+
+impl<'a> System<'a> for BallSpinSystem {
+    type SystemData = (
+        Read<'a, BallSpinSettings>,
+        Read<'a, TimeScale>,
+        Read<'a, GamePhase>,
+        WriteStorage<'a, BallComponent>,
+    );
+
+    fn run(&mut self, (spin_settings, time_scale, phase, mut balls): Self::SystemData) {
+        if !spin_settings.enabled || phase.is_level_won() {
+            return;
+        }
 
+        let time_scale = time_scale.get();
+        for ball in (&mut balls).join() {
+            if !ball.is_launched() || ball.velocity.angular == 0.0 {
                 continue;
             }
+
+            // The push is perpendicular to the direction of travel, so only the direction changes
+            let linear = ball.velocity.linear;
+            let speed = linear.magnitude();
+            if speed > std::f64::EPSILON {
+                let sideways = Vector2d::new(-linear.y, linear.x);
+                let curve = ball.velocity.angular * spin_settings.curve_strength * time_scale;
+                let curved = linear + sideways * curve;
+                ball.velocity.linear = curved.normalize() * speed;
+            }
+
+            ball.velocity.angular *= spin_settings.decay.powf(time_scale);
+            if ball.velocity.angular.abs() < 0.001 {
+                ball.velocity.angular = 0.0;
+            }
         }
     }
 }
 
+// Spawns one ball for each SpawnBallEvent. Events are handled in the order they were written, and each one creates
+// its entity straight away, so the same events always spawn the same balls, in the same order, with the same ids.
+// That keeps seeded games and replays deterministic.
 #[derive(Default)]
 pub struct SpawnBallSystem {
     spawn_ball_event_reader: Option<ReaderId<SpawnBallEvent>>,
@@ -245,8 +507,12 @@ impl<'a> System<'a> for SpawnBallSystem {
     type SystemData = (
         Entities<'a>,
         Read<'a, LazyUpdate>,
+        Read<'a, GamePhase>,
+        Read<'a, CollisionSettings>,
+        ReadExpect<'a, Assets>,
         Read<'a, EventChannel<SpawnBallEvent>>,
         WriteStorage<'a, PlayerPaddleComponent>,
+        ReadStorage<'a, BallComponent>,
     );
 
     fn setup(&mut self, world: &mut World) {
@@ -258,8 +524,48 @@ impl<'a> System<'a> for SpawnBallSystem {
         );
     }
 
-    fn run(&mut self, (ents, lazy_updater, spawn_ball_events, mut paddles): Self::SystemData) {
+    fn run(
+        &mut self,
+        (
+            ents,
+            lazy_updater,
+            phase,
+            collision_settings,
+            assets,
+            spawn_ball_events,
+            mut paddles,
+            balls,
+        ): Self::SystemData,
+    ) {
+        // Balls spawned in this run aren't in the storage until the lazy updates are applied, so they are counted here
+        let mut active_balls = count_active_balls(&ents, &balls);
+
         for event in spawn_ball_events.read(&mut self.spawn_ball_event_reader.as_mut().unwrap()) {
+            // No more balls are spawned once the level is complete
+            if phase.is_level_won() {
+                continue;
+            }
+
+            if active_balls >= MAX_ACTIVE_BALLS {
+                debug!(
+                    target: "ball",
+                    "[SpawnBallSystem] Already {} balls in play, not spawning another",
+                    active_balls
+                );
+                continue;
+            }
+
+            let radius = if event.radius > 0.0 {
+                event.radius
+            } else {
+                warn!(
+                    target: "ball",
+                    "[SpawnBallSystem] Ball radius must be positive! Using the default radius instead. Radius = {}",
+                    event.radius
+                );
+                BALL_DEFAULT_RADIUS
+            };
+
             let ent = ents.create();
 
             // If an owning paddle was given, we need to spawn the ball on the paddle. Otherwise use the given spawn position.
@@ -276,7 +582,7 @@ impl<'a> System<'a> for SpawnBallSystem {
                 TransformComponent::new(
                     spawn_pos,
                     Point2f::new(16.0, 16.0),
-                    Vector2f::new(1.0, 1.0),
+                    Vector2f::new(ball_sprite_scale(radius), ball_sprite_scale(radius)),
                 ),
             );
 
@@ -284,7 +590,7 @@ impl<'a> System<'a> for SpawnBallSystem {
                 ent,
                 SpriteComponent {
                     color: COLOR_WHITE,
-                    spritesheet_tex_id: 2,
+                    spritesheet_tex_id: assets.texture("sprites"),
                     region: SpriteRegion {
                         x: 64,
                         y: 0,
@@ -293,14 +599,20 @@ impl<'a> System<'a> for SpawnBallSystem {
                     },
                     layer: 2,
                     transparency: Transparency::Opaque,
+                    flip_x: false,
+                    flip_y: false,
                 },
             );
 
             lazy_updater.insert(
                 ent,
-                BallComponent::new(event.linear_velocity, event.owning_paddle_ent),
+                BallComponent::new(event.linear_velocity, event.owning_paddle_ent)
+                    .with_radius(radius)
+                    .with_owner(event.owner),
             );
 
+            lazy_updater.insert(ent, BallTrailComponent::new(BALL_TRAIL_DEFAULT_LENGTH));
+
             lazy_updater.insert(
                 ent,
                 RigidbodyComponent::new(
@@ -311,17 +623,18 @@ impl<'a> System<'a> for SpawnBallSystem {
                 ),
             );
 
-            let collision_groups = ncollide2d::pipeline::CollisionGroups::new()
-                .with_membership(&[0])
-                .with_blacklist(&[0]);
+            // The collider is a circle, so hitting the corner of a brick gives a diagonal contact normal,
+            // and the ball glances off of it rather than bouncing straight back
+            let collision_groups = collision::ball_groups(collision_settings.ball_ball_collisions);
             lazy_updater.insert(
                 ent,
                 ColliderComponent::new(
-                    Ball::new(BALL_COLLIDER_RADIUS * crate::game::WORLD_UNIT_RATIO),
+                    Ball::new(radius * crate::game::WORLD_UNIT_RATIO),
                     Vector2::zeros(),
                     collision_groups,
                     0.0,
-                ),
+                )
+                .with_ccd(true),
             );
 
             if let Some(paddle_ent) = event.owning_paddle_ent {
@@ -331,7 +644,461 @@ impl<'a> System<'a> for SpawnBallSystem {
                 paddle.held_ball_ent = Some(ent);
             }
 
-            println!("[EntitySpawnSystem] Spawned ball");
+            active_balls += 1;
+
+            debug!(target: "ball", "[EntitySpawnSystem] Spawned ball");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        brick::Combo,
+        level::{Lives, PLAYER_DEFAULT_BALLS},
+        physics::PhysicsState,
+        testing::{self, DT},
+        GameState, Point2d,
+    };
+    use gfx::input::{InputState, VirtualKeyCode};
+
+    #[test]
+    fn far_left_paddle_hit_sends_the_ball_left() {
+        let (mut game, paddle_ent) = testing::empty_game();
+        let paddle_x = testing::position(&game.world, paddle_ent).x;
+
+        // Dropping straight down onto the left end of the paddle
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(paddle_x - 26.0, 200.0),
+            Vector2d::new(0.0, BALL_DEFAULT_FORCE),
+        );
+        game.tick(DT);
+        let ball_ent = testing::balls(&game.world)[0];
+
+        let mut velocity = testing::ball_velocity(&game.world, ball_ent);
+        for _ in 0..30 {
+            game.tick(DT);
+            velocity = testing::ball_velocity(&game.world, ball_ent);
+            if velocity.y < 0.0 {
+                break;
+            }
         }
+
+        assert!(velocity.y < 0.0, "ball never bounced off the paddle");
+        assert!(velocity.x < 0.0);
+    }
+
+    // Velocity the first ball of a new game is spawned with
+    fn first_ball_velocity(seed: u64) -> Vector2d {
+        let mut game = testing::headless_game(seed);
+        let mut reader = game
+            .world
+            .write_resource::<EventChannel<SpawnBallEvent>>()
+            .register_reader();
+        game.tick(DT);
+
+        let events = game.world.read_resource::<EventChannel<SpawnBallEvent>>();
+        let first_event = events.read(&mut reader).next().unwrap();
+        first_event.linear_velocity
+    }
+
+    #[test]
+    fn same_seed_spawns_the_same_first_ball() {
+        let velocity = first_ball_velocity(1234);
+        assert_eq!(first_ball_velocity(1234), velocity);
+        assert_ne!(first_ball_velocity(4321), velocity);
+        assert!((velocity.magnitude() - BALL_DEFAULT_FORCE).abs() < 1e-9);
+    }
+
+    #[test]
+    fn nearly_horizontal_ball_is_steepened() {
+        let (mut game, _) = testing::empty_game();
+        let stalling_velocity = Vector2d::new(BALL_DEFAULT_FORCE, 0.1);
+        testing::spawn_ball(&game.world, Vector2d::new(160.0, 120.0), stalling_velocity);
+        game.tick(DT);
+        let ball_ent = testing::balls(&game.world)[0];
+
+        game.tick(DT);
+
+        let velocity = testing::ball_velocity(&game.world, ball_ent);
+        assert!((velocity.magnitude() - stalling_velocity.magnitude()).abs() < 1e-6);
+        assert!(velocity.x > 0.0);
+        assert!(velocity.y / velocity.magnitude() > BALL_DEFAULT_MIN_VERTICAL_ANGLE.sin());
+    }
+
+    #[test]
+    fn spawn_event_spawns_exactly_one_ball() {
+        let (mut game, _) = testing::empty_game();
+        assert!(testing::balls(&game.world).is_empty());
+
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(160.0, 120.0),
+            Vector2d::new(0.0, -BALL_DEFAULT_FORCE),
+        );
+        game.tick(DT);
+
+        assert_eq!(testing::balls(&game.world).len(), 1);
+    }
+
+    #[test]
+    fn balls_spawned_mid_game_get_one_body_each() {
+        let (mut game, _) = testing::empty_game();
+        game.tick(DT);
+        let baseline = game.world.read_resource::<PhysicsState>().body_count();
+        testing::capture_logs();
+
+        for row in 0..5 {
+            for column in 0..4 {
+                testing::spawn_ball(
+                    &game.world,
+                    Vector2d::new(100.0 + column as f64 * 40.0, 120.0 + row as f64 * 20.0),
+                    Vector2d::new(0.0, -BALL_DEFAULT_MIN_SPEED),
+                );
+            }
+            game.tick(DT);
+        }
+        game.tick(DT);
+        game.tick(DT);
+
+        assert_eq!(testing::balls(&game.world).len(), 20);
+        assert_eq!(
+            game.world.read_resource::<PhysicsState>().body_count(),
+            baseline + 20
+        );
+        assert!(testing::captured_logs()
+            .iter()
+            .all(|log| !log.message.contains("Duplicate")));
+    }
+
+    // Two balls on a collision course, heading towards each other. Returns the x velocity of the left one
+    // once they have met.
+    fn ball_ball_deflection(ball_ball_collisions: bool) -> f64 {
+        let (mut game, _) = testing::empty_game();
+        game.world
+            .write_resource::<CollisionSettings>()
+            .ball_ball_collisions = ball_ball_collisions;
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(140.0, 120.0),
+            Vector2d::new(4.0, -3.0),
+        );
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(180.0, 120.0),
+            Vector2d::new(-4.0, -3.0),
+        );
+        game.tick(DT);
+        let left_ball_ent = testing::balls(&game.world)[0];
+
+        game.run_headless(20, DT);
+
+        testing::ball_velocity(&game.world, left_ball_ent).x
+    }
+
+    #[test]
+    fn balls_only_bounce_off_each_other_when_enabled() {
+        assert!(ball_ball_deflection(false) > 0.0);
+        assert!(ball_ball_deflection(true) < 0.0);
+    }
+
+    #[test]
+    fn ball_collider_and_sprite_share_the_radius() {
+        let (mut game, _) = testing::empty_game();
+        let radius = 5.0;
+        game.world
+            .write_resource::<EventChannel<SpawnBallEvent>>()
+            .single_write(SpawnBallEvent {
+                position: Vector2d::new(160.0, 120.0),
+                linear_velocity: Vector2d::new(0.0, -BALL_DEFAULT_FORCE),
+                owning_paddle_ent: None,
+                radius,
+                owner: None,
+            });
+        game.tick(DT);
+
+        let ball_ent = testing::balls(&game.world)[0];
+        let colliders = game.world.read_storage::<ColliderComponent>();
+        let collider_radius = colliders
+            .get(ball_ent)
+            .unwrap()
+            .shape
+            .as_shape::<Ball<f64>>()
+            .unwrap()
+            .radius();
+        assert!((collider_radius / crate::game::WORLD_UNIT_RATIO - radius).abs() < 1e-9);
+
+        let transforms = game.world.read_storage::<TransformComponent>();
+        let scale = transforms.get(ball_ent).unwrap().scale;
+        assert!((BALL_SPRITE_RADIUS * scale.x as f64 - radius).abs() < 1e-5);
+        assert!((BALL_SPRITE_RADIUS * scale.y as f64 - radius).abs() < 1e-5);
+    }
+
+    #[test]
+    fn spinning_balls_curve_and_others_go_straight() {
+        let mut world = World::new();
+        world.register::<BallComponent>();
+        world.insert(BallSpinSettings {
+            enabled: true,
+            ..Default::default()
+        });
+        world.insert(TimeScale::default());
+        world.insert(GamePhase::Playing);
+        let velocity = Vector2d::new(0.0, -BALL_DEFAULT_FORCE);
+        let straight_ent = world
+            .create_entity()
+            .with(BallComponent::new(velocity, None))
+            .build();
+        let mut spinning_ball = BallComponent::new(velocity, None);
+        spinning_ball.velocity.angular = 1.0;
+        let spinning_ent = world.create_entity().with(spinning_ball).build();
+
+        let mut spinning_xs = Vec::new();
+        for _ in 0..10 {
+            BallSpinSystem.run_now(&world);
+            spinning_xs.push(
+                world
+                    .read_storage::<BallComponent>()
+                    .get(spinning_ent)
+                    .unwrap()
+                    .velocity
+                    .linear
+                    .x,
+            );
+        }
+
+        let balls = world.read_storage::<BallComponent>();
+        assert_eq!(balls.get(straight_ent).unwrap().velocity.linear.x, 0.0);
+        for pair in spinning_xs.windows(2) {
+            assert!(pair[1] != pair[0]);
+        }
+        let spinning_velocity = balls.get(spinning_ent).unwrap().velocity.linear;
+        assert!((spinning_velocity.magnitude() - BALL_DEFAULT_FORCE).abs() < 1e-9);
+    }
+
+    #[test]
+    fn lost_ball_sends_one_event_that_every_system_handles() {
+        let (mut game, _) = testing::empty_game();
+        let mut ball_lost_reader = game
+            .world
+            .write_resource::<EventChannel<BallLostEvent>>()
+            .register_reader();
+        game.world.write_resource::<Combo>().multiplier = 3;
+
+        // Falling straight into the bottom wall, well away from the paddle
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(40.0, 225.0),
+            Vector2d::new(0.0, BALL_DEFAULT_FORCE),
+        );
+        game.tick(DT);
+        let ball_ent = testing::balls(&game.world)[0];
+        game.run_headless(30, DT);
+
+        let events: Vec<BallLostEvent> = game
+            .world
+            .read_resource::<EventChannel<BallLostEvent>>()
+            .read(&mut ball_lost_reader)
+            .cloned()
+            .collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].ball_ent, ball_ent);
+        assert!((events[0].position.x - 40.0).abs() < 1.0);
+
+        assert_eq!(
+            game.world.read_resource::<Lives>().count,
+            PLAYER_DEFAULT_BALLS - 1
+        );
+        assert_eq!(game.world.read_resource::<Combo>().multiplier, 1);
+    }
+
+    #[test]
+    fn slow_ball_is_sped_up_to_the_minimum_speed() {
+        let (mut game, _) = testing::empty_game();
+        let slow_velocity = Vector2d::new(1.0, -2.0);
+        assert!(slow_velocity.magnitude() < BALL_DEFAULT_MIN_SPEED);
+        testing::spawn_ball(&game.world, Vector2d::new(160.0, 150.0), slow_velocity);
+        game.tick(DT);
+        let ball_ent = testing::balls(&game.world)[0];
+        game.tick(DT);
+
+        let velocity = testing::ball_velocity(&game.world, ball_ent);
+        assert!((velocity.magnitude() - BALL_DEFAULT_MIN_SPEED).abs() < 1e-9);
+        assert!((velocity.normalize() - slow_velocity.normalize()).norm() < 1e-9);
+
+        // A floor of 0 turns it off
+        let mut ball = BallComponent::new(slow_velocity, None);
+        ball.min_speed = 0.0;
+        ball.enforce_min_speed();
+        assert_eq!(ball.velocity.linear, slow_velocity);
+    }
+
+    #[test]
+    fn ball_at_max_speed_never_passes_through_the_paddle() {
+        // Start a pixel further along each time, to try every way a step can line up with the paddle
+        for offset in 0..8 {
+            let (mut game, paddle_ent) = testing::empty_game();
+            let paddle_x = testing::position(&game.world, paddle_ent).x;
+            let mut reader = game
+                .world
+                .write_resource::<EventChannel<CollisionEvent>>()
+                .register_reader();
+
+            testing::spawn_ball(
+                &game.world,
+                Vector2d::new(paddle_x, 120.0 + offset as f64),
+                Vector2d::new(0.0, BALL_MAX_LINEAR_VELOCITY),
+            );
+            game.tick(DT);
+            let ball_ent = testing::balls(&game.world)[0];
+
+            let mut hit_paddle = false;
+            for _ in 0..30 {
+                game.tick(DT);
+                hit_paddle = game
+                    .world
+                    .read_resource::<EventChannel<CollisionEvent>>()
+                    .read(&mut reader)
+                    .any(|event| {
+                        event.entity_a == Some(ball_ent) && event.entity_b == Some(paddle_ent)
+                    });
+                if hit_paddle {
+                    break;
+                }
+            }
+
+            assert!(
+                hit_paddle,
+                "ball went through the paddle, from offset {}",
+                offset
+            );
+        }
+    }
+
+    // Reports the ball hitting the far left end of the paddle, and returns its velocity after the next tick
+    fn velocity_after_left_end_hit(
+        game: &mut GameState,
+        ball_ent: Entity,
+        paddle_ent: Entity,
+    ) -> Vector2d {
+        let paddle_position = testing::position(&game.world, paddle_ent);
+        let paddle_width = game
+            .world
+            .read_storage::<PlayerPaddleComponent>()
+            .get(paddle_ent)
+            .unwrap()
+            .width;
+        testing::collide(
+            &game.world,
+            ball_ent,
+            paddle_ent,
+            Vector2d::new(0.0, 1.0),
+            Point2d::new(paddle_position.x - paddle_width / 2.0, paddle_position.y),
+        );
+        game.tick(DT);
+
+        testing::ball_velocity(&game.world, ball_ent)
+    }
+
+    #[test]
+    fn launched_ball_ignores_the_paddle_until_its_protection_runs_out() {
+        let (mut game, paddle_ent) = testing::empty_game();
+        testing::spawn_held_ball(&game.world, paddle_ent);
+        game.tick(DT);
+        let ball_ent = testing::balls(&game.world)[0];
+
+        game.world
+            .insert(testing::pressed(&[VirtualKeyCode::Space]));
+        game.tick(DT);
+        game.world.insert(InputState::new());
+        assert!(game
+            .world
+            .read_storage::<BallComponent>()
+            .get(ball_ent)
+            .unwrap()
+            .is_spawn_protected());
+
+        let mut protected_ticks = 0;
+        while game
+            .world
+            .read_storage::<BallComponent>()
+            .get(ball_ent)
+            .unwrap()
+            .is_spawn_protected()
+        {
+            let velocity = testing::ball_velocity(&game.world, ball_ent);
+            let after_hit = velocity_after_left_end_hit(&mut game, ball_ent, paddle_ent);
+            assert!(
+                (after_hit - velocity).norm() < 1e-6,
+                "protected ball bounced"
+            );
+            protected_ticks += 1;
+        }
+        assert!(protected_ticks > 0 && protected_ticks <= BALL_DEFAULT_SPAWN_PROTECTION_TICKS);
+
+        let after_hit = velocity_after_left_end_hit(&mut game, ball_ent, paddle_ent);
+        assert!(after_hit.x < 0.0);
+        assert!(after_hit.y < 0.0);
+    }
+
+    #[test]
+    fn spawn_events_become_balls_in_the_order_they_were_written() {
+        let (mut game, _) = testing::empty_game();
+        let velocities = vec![
+            Vector2d::new(1.0, -5.0),
+            Vector2d::new(-2.0, -5.0),
+            Vector2d::new(3.0, -5.0),
+        ];
+        for (i, velocity) in velocities.iter().enumerate() {
+            testing::spawn_ball(
+                &game.world,
+                Vector2d::new(100.0 + i as f64 * 40.0, 120.0),
+                *velocity,
+            );
+        }
+        game.tick(DT);
+
+        let spawned: Vec<Vector2d> = testing::balls(&game.world)
+            .into_iter()
+            .map(|ball_ent| testing::ball_velocity(&game.world, ball_ent))
+            .collect();
+        assert_eq!(spawned, velocities);
+    }
+
+    #[test]
+    fn brick_top_hit_mirrors_the_vertical_velocity_exactly() {
+        let (mut game, _) = testing::empty_game();
+        game.world
+            .write_resource::<CollisionSettings>()
+            .axis_aligned_brick_bounces = true;
+
+        let brick_ent = testing::spawn_brick(
+            &mut game.world,
+            Vector2d::new(96.0, 100.0),
+            BrickComponent::new(2, 100),
+        );
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(110.0, 60.0),
+            Vector2d::new(3.0, 4.0),
+        );
+        game.tick(DT);
+        let ball_ent = testing::balls(&game.world)[0];
+
+        // Contact normals are rarely exactly vertical, even on a flat face
+        testing::collide(
+            &game.world,
+            ball_ent,
+            brick_ent,
+            Vector2d::new(0.05, 1.0).normalize(),
+            Point2d::new(112.0, 100.0),
+        );
+        game.tick(DT);
+
+        let velocity = testing::ball_velocity(&game.world, ball_ent);
+        assert_eq!(velocity, Vector2d::new(3.0, -4.0));
+        assert_eq!(velocity.magnitude(), 5.0);
     }
 }
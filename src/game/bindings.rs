@@ -0,0 +1,197 @@
+use gfx::input::{GamepadButton, InputState, VirtualKeyCode};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, error::Error};
+
+pub const BINDINGS_PATH: &str = "bindings.ron";
+
+// Things the player can do, independent of the keys that do them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    // Launches a held ball, or fires the laser paddle
+    Launch,
+    Pause,
+    Restart,
+    RestartLevel,
+    ToggleControlMode,
+}
+
+// Maps each action to the keys and gamepad buttons that trigger it. Actions can have more than one.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct InputBindings {
+    bindings: HashMap<Action, Vec<VirtualKeyCode>>,
+    #[serde(default = "default_button_bindings")]
+    button_bindings: HashMap<Action, Vec<GamepadButton>>,
+    // Whether the gamepad stick moves the paddle too. Only one player can use the gamepad.
+    #[serde(default = "default_gamepad_stick")]
+    gamepad_stick: bool,
+}
+
+fn default_gamepad_stick() -> bool {
+    true
+}
+
+fn default_button_bindings() -> HashMap<Action, Vec<GamepadButton>> {
+    let mut button_bindings = HashMap::new();
+    button_bindings.insert(Action::MoveLeft, vec![GamepadButton::DPadLeft]);
+    button_bindings.insert(Action::MoveRight, vec![GamepadButton::DPadRight]);
+    button_bindings.insert(Action::Launch, vec![GamepadButton::South]);
+    button_bindings.insert(Action::Pause, vec![GamepadButton::Start]);
+    button_bindings.insert(Action::Restart, vec![GamepadButton::Select]);
+    button_bindings.insert(Action::RestartLevel, vec![GamepadButton::West]);
+
+    button_bindings
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        let mut bindings = HashMap::new();
+        bindings.insert(
+            Action::MoveLeft,
+            vec![VirtualKeyCode::A, VirtualKeyCode::Left],
+        );
+        bindings.insert(
+            Action::MoveRight,
+            vec![VirtualKeyCode::D, VirtualKeyCode::Right],
+        );
+        bindings.insert(Action::Launch, vec![VirtualKeyCode::Space]);
+        bindings.insert(Action::Pause, vec![VirtualKeyCode::P]);
+        bindings.insert(Action::Restart, vec![VirtualKeyCode::R]);
+        bindings.insert(Action::RestartLevel, vec![VirtualKeyCode::L]);
+        bindings.insert(Action::ToggleControlMode, vec![VirtualKeyCode::M]);
+
+        InputBindings {
+            bindings,
+            button_bindings: default_button_bindings(),
+            gamepad_stick: true,
+        }
+    }
+}
+
+impl InputBindings {
+    // Paddle controls for one player in a multiplayer game, so players don't share any keys.
+    // Player 0 keeps the gamepad. Any action left unbound here falls back to the shared bindings.
+    pub fn for_player(player_id: u32) -> Self {
+        let mut bindings = HashMap::new();
+        let mut button_bindings = HashMap::new();
+        let gamepad_stick = player_id == 0;
+
+        match player_id {
+            0 => {
+                bindings.insert(Action::MoveLeft, vec![VirtualKeyCode::A]);
+                bindings.insert(Action::MoveRight, vec![VirtualKeyCode::D]);
+                bindings.insert(Action::Launch, vec![VirtualKeyCode::W]);
+                bindings.insert(Action::ToggleControlMode, vec![VirtualKeyCode::S]);
+
+                let default_buttons = default_button_bindings();
+                for action in [Action::MoveLeft, Action::MoveRight, Action::Launch].iter() {
+                    if let Some(buttons) = default_buttons.get(action) {
+                        button_bindings.insert(*action, buttons.clone());
+                    }
+                }
+            }
+            1 => {
+                bindings.insert(Action::MoveLeft, vec![VirtualKeyCode::Left]);
+                bindings.insert(Action::MoveRight, vec![VirtualKeyCode::Right]);
+                bindings.insert(Action::Launch, vec![VirtualKeyCode::Up]);
+                bindings.insert(Action::ToggleControlMode, vec![VirtualKeyCode::Down]);
+            }
+            _ => {
+                warn!(
+                    target: "bindings",
+                    "[InputBindings] No default controls for player {}",
+                    player_id
+                );
+            }
+        }
+
+        InputBindings {
+            bindings,
+            button_bindings,
+            gamepad_stick,
+        }
+    }
+
+    pub fn keys(&self, action: Action) -> &[VirtualKeyCode] {
+        self.bindings
+            .get(&action)
+            .map(|keys| keys.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn buttons(&self, action: Action) -> &[GamepadButton] {
+        self.button_bindings
+            .get(&action)
+            .map(|buttons| buttons.as_slice())
+            .unwrap_or(&[])
+    }
+
+    // Whether any key or button is bound to the action
+    pub fn binds(&self, action: Action) -> bool {
+        !self.keys(action).is_empty() || !self.buttons(action).is_empty()
+    }
+
+    // Replaces every key bound to the action with the given one
+    pub fn rebind(&mut self, action: Action, key: VirtualKeyCode) {
+        self.bindings.insert(action, vec![key]);
+    }
+
+    // Replaces every gamepad button bound to the action with the given one
+    pub fn rebind_button(&mut self, action: Action, button: GamepadButton) {
+        self.button_bindings.insert(action, vec![button]);
+    }
+
+    // True for as long as any key or button bound to the action is held down
+    pub fn is_down(&self, input: &InputState, action: Action) -> bool {
+        self.keys(action).iter().any(|&key| input.is_down(key))
+            || self
+                .buttons(action)
+                .iter()
+                .any(|&button| input.is_button_down(button))
+    }
+
+    // True only on the tick any key or button bound to the action went down
+    pub fn was_pressed(&self, input: &InputState, action: Action) -> bool {
+        self.keys(action).iter().any(|&key| input.was_pressed(key))
+            || self
+                .buttons(action)
+                .iter()
+                .any(|&button| input.was_button_pressed(button))
+    }
+
+    // Horizontal movement, from -1.0 (left) to 1.0 (right). The move actions and the gamepad stick
+    // are added together, so the stick gives proportional movement and keys still work alongside it.
+    pub fn move_axis(&self, input: &InputState) -> f64 {
+        let mut axis = if self.gamepad_stick {
+            input.gamepad_axis_x() as f64
+        } else {
+            0.0
+        };
+
+        if self.is_down(input, Action::MoveLeft) {
+            axis -= 1.0;
+        }
+
+        if self.is_down(input, Action::MoveRight) {
+            axis += 1.0;
+        }
+
+        nalgebra::clamp(axis, -1.0, 1.0)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let source = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        std::fs::write(path, source)?;
+
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<InputBindings, Box<dyn Error>> {
+        let source = std::fs::read_to_string(path)?;
+        let bindings = ron::de::from_str(&source)?;
+
+        Ok(bindings)
+    }
+}
@@ -1,18 +1,151 @@
-use crate::game::{audio::{self, AudioAssetId, AudioAssetDb}, ball::BallComponent, physics::CollisionEvent, LevelState};
+use crate::game::{
+    ball::{BallComponent, BallLostEvent},
+    laser::ProjectileComponent,
+    level::LevelConfig,
+    paddle::{PlayerId, PlayerPaddleComponent},
+    physics::CollisionEvent,
+    popup,
+    powerup::PowerUpKind,
+    render::SpriteComponent,
+    transform::TransformComponent,
+    tween::ColorTweenComponent,
+    LevelState, Vector2d,
+};
+use gfx::{
+    color::{Color, COLOR_WHITE},
+    sprite::SpriteRegion,
+};
+use serde::{Deserialize, Serialize};
 use shrev::EventChannel;
 use specs::prelude::*;
+use std::collections::HashMap;
 
 pub const BRICK_DEFAULT_HP: i32 = 2;
+pub const BRICK_DEFAULT_POINTS: u32 = 100;
 pub const BRICK_SPRITE_WIDTH: u32 = 32;
 pub const BRICK_SPRITE_HEIGHT: u32 = 16;
+pub const BRICK_HIT_FLASH_TICKS: u32 = 8;
+pub const COMBO_DEFAULT_MAX_MULTIPLIER: u32 = 8;
+// Distance (between brick centers) that an explosion reaches. This covers the 8 bricks around a bomb in a grid.
+pub const BRICK_EXPLOSION_RADIUS: f64 = 40.0;
+// How many bombs can set each other off in a row, from the first one broken by a ball
+pub const BRICK_EXPLOSION_MAX_CHAIN_DEPTH: u32 = 4;
+
+// Sent when an explosive brick goes off
+pub struct ExplosionEvent {
+    pub position: Vector2d,
+}
+
+// Sent for every brick that breaks, whether a ball, a laser or an explosion broke it
+#[derive(Clone, Copy, Debug)]
+pub struct BrickBrokenEvent {
+    // Center of the brick
+    pub position: Vector2d,
+    pub contains_powerup: Option<PowerUpKind>,
+}
+
+// Multiplies the points for bricks broken one after another, without a ball touching the paddle in between.
+// The multiplier goes up with every brick broken, and resets when a ball hits the paddle or is lost.
+pub struct Combo {
+    pub multiplier: u32,
+    pub max_multiplier: u32,
+}
+
+impl Combo {
+    pub fn new(max_multiplier: u32) -> Self {
+        Combo {
+            multiplier: 1,
+            max_multiplier,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.multiplier = 1;
+    }
+
+    // Points awarded for breaking a brick worth the given points, which also advances the combo
+    pub fn award(&mut self, points: u32) -> u32 {
+        let awarded = points * self.multiplier;
+        self.multiplier = (self.multiplier + 1).min(self.max_multiplier);
+
+        awarded
+    }
+}
+
+// Points scored by each player, for multiplayer. The shared total is still kept in the LevelState.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Scores {
+    scores: HashMap<PlayerId, u32>,
+}
+
+impl Scores {
+    pub fn add(&mut self, player_id: PlayerId, points: u32) {
+        *self.scores.entry(player_id).or_insert(0) += points;
+    }
+
+    pub fn get(&self, player_id: PlayerId) -> u32 {
+        self.scores.get(&player_id).cloned().unwrap_or(0)
+    }
+
+    pub fn reset(&mut self) {
+        self.scores.clear();
+    }
+}
 
 pub struct BrickComponent {
     pub hp: i32,
+    pub points: u32,
+    // Sprite regions to show as the brick takes damage, indexed by remaining hp - 1.
+    // If there is no region for the remaining hp, the sprite is left as is.
+    pub damage_regions: Vec<SpriteRegion>,
+    // Indestructible (steel) bricks still bounce balls, but never take damage or award points
+    pub indestructible: bool,
+    // Explosive bricks damage every brick around them when they break
+    pub explosive: bool,
+    // Power-up that is always dropped when the brick breaks, instead of rolling for a random one
+    pub contains_powerup: Option<PowerUpKind>,
 }
 
 impl BrickComponent {
-    pub fn new(hp: i32) -> Self {
-        BrickComponent { hp }
+    pub fn new(hp: i32, points: u32) -> Self {
+        BrickComponent {
+            hp,
+            points,
+            damage_regions: Vec::new(),
+            indestructible: false,
+            explosive: false,
+            contains_powerup: None,
+        }
+    }
+
+    pub fn new_indestructible() -> Self {
+        BrickComponent {
+            indestructible: true,
+            ..BrickComponent::new(1, 0)
+        }
+    }
+
+    pub fn with_damage_regions(mut self, damage_regions: Vec<SpriteRegion>) -> Self {
+        self.damage_regions = damage_regions;
+        self
+    }
+
+    pub fn with_explosive(mut self, explosive: bool) -> Self {
+        self.explosive = explosive;
+        self
+    }
+
+    pub fn with_powerup(mut self, contains_powerup: Option<PowerUpKind>) -> Self {
+        self.contains_powerup = contains_powerup;
+        self
+    }
+
+    pub fn damage_region(&self) -> Option<SpriteRegion> {
+        if self.hp <= 0 {
+            return None;
+        }
+
+        self.damage_regions.get((self.hp - 1) as usize).cloned()
     }
 }
 
@@ -20,19 +153,39 @@ impl Component for BrickComponent {
     type Storage = VecStorage<Self>;
 }
 
+// Bricks that still have to be broken to complete the level
+pub fn count_breakable_bricks(ents: &Entities, bricks: &ReadStorage<BrickComponent>) -> usize {
+    (ents, bricks)
+        .join()
+        .filter(|(_, brick)| !brick.indestructible)
+        .count()
+}
+
 #[derive(Default)]
 pub struct BrickSystem {
     collision_event_reader: Option<ReaderId<CollisionEvent>>,
+    ball_lost_event_reader: Option<ReaderId<BallLostEvent>>,
 }
 
 impl<'a> System<'a> for BrickSystem {
     type SystemData = (
         Entities<'a>,
-        ReadExpect<'a, AudioAssetDb>,
+        Read<'a, LazyUpdate>,
         Write<'a, LevelState>,
+        ReadExpect<'a, LevelConfig>,
+        WriteExpect<'a, Combo>,
+        Write<'a, Scores>,
         Read<'a, EventChannel<CollisionEvent>>,
+        Read<'a, EventChannel<BallLostEvent>>,
+        Write<'a, EventChannel<ExplosionEvent>>,
+        Write<'a, EventChannel<BrickBrokenEvent>>,
         WriteStorage<'a, BrickComponent>,
         ReadStorage<'a, BallComponent>,
+        ReadStorage<'a, ProjectileComponent>,
+        ReadStorage<'a, PlayerPaddleComponent>,
+        ReadStorage<'a, TransformComponent>,
+        WriteStorage<'a, SpriteComponent>,
+        WriteStorage<'a, ColorTweenComponent>,
     );
 
     fn setup(&mut self, world: &mut World) {
@@ -42,10 +195,40 @@ impl<'a> System<'a> for BrickSystem {
                 .fetch_mut::<EventChannel<CollisionEvent>>()
                 .register_reader(),
         );
+        self.ball_lost_event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<BallLostEvent>>()
+                .register_reader(),
+        );
     }
 
-    fn run(&mut self, (ents, audio_db, mut level, collision_events, mut bricks, balls): Self::SystemData) {
+    fn run(
+        &mut self,
+        (
+            ents,
+            lazy_updater,
+            mut level,
+            config,
+            mut combo,
+            mut scores,
+            collision_events,
+            ball_lost_events,
+            mut explosion_events,
+            mut brick_broken_events,
+            mut bricks,
+            balls,
+            projectiles,
+            paddles,
+            transforms,
+            mut sprites,
+            mut tweens,
+        ): Self::SystemData,
+    ) {
         let mut bricks_hit_this_tick: BitSet = BitSet::new();
+        // Where each brick was hit, by entity id. Score popups start from here.
+        let mut hit_points: HashMap<u32, Vector2d> = HashMap::new();
+        // Which player's ball hit each brick, by entity id. They get the points if it breaks.
+        let mut hit_owners: HashMap<u32, PlayerId> = HashMap::new();
         for event in collision_events.read(&mut self.collision_event_reader.as_mut().unwrap()) {
             // Get the entities involved in the event, ignoring it entirely if either of them are not an entity
             let (entity_a, entity_b) = {
@@ -56,33 +239,340 @@ impl<'a> System<'a> for BrickSystem {
                 (event.entity_a.unwrap(), event.entity_b.unwrap())
             };
 
-            // If the collision was between a brick and a ball entity, mark the brick as hit so we can damage it
-            if bricks.get(entity_a).is_some() && balls.get(entity_b).is_some() {
+            // If the collision was between a brick and a ball or projectile, mark the brick as hit so we can damage it
+            if bricks.get(entity_a).is_some()
+                && (balls.get(entity_b).is_some() || projectiles.get(entity_b).is_some())
+            {
                 bricks_hit_this_tick.add(entity_a.id());
+                if let Some(point) = event.collision_point {
+                    hit_points.insert(entity_a.id(), point.coords);
+                }
+
+                if let Some(owner) = balls.get(entity_b).and_then(|ball| ball.owner) {
+                    hit_owners.insert(entity_a.id(), owner);
+                }
+            }
+
+            // The combo ends when a ball comes back to the paddle
+            let bounced_off_paddle = balls
+                .get(entity_a)
+                .map_or(false, |ball| !ball.is_spawn_protected());
+            if bounced_off_paddle && paddles.get(entity_b).is_some() {
+                combo.reset();
             }
         }
 
-        for (ent, mut brick, _) in (&ents, &mut bricks, &bricks_hit_this_tick).join() {
-            brick.hp -= 1;
-            if brick.hp <= 0 {
+        // It also ends when a ball is lost
+        if ball_lost_events
+            .read(&mut self.ball_lost_event_reader.as_mut().unwrap())
+            .count()
+            > 0
+        {
+            combo.reset();
+        }
+
+        // Explosions to set off, as (position, chain depth, player that set it off)
+        let mut explosions: Vec<(Vector2d, u32, Option<PlayerId>)> = Vec::new();
+
+        for (ent, brick, transform, _) in
+            (&ents, &mut bricks, &transforms, &bricks_hit_this_tick).join()
+        {
+            if brick.indestructible {
+                continue;
+            }
+
+            if damage_brick(ent, brick, &mut sprites, &mut tweens) {
                 ents.delete(ent).unwrap();
 
-                level.score += 100;
+                let points = combo.award(brick.points);
+                level.score += points;
+
+                let owner = hit_owners.get(&ent.id()).cloned();
+                if let Some(owner) = owner {
+                    scores.add(owner, points);
+                }
+
+                let center = transform.position + config.brick_center_offset();
+                let popup_position = hit_points.get(&ent.id()).cloned().unwrap_or(center);
+                popup::spawn_score_popup(&ents, &lazy_updater, popup_position, points);
+                brick_broken_events.single_write(BrickBrokenEvent {
+                    position: center,
+                    contains_powerup: brick.contains_powerup,
+                });
+
+                if brick.explosive {
+                    explosions.push((center, 0, owner));
+                }
+            }
+        }
+
+        while let Some((position, depth, owner)) = explosions.pop() {
+            explosion_events.single_write(ExplosionEvent { position });
+
+            for (ent, brick, transform) in (&ents, &mut bricks, &transforms).join() {
+                // Bricks that are already broken are skipped, so each one can only go off once
+                if brick.indestructible || brick.hp <= 0 {
+                    continue;
+                }
+
+                let center = transform.position + config.brick_center_offset();
+                if (center - position).magnitude() > BRICK_EXPLOSION_RADIUS {
+                    continue;
+                }
+
+                if damage_brick(ent, brick, &mut sprites, &mut tweens) {
+                    ents.delete(ent).unwrap();
 
-                // Pick and play one of the brick break audio clips
-                let clip_id = {
-                    use rand::Rng;
-                    let roll: f32 = rand::thread_rng().gen();
+                    let points = combo.award(brick.points);
+                    level.score += points;
+                    if let Some(owner) = owner {
+                        scores.add(owner, points);
+                    }
+
+                    popup::spawn_score_popup(&ents, &lazy_updater, center, points);
+                    brick_broken_events.single_write(BrickBrokenEvent {
+                        position: center,
+                        contains_powerup: brick.contains_powerup,
+                    });
 
-                    if roll <= 0.5 {
-                        AudioAssetId::SfxBrickBreak0
-                    } else {
-                        AudioAssetId::SfxBrickBreak1
+                    if brick.explosive && depth < BRICK_EXPLOSION_MAX_CHAIN_DEPTH {
+                        explosions.push((center, depth + 1, owner));
                     }
-                };
+                }
+            }
+        }
+    }
+}
+
+// Takes one hp from the brick, and shows the damage. Returns true if the brick is now broken.
+fn damage_brick(
+    ent: Entity,
+    brick: &mut BrickComponent,
+    sprites: &mut WriteStorage<SpriteComponent>,
+    tweens: &mut WriteStorage<ColorTweenComponent>,
+) -> bool {
+    brick.hp -= 1;
+
+    // Show how damaged the brick is
+    if let Some(region) = brick.damage_region() {
+        if let Some(sprite) = sprites.get_mut(ent) {
+            sprite.region = region;
+        }
+    }
+
+    if brick.hp <= 0 {
+        return true;
+    }
+
+    // Flicker the brick so the hit is noticeable
+    let flash = ColorTweenComponent::new(
+        Color::from_f32(1.0, 1.0, 1.0, 0.5),
+        COLOR_WHITE,
+        BRICK_HIT_FLASH_TICKS,
+    );
+    tweens.insert(ent, flash).unwrap();
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        testing::{self, DT},
+        GameState, Point2d,
+    };
+
+    // A ball to hit bricks with. It's spawned out of the way of everything, and drifts slowly.
+    fn spawn_ball(game: &mut GameState) -> Entity {
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(160.0, 150.0),
+            Vector2d::new(0.0, -4.0),
+        );
+        game.tick(DT);
+
+        testing::balls(&game.world)[0]
+    }
+
+    // Hits the bottom of the brick with the ball, and runs the tick that handles it
+    fn hit_brick(game: &mut GameState, brick_ent: Entity, ball_ent: Entity) {
+        let center = testing::position(&game.world, brick_ent)
+            + game
+                .world
+                .read_resource::<LevelConfig>()
+                .brick_center_offset();
+        testing::collide(
+            &game.world,
+            brick_ent,
+            ball_ent,
+            Vector2d::new(0.0, 1.0),
+            Point2d::from(center),
+        );
+        game.tick(DT);
+    }
+
+    fn region(x: u32) -> SpriteRegion {
+        SpriteRegion {
+            x,
+            y: 0,
+            w: BRICK_SPRITE_WIDTH,
+            h: BRICK_SPRITE_HEIGHT,
+        }
+    }
 
-                audio::play(clip_id, &audio_db, false);
+    #[test]
+    fn damaged_brick_shows_the_region_for_its_hp() {
+        let (mut game, _) = testing::empty_game();
+        let brick_ent = testing::spawn_brick(
+            &mut game.world,
+            Vector2d::new(100.0, 60.0),
+            BrickComponent::new(3, BRICK_DEFAULT_POINTS).with_damage_regions(vec![
+                region(0),
+                region(32),
+                region(64),
+            ]),
+        );
+        let ball_ent = spawn_ball(&mut game);
+
+        hit_brick(&mut game, brick_ent, ball_ent);
+        hit_brick(&mut game, brick_ent, ball_ent);
+
+        let bricks = game.world.read_storage::<BrickComponent>();
+        let sprites = game.world.read_storage::<SpriteComponent>();
+        assert_eq!(bricks.get(brick_ent).unwrap().hp, 1);
+        assert_eq!(sprites.get(brick_ent).unwrap().region, region(0));
+    }
+
+    #[test]
+    fn steel_bricks_never_break_or_score() {
+        let (mut game, _) = testing::empty_game();
+        let brick_ent = testing::spawn_brick(
+            &mut game.world,
+            Vector2d::new(100.0, 60.0),
+            BrickComponent::new_indestructible(),
+        );
+        let ball_ent = spawn_ball(&mut game);
+
+        for _ in 0..5 {
+            hit_brick(&mut game, brick_ent, ball_ent);
+        }
+
+        assert!(game.world.is_alive(brick_ent));
+        assert_eq!(
+            game.world
+                .read_storage::<BrickComponent>()
+                .get(brick_ent)
+                .unwrap()
+                .hp,
+            1
+        );
+        assert_eq!(game.world.read_resource::<LevelState>().score, 0);
+    }
+
+    #[test]
+    fn combo_builds_until_the_ball_hits_the_paddle() {
+        let (mut game, paddle_ent) = testing::empty_game();
+        let bricks: Vec<Entity> = (0..3)
+            .map(|i| {
+                testing::spawn_brick(
+                    &mut game.world,
+                    Vector2d::new(64.0 + i as f64 * 64.0, 60.0),
+                    BrickComponent::new(1, 100),
+                )
+            })
+            .collect();
+        let ball_ent = spawn_ball(&mut game);
+
+        let mut awarded = Vec::new();
+        for brick_ent in bricks {
+            let score = game.world.read_resource::<LevelState>().score;
+            hit_brick(&mut game, brick_ent, ball_ent);
+            awarded.push(game.world.read_resource::<LevelState>().score - score);
+        }
+        assert_eq!(awarded, vec![100, 200, 300]);
+
+        let ball_position = testing::position(&game.world, ball_ent);
+        testing::collide(
+            &game.world,
+            ball_ent,
+            paddle_ent,
+            Vector2d::new(0.0, 1.0),
+            Point2d::from(ball_position),
+        );
+        game.tick(DT);
+
+        assert_eq!(game.world.read_resource::<Combo>().multiplier, 1);
+    }
+
+    #[test]
+    fn bomb_damages_the_bricks_around_it() {
+        let (mut game, _) = testing::empty_game();
+        let bomb_position = Vector2d::new(128.0, 80.0);
+        let bomb_ent = testing::spawn_brick(
+            &mut game.world,
+            bomb_position,
+            BrickComponent::new(1, BRICK_DEFAULT_POINTS).with_explosive(true),
+        );
+        let mut neighbours = Vec::new();
+        for row in -1..=1 {
+            for column in -1..=1 {
+                if row == 0 && column == 0 {
+                    continue;
+                }
+
+                let offset = Vector2d::new(
+                    column as f64 * BRICK_SPRITE_WIDTH as f64,
+                    row as f64 * BRICK_SPRITE_HEIGHT as f64,
+                );
+                neighbours.push(testing::spawn_brick(
+                    &mut game.world,
+                    bomb_position + offset,
+                    BrickComponent::new(2, BRICK_DEFAULT_POINTS),
+                ));
             }
         }
+        let far_ent = testing::spawn_brick(
+            &mut game.world,
+            bomb_position + Vector2d::new(96.0, 0.0),
+            BrickComponent::new(2, BRICK_DEFAULT_POINTS),
+        );
+        let ball_ent = spawn_ball(&mut game);
+
+        hit_brick(&mut game, bomb_ent, ball_ent);
+
+        assert!(!game.world.is_alive(bomb_ent));
+        let bricks = game.world.read_storage::<BrickComponent>();
+        assert_eq!(neighbours.len(), 8);
+        for ent in neighbours {
+            assert_eq!(bricks.get(ent).unwrap().hp, 1);
+        }
+        assert_eq!(bricks.get(far_ent).unwrap().hp, 2);
+    }
+
+    #[test]
+    fn brick_broken_by_player_twos_ball_scores_for_player_two() {
+        let (mut game, _) = testing::empty_game();
+        let brick_ent = testing::spawn_brick(
+            &mut game.world,
+            Vector2d::new(64.0, 60.0),
+            BrickComponent::new(1, BRICK_DEFAULT_POINTS),
+        );
+        let ball_ent = spawn_ball(&mut game);
+        // Players are numbered from 0
+        game.world
+            .write_storage::<BallComponent>()
+            .get_mut(ball_ent)
+            .unwrap()
+            .owner = Some(1);
+
+        hit_brick(&mut game, brick_ent, ball_ent);
+
+        assert!(!game.world.is_alive(brick_ent));
+        let points = game.world.read_resource::<LevelState>().score;
+        assert!(points > 0);
+        let scores = game.world.read_resource::<Scores>();
+        assert_eq!(scores.get(1), points);
+        assert_eq!(scores.get(0), 0);
     }
 }
@@ -0,0 +1,295 @@
+use crate::game::{
+    ball::BallComponent,
+    brick::{BrickComponent, ExplosionEvent},
+    level::BottomWallComponent,
+    paddle::PlayerPaddleComponent,
+    physics::CollisionEvent,
+    rng::RngResource,
+    Vector2d,
+};
+use gfx::window::{self, ScalingMode};
+use shrev::EventChannel;
+use specs::prelude::*;
+
+pub const SCREEN_SHAKE_BRICK_BREAK_MAGNITUDE: f32 = 2.0;
+pub const SCREEN_SHAKE_BRICK_BREAK_TICKS: u32 = 12;
+pub const SCREEN_SHAKE_WALL_HIT_MAGNITUDE: f32 = 1.0;
+pub const SCREEN_SHAKE_WALL_HIT_TICKS: u32 = 6;
+pub const SCREEN_SHAKE_EXPLOSION_MAGNITUDE: f32 = 4.0;
+pub const SCREEN_SHAKE_EXPLOSION_TICKS: u32 = 20;
+
+// Maps level pixels to render space. The level is scaled up as far as it can go while keeping its aspect ratio,
+// and centered, so a resized window gets black bars rather than a stretched or misplaced play field.
+pub struct Camera {
+    // Top left of the view, in level pixels
+    pub position: Vector2d,
+    pub zoom: f32,
+    pub scaling_mode: ScalingMode,
+    view_width: f32,
+    view_height: f32,
+    scale: f32,
+    offset: (f32, f32),
+}
+
+impl Camera {
+    pub fn new(view_width: u32, view_height: u32) -> Self {
+        Camera {
+            position: Vector2d::zeros(),
+            zoom: 1.0,
+            scaling_mode: ScalingMode::Stretch,
+            view_width: view_width as f32,
+            view_height: view_height as f32,
+            scale: 1.0,
+            offset: (0.0, 0.0),
+        }
+    }
+
+    // Recomputes the letterbox scale and offset for a render space of the given size.
+    // The pixel ratio is how many physical pixels one unit of render space covers.
+    pub fn resize(&mut self, screen_width: f32, screen_height: f32, pixel_ratio: f32) {
+        match self.scaling_mode {
+            ScalingMode::Stretch => {
+                self.scale = (screen_width / self.view_width).min(screen_height / self.view_height);
+                self.offset = (
+                    (screen_width - (self.view_width * self.scale)) / 2.0,
+                    (screen_height - (self.view_height * self.scale)) / 2.0,
+                );
+            }
+            ScalingMode::IntegerNearest => {
+                // Every level pixel covers the same whole number of physical pixels,
+                // and the view starts on a physical pixel boundary
+                let pixel_scale = window::integer_scale(
+                    self.view_width as u32,
+                    self.view_height as u32,
+                    (screen_width * pixel_ratio) as u32,
+                    (screen_height * pixel_ratio) as u32,
+                );
+
+                self.scale = pixel_scale as f32 / pixel_ratio;
+                self.offset = (
+                    ((screen_width - (self.view_width * self.scale)) * pixel_ratio / 2.0).floor()
+                        / pixel_ratio,
+                    ((screen_height - (self.view_height * self.scale)) * pixel_ratio / 2.0).floor()
+                        / pixel_ratio,
+                );
+            }
+        }
+    }
+
+    // Total scale applied to anything drawn through the camera
+    pub fn scale(&self) -> f32 {
+        self.scale * self.zoom
+    }
+
+    pub fn world_to_screen(&self, position: Vector2d) -> (f32, f32) {
+        let x = (position.x - self.position.x) as f32 * self.scale() + self.offset.0;
+        let y = (position.y - self.position.y) as f32 * self.scale() + self.offset.1;
+
+        (x, y)
+    }
+
+    pub fn screen_to_world(&self, x: f32, y: f32) -> Vector2d {
+        Vector2d::new(
+            ((x - self.offset.0) / self.scale()) as f64 + self.position.x,
+            ((y - self.offset.1) / self.scale()) as f64 + self.position.y,
+        )
+    }
+}
+
+// Jitters everything drawn through the camera for a short time. This only offsets rendering, never the simulation.
+pub struct ScreenShake {
+    pub magnitude: f32,
+    pub duration_ticks: u32,
+    pub remaining_ticks: u32,
+    pub offset: Vector2d,
+}
+
+impl ScreenShake {
+    pub fn new() -> Self {
+        ScreenShake {
+            magnitude: 0.0,
+            duration_ticks: 0,
+            remaining_ticks: 0,
+            offset: Vector2d::zeros(),
+        }
+    }
+
+    // Starts a shake, unless a stronger one is already going
+    pub fn trigger(&mut self, magnitude: f32, ticks: u32) {
+        if self.current_magnitude() > magnitude {
+            return;
+        }
+
+        self.magnitude = magnitude;
+        self.duration_ticks = ticks;
+        self.remaining_ticks = ticks;
+    }
+
+    // Magnitude of the shake right now. This falls off linearly to 0 over the duration.
+    pub fn current_magnitude(&self) -> f32 {
+        if self.duration_ticks == 0 {
+            return 0.0;
+        }
+
+        self.magnitude * (self.remaining_ticks as f32 / self.duration_ticks as f32)
+    }
+
+    // Advances the shake by one tick and picks a new offset
+    pub fn update(&mut self, rng: &mut RngResource) {
+        use rand::Rng;
+
+        self.remaining_ticks = self.remaining_ticks.saturating_sub(1);
+
+        let magnitude = self.current_magnitude() as f64;
+        self.offset = if magnitude > 0.0 {
+            Vector2d::new(
+                rng.rng.gen_range(-magnitude, magnitude),
+                rng.rng.gen_range(-magnitude, magnitude),
+            )
+        } else {
+            Vector2d::zeros()
+        };
+    }
+}
+
+#[derive(Default)]
+pub struct ScreenShakeSystem {
+    collision_event_reader: Option<ReaderId<CollisionEvent>>,
+    explosion_event_reader: Option<ReaderId<ExplosionEvent>>,
+}
+
+impl<'a> System<'a> for ScreenShakeSystem {
+    type SystemData = (
+        WriteExpect<'a, ScreenShake>,
+        WriteExpect<'a, RngResource>,
+        Read<'a, EventChannel<CollisionEvent>>,
+        Read<'a, EventChannel<ExplosionEvent>>,
+        ReadStorage<'a, BallComponent>,
+        ReadStorage<'a, BrickComponent>,
+        ReadStorage<'a, PlayerPaddleComponent>,
+        ReadStorage<'a, BottomWallComponent>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.collision_event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<CollisionEvent>>()
+                .register_reader(),
+        );
+        self.explosion_event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<ExplosionEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(
+        &mut self,
+        (
+            mut shake,
+            mut rng,
+            collision_events,
+            explosion_events,
+            balls,
+            bricks,
+            paddles,
+            bottom_walls,
+        ): Self::SystemData,
+    ) {
+        for event in collision_events.read(&mut self.collision_event_reader.as_mut().unwrap()) {
+            let (entity_a, entity_b) = match (event.entity_a, event.entity_b) {
+                (Some(a), Some(b)) => (a, b),
+                _ => continue,
+            };
+
+            if balls.get(entity_a).is_none() {
+                continue;
+            }
+
+            // This runs after the BrickSystem, so a brick with no hp left was just broken by this hit
+            if let Some(brick) = bricks.get(entity_b) {
+                if !brick.indestructible && brick.hp <= 0 {
+                    shake.trigger(
+                        SCREEN_SHAKE_BRICK_BREAK_MAGNITUDE,
+                        SCREEN_SHAKE_BRICK_BREAK_TICKS,
+                    );
+                }
+            } else if paddles.get(entity_b).is_none() && bottom_walls.get(entity_b).is_none() {
+                shake.trigger(SCREEN_SHAKE_WALL_HIT_MAGNITUDE, SCREEN_SHAKE_WALL_HIT_TICKS);
+            }
+        }
+
+        if explosion_events
+            .read(&mut self.explosion_event_reader.as_mut().unwrap())
+            .count()
+            > 0
+        {
+            shake.trigger(
+                SCREEN_SHAKE_EXPLOSION_MAGNITUDE,
+                SCREEN_SHAKE_EXPLOSION_TICKS,
+            );
+        }
+
+        shake.update(&mut rng);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn world_to_screen_applies_the_camera() {
+        let mut camera = Camera::new(320, 240);
+        camera.resize(640.0, 480.0, 1.0);
+        assert_eq!(
+            camera.world_to_screen(Vector2d::new(10.0, 20.0)),
+            (20.0, 40.0)
+        );
+
+        camera.position = Vector2d::new(5.0, 5.0);
+        camera.zoom = 1.5;
+        assert_eq!(
+            camera.world_to_screen(Vector2d::new(10.0, 20.0)),
+            (15.0, 45.0)
+        );
+    }
+
+    #[test]
+    fn wide_window_letterboxes_the_level() {
+        let mut camera = Camera::new(320, 240);
+        camera.resize(800.0, 480.0, 1.0);
+        assert_eq!(camera.world_to_screen(Vector2d::zeros()), (80.0, 0.0));
+        assert_eq!(
+            camera.world_to_screen(Vector2d::new(320.0, 240.0)),
+            (720.0, 480.0)
+        );
+    }
+
+    #[test]
+    fn screen_shake_decays_to_nothing() {
+        let mut rng = RngResource::new(0);
+        let mut shake = ScreenShake::new();
+        shake.trigger(
+            SCREEN_SHAKE_BRICK_BREAK_MAGNITUDE,
+            SCREEN_SHAKE_BRICK_BREAK_TICKS,
+        );
+
+        shake.update(&mut rng);
+        assert!(shake.offset.norm() > 0.0);
+
+        let mut last_magnitude = shake.current_magnitude();
+        for _ in 1..SCREEN_SHAKE_BRICK_BREAK_TICKS {
+            shake.update(&mut rng);
+            let magnitude = shake.current_magnitude();
+            assert!(magnitude < last_magnitude);
+            assert!(shake.offset.x.abs() <= magnitude as f64);
+            assert!(shake.offset.y.abs() <= magnitude as f64);
+            last_magnitude = magnitude;
+        }
+
+        assert_eq!(shake.current_magnitude(), 0.0);
+        assert_eq!(shake.offset, Vector2d::zeros());
+    }
+}
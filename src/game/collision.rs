@@ -0,0 +1,74 @@
+use ncollide2d::pipeline::CollisionGroups;
+
+// Collision groups. Every collider is a member of exactly one of these.
+pub const BALL_GROUP: usize = 0;
+pub const BRICK_GROUP: usize = 1;
+pub const PADDLE_GROUP: usize = 2;
+pub const WALL_GROUP: usize = 3;
+pub const BOTTOM_WALL_GROUP: usize = 4;
+pub const POWERUP_GROUP: usize = 5;
+pub const PROJECTILE_GROUP: usize = 6;
+
+#[derive(Default)]
+pub struct CollisionSettings {
+    // Whether balls bounce off of each other. Most brick breakers let them pass through, so this is off by default.
+    pub ball_ball_collisions: bool,
+    // Bounces balls off of bricks as if every hit was square on a face, keeping their speed, for the classic feel.
+    // Otherwise the contact normal is used as is, so corner hits glance off at an angle.
+    pub axis_aligned_brick_bounces: bool,
+}
+
+// Balls bounce off of everything except power-ups, and other balls unless ball_ball_collisions is set
+pub fn ball_groups(ball_ball_collisions: bool) -> CollisionGroups {
+    let groups = CollisionGroups::new().with_membership(&[BALL_GROUP]);
+    if ball_ball_collisions {
+        groups.with_blacklist(&[POWERUP_GROUP])
+    } else {
+        groups.with_blacklist(&[BALL_GROUP, POWERUP_GROUP])
+    }
+}
+
+pub fn brick_groups() -> CollisionGroups {
+    CollisionGroups::new().with_membership(&[BRICK_GROUP])
+}
+
+pub fn paddle_groups() -> CollisionGroups {
+    CollisionGroups::new().with_membership(&[PADDLE_GROUP])
+}
+
+pub fn wall_groups() -> CollisionGroups {
+    CollisionGroups::new().with_membership(&[WALL_GROUP])
+}
+
+pub fn bottom_wall_groups() -> CollisionGroups {
+    CollisionGroups::new().with_membership(&[BOTTOM_WALL_GROUP])
+}
+
+// Power-up capsules fall straight through everything, until they are caught by the paddle or hit the bottom wall
+pub fn powerup_groups() -> CollisionGroups {
+    CollisionGroups::new()
+        .with_membership(&[POWERUP_GROUP])
+        .with_whitelist(&[PADDLE_GROUP, BOTTOM_WALL_GROUP])
+}
+
+// Laser projectiles only hit bricks and walls, and are gone as soon as they do
+pub fn projectile_groups() -> CollisionGroups {
+    CollisionGroups::new()
+        .with_membership(&[PROJECTILE_GROUP])
+        .with_whitelist(&[BRICK_GROUP, WALL_GROUP])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capsules_only_interact_with_the_paddle_and_bottom_wall() {
+        let capsule = powerup_groups();
+        assert!(capsule.can_interact_with_groups(&paddle_groups()));
+        assert!(capsule.can_interact_with_groups(&bottom_wall_groups()));
+        assert!(!capsule.can_interact_with_groups(&brick_groups()));
+        assert!(!capsule.can_interact_with_groups(&wall_groups()));
+        assert!(!capsule.can_interact_with_groups(&ball_groups(true)));
+    }
+}
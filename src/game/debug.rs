@@ -0,0 +1,136 @@
+use crate::game::{
+    camera::Camera,
+    level::LevelConfig,
+    physics::{ColliderComponent, PhysicsState},
+    render::{RenderState, HUD_TEXT_SCALE},
+    transform::TransformComponent,
+    Vector2d, PIXELS_PER_WORLD_UNIT,
+};
+use gfx::{color::Color, font::Font, renderer::Transparency};
+use ncollide2d::shape::Cuboid;
+use specs::prelude::*;
+
+// Drawn above the sprites, but below the HUD
+pub const DEBUG_DRAW_LAYER: u8 = 9;
+pub const DEBUG_DRAW_LINE_WIDTH: f32 = 1.0;
+
+// Draws collider outlines over everything, so they can be compared against the sprites.
+#[derive(Default)]
+pub struct DebugDraw {
+    pub enabled: bool,
+}
+
+// Screen space rectangle (left, top, right, bottom) covered by a cuboid collider.
+// The offset is in pixels, like the transform position, while the half extents are in world units.
+pub fn cuboid_screen_rect(
+    camera: &Camera,
+    position: Vector2d,
+    offset: Vector2d,
+    half_extents: Vector2d,
+) -> (f32, f32, f32, f32) {
+    let center = position + offset;
+    let half_extents = half_extents * PIXELS_PER_WORLD_UNIT as f64;
+    let (left, top) = camera.world_to_screen(center - half_extents);
+    let (right, bottom) = camera.world_to_screen(center + half_extents);
+
+    (left, top, right, bottom)
+}
+
+#[derive(Default)]
+pub struct DebugDrawSystem;
+
+impl<'a> System<'a> for DebugDrawSystem {
+    type SystemData = (
+        Read<'a, DebugDraw>,
+        ReadExpect<'a, Font>,
+        ReadExpect<'a, Camera>,
+        ReadExpect<'a, LevelConfig>,
+        ReadExpect<'a, PhysicsState>,
+        Write<'a, RenderState>,
+        ReadStorage<'a, TransformComponent>,
+        ReadStorage<'a, ColliderComponent>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            debug_draw,
+            font,
+            camera,
+            config,
+            physics,
+            mut render,
+            transforms,
+            colliders,
+        ): Self::SystemData,
+    ) {
+        if !debug_draw.enabled {
+            return;
+        }
+
+        render.bind_layer(DEBUG_DRAW_LAYER);
+        render.bind_transparency(Transparency::Transparent);
+        for (transform, collider) in (&transforms, &colliders).join() {
+            // Only cuboids are drawn for now. Rotation is ignored.
+            let cuboid = match collider.shape.as_shape::<Cuboid<f64>>() {
+                Some(cuboid) => cuboid,
+                None => continue,
+            };
+
+            // Sensors are drawn in a different color, since they don't physically collide with anything
+            let color = if collider.sensor {
+                Color::from_f32(0.0, 1.0, 1.0, 0.75)
+            } else {
+                Color::from_f32(1.0, 0.0, 1.0, 0.75)
+            };
+
+            let (left, top, right, bottom) = cuboid_screen_rect(
+                &camera,
+                transform.position,
+                collider.offset,
+                *cuboid.half_extents(),
+            );
+
+            render.bind_color(color);
+            render.rect_outline(left, top, right, bottom, DEBUG_DRAW_LINE_WIDTH);
+        }
+
+        // Physics object counts, in the bottom left corner. These should stay steady while playing.
+        let text = format!(
+            "Bodies: {} Colliders: {} Entities: {}",
+            physics.body_count(),
+            physics.collider_count(),
+            physics.tracked_entity_count()
+        );
+        let scale = HUD_TEXT_SCALE * camera.scale();
+        let (x, y) = camera.world_to_screen(Vector2d::new(2.0, config.field_height as f64 - 2.0));
+        render.bind_color(Color::from_f32(1.0, 1.0, 0.0, 1.0));
+        render.draw_text(
+            &font,
+            x,
+            y - (font.glyph_height as f32 * scale),
+            scale,
+            &text,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cuboid_covers_its_half_extents_around_the_offset() {
+        let mut camera = Camera::new(320, 240);
+        camera.resize(640.0, 480.0, 1.0);
+
+        // 16 by 8 pixels each way, around (100, 54)
+        let rect = cuboid_screen_rect(
+            &camera,
+            Vector2d::new(100.0, 50.0),
+            Vector2d::new(0.0, 4.0),
+            Vector2d::new(0.5, 0.25),
+        );
+        assert_eq!(rect, (168.0, 92.0, 232.0, 124.0));
+    }
+}
@@ -0,0 +1,122 @@
+use crate::game::{
+    ball::{BallComponent, BALL_MAX_LINEAR_VELOCITY},
+    level::GamePhase,
+    TimeScale,
+};
+use gfx::window::DeltaTime;
+use log::debug;
+use specs::prelude::*;
+
+pub const DIFFICULTY_STEP_INTERVAL: f64 = 15.0;
+pub const DIFFICULTY_SPEED_STEP: f64 = 0.05;
+pub const DIFFICULTY_MAX_SPEED_SCALE: f64 = 1.5;
+
+// Speeds balls up the longer a level goes on, so games don't stall out
+pub struct DifficultyState {
+    // Seconds spent playing the current level
+    pub elapsed: f64,
+    pub speed_scale: f64,
+    pub step_interval: f64,
+    pub speed_step: f64,
+    pub max_speed_scale: f64,
+}
+
+impl DifficultyState {
+    pub fn new() -> Self {
+        DifficultyState {
+            elapsed: 0.0,
+            speed_scale: 1.0,
+            step_interval: DIFFICULTY_STEP_INTERVAL,
+            speed_step: DIFFICULTY_SPEED_STEP,
+            max_speed_scale: DIFFICULTY_MAX_SPEED_SCALE,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+        self.speed_scale = 1.0;
+    }
+}
+
+#[derive(Default)]
+pub struct DifficultySystem;
+
+impl<'a> System<'a> for DifficultySystem {
+    type SystemData = (
+        WriteExpect<'a, DifficultyState>,
+        Read<'a, DeltaTime>,
+        Read<'a, TimeScale>,
+        Read<'a, GamePhase>,
+        WriteStorage<'a, BallComponent>,
+    );
+
+    fn run(&mut self, (mut difficulty, dt, time_scale, phase, mut balls): Self::SystemData) {
+        if !phase.is_simulating() {
+            return;
+        }
+
+        let last_step = (difficulty.elapsed / difficulty.step_interval).floor();
+        difficulty.elapsed += *dt * time_scale.get();
+        let step = (difficulty.elapsed / difficulty.step_interval).floor();
+        if step <= last_step {
+            return;
+        }
+
+        let old_speed_scale = difficulty.speed_scale;
+        difficulty.speed_scale = (difficulty.speed_scale * (1.0 + difficulty.speed_step))
+            .min(difficulty.max_speed_scale);
+        if difficulty.speed_scale <= old_speed_scale {
+            return;
+        }
+
+        debug!(
+            target: "difficulty",
+            "[DifficultySystem] Ball speed scale is now {:.2}",
+            difficulty.speed_scale
+        );
+
+        // Only the speed changes, so balls keep going in the same direction
+        let ratio = difficulty.speed_scale / old_speed_scale;
+        for ball in (&mut balls).join() {
+            if !ball.is_launched() {
+                continue;
+            }
+
+            let speed = (ball.velocity.linear.magnitude() * ratio).min(BALL_MAX_LINEAR_VELOCITY);
+            if let Some(direction) = ball.velocity.linear.try_normalize(std::f64::EPSILON) {
+                ball.velocity.linear = direction * speed;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Vector2d;
+
+    #[test]
+    fn difficulty_step_speeds_balls_up_without_turning_them() {
+        let mut world = World::new();
+        world.register::<BallComponent>();
+        let mut difficulty = DifficultyState::new();
+        difficulty.elapsed = DIFFICULTY_STEP_INTERVAL - 0.01;
+        world.insert(difficulty);
+        world.insert::<DeltaTime>(1.0 / 60.0);
+        world.insert(TimeScale::default());
+        world.insert(GamePhase::Playing);
+
+        let velocity = Vector2d::new(3.0, -4.0);
+        let ball_ent = world
+            .create_entity()
+            .with(BallComponent::new(velocity, None))
+            .build();
+
+        DifficultySystem.run_now(&world);
+
+        let balls = world.read_storage::<BallComponent>();
+        let new_velocity = balls.get(ball_ent).unwrap().velocity.linear;
+        assert!((new_velocity.magnitude() - 5.0 * (1.0 + DIFFICULTY_SPEED_STEP)).abs() < 1e-9);
+        assert!(new_velocity.angle(&velocity) < 1e-6);
+    }
+}
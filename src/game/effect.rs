@@ -0,0 +1,96 @@
+use specs::prelude::*;
+use std::marker::PhantomData;
+
+// Puts a time limit on the effect component T of the same entity. Once the timer runs out, both this and the
+// effect are removed, and whatever system handles the effect goes back to its normal behaviour.
+pub struct TimedEffectComponent<T> {
+    pub ticks_remaining: u32,
+    effect: PhantomData<T>,
+}
+
+impl<T> TimedEffectComponent<T> {
+    pub fn new(ticks: u32) -> Self {
+        TimedEffectComponent {
+            ticks_remaining: ticks,
+            effect: PhantomData,
+        }
+    }
+}
+
+impl<T: Component + Send + Sync> Component for TimedEffectComponent<T> {
+    type Storage = VecStorage<Self>;
+}
+
+// Counts down the timers on effects of type T. An effect that lasts n ticks is active for exactly n ticks,
+// and removed on the tick after.
+pub struct TimedEffectSystem<T> {
+    effect: PhantomData<T>,
+}
+
+impl<T> Default for TimedEffectSystem<T> {
+    fn default() -> Self {
+        TimedEffectSystem {
+            effect: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: Component + Send + Sync> System<'a> for TimedEffectSystem<T> {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, TimedEffectComponent<T>>,
+        WriteStorage<'a, T>,
+    );
+
+    fn run(&mut self, (ents, mut timers, mut effects): Self::SystemData) {
+        let mut expired_effects: Vec<Entity> = Vec::new();
+        for (ent, timer) in (&ents, &mut timers).join() {
+            if timer.ticks_remaining == 0 {
+                expired_effects.push(ent);
+            } else {
+                timer.ticks_remaining -= 1;
+            }
+        }
+
+        for ent in expired_effects {
+            timers.remove(ent);
+            effects.remove(ent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct TestEffect;
+
+    impl Component for TestEffect {
+        type Storage = NullStorage<Self>;
+    }
+
+    #[test]
+    fn three_tick_effect_is_removed_on_the_fourth_tick() {
+        let mut world = World::new();
+        world.register::<TestEffect>();
+        world.register::<TimedEffectComponent<TestEffect>>();
+        let ent = world
+            .create_entity()
+            .with(TestEffect)
+            .with(TimedEffectComponent::<TestEffect>::new(3))
+            .build();
+        let mut system = TimedEffectSystem::<TestEffect>::default();
+
+        for _ in 0..3 {
+            system.run_now(&world);
+            assert!(world.read_storage::<TestEffect>().contains(ent));
+        }
+
+        system.run_now(&world);
+        assert!(!world.read_storage::<TestEffect>().contains(ent));
+        assert!(!world
+            .read_storage::<TimedEffectComponent<TestEffect>>()
+            .contains(ent));
+    }
+}
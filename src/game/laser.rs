@@ -0,0 +1,247 @@
+use crate::game::{
+    assets::Assets,
+    bindings::{Action, InputBindings},
+    collision,
+    level::LevelConfig,
+    paddle::PlayerPaddleComponent,
+    physics::{ColliderComponent, CollisionEvent, RigidbodyComponent},
+    render::SpriteComponent,
+    transform::TransformComponent,
+    Point2f, Vector2d, Vector2f, WORLD_UNIT_RATIO,
+};
+use gfx::{color::*, input::InputState, renderer::Transparency, sprite::SpriteRegion};
+use nalgebra::Vector2;
+use ncollide2d::shape::Ball;
+use nphysics2d::object::BodyStatus;
+use shrev::EventChannel;
+use specs::prelude::*;
+
+pub const LASER_EFFECT_TICKS: u32 = 60 * 10;
+pub const LASER_FIRE_COOLDOWN_TICKS: u32 = 15;
+pub const LASER_PROJECTILE_SPEED: f64 = 10.0;
+pub const LASER_PROJECTILE_RADIUS: f64 = 2.0;
+
+// Lets a paddle shoot projectiles at the bricks. A TimedEffectComponent removes this once it runs out.
+pub struct LaserPaddleComponent {
+    pub cooldown_ticks: u32,
+}
+
+impl LaserPaddleComponent {
+    pub fn new() -> Self {
+        LaserPaddleComponent { cooldown_ticks: 0 }
+    }
+}
+
+impl Component for LaserPaddleComponent {
+    type Storage = VecStorage<Self>;
+}
+
+// A laser shot. Bricks take damage from these the same way they do from balls.
+#[derive(Default)]
+pub struct ProjectileComponent;
+
+impl Component for ProjectileComponent {
+    type Storage = NullStorage<Self>;
+}
+
+#[derive(Default)]
+pub struct LaserSystem {
+    collision_event_reader: Option<ReaderId<CollisionEvent>>,
+}
+
+impl<'a> System<'a> for LaserSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, LazyUpdate>,
+        ReadExpect<'a, InputState>,
+        Read<'a, InputBindings>,
+        ReadExpect<'a, LevelConfig>,
+        ReadExpect<'a, Assets>,
+        Read<'a, EventChannel<CollisionEvent>>,
+        ReadStorage<'a, TransformComponent>,
+        ReadStorage<'a, PlayerPaddleComponent>,
+        WriteStorage<'a, LaserPaddleComponent>,
+        ReadStorage<'a, ProjectileComponent>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.collision_event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<CollisionEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(
+        &mut self,
+        (
+            ents,
+            lazy_updater,
+            input,
+            bindings,
+            config,
+            assets,
+            collision_events,
+            transforms,
+            paddles,
+            mut lasers,
+            projectiles,
+        ): Self::SystemData,
+    ) {
+        // Projectiles are used up by the first thing they hit
+        let mut removed_projectiles: BitSet = BitSet::new();
+        for event in collision_events.read(&mut self.collision_event_reader.as_mut().unwrap()) {
+            let entity_a = match event.entity_a {
+                Some(ent) => ent,
+                None => continue,
+            };
+
+            if projectiles.get(entity_a).is_some() && !removed_projectiles.contains(entity_a.id()) {
+                ents.delete(entity_a).unwrap();
+                removed_projectiles.add(entity_a.id());
+            }
+        }
+
+        for (transform, paddle, laser) in (&transforms, &paddles, &mut lasers).join() {
+            laser.cooldown_ticks = laser.cooldown_ticks.saturating_sub(1);
+
+            // The fire key launches a held ball instead
+            if paddle.held_ball_ent.is_some()
+                || laser.cooldown_ticks > 0
+                || !paddle
+                    .bindings(&bindings, Action::Launch)
+                    .is_down(&input, Action::Launch)
+            {
+                continue;
+            }
+
+            // Fire from both ends of the paddle
+            let y = transform.position.y - (config.paddle_height / 2.0) - LASER_PROJECTILE_RADIUS;
+            for side in [-1.0, 1.0].iter() {
+                let x = transform.position.x + (side * (paddle.width / 2.0 - 4.0));
+                spawn_projectile(&ents, &lazy_updater, &assets, Vector2d::new(x, y));
+            }
+
+            laser.cooldown_ticks = LASER_FIRE_COOLDOWN_TICKS;
+        }
+    }
+}
+
+pub fn spawn_projectile(
+    ents: &Entities,
+    lazy_updater: &LazyUpdate,
+    assets: &Assets,
+    position: Vector2d,
+) -> Entity {
+    let ent = ents.create();
+
+    lazy_updater.insert(
+        ent,
+        TransformComponent::new(position, Point2f::new(16.0, 16.0), Vector2f::new(0.5, 1.0)),
+    );
+
+    lazy_updater.insert(
+        ent,
+        SpriteComponent {
+            color: COLOR_RED,
+            spritesheet_tex_id: assets.texture("sprites"),
+            region: SpriteRegion {
+                x: 64,
+                y: 0,
+                w: 32,
+                h: 32,
+            },
+            layer: 2,
+            transparency: Transparency::Opaque,
+            flip_x: false,
+            flip_y: false,
+        },
+    );
+
+    lazy_updater.insert(ent, ProjectileComponent);
+
+    lazy_updater.insert(
+        ent,
+        RigidbodyComponent::new(
+            1.0,
+            Vector2::new(0.0, -LASER_PROJECTILE_SPEED),
+            LASER_PROJECTILE_SPEED,
+            BodyStatus::Dynamic,
+        ),
+    );
+
+    lazy_updater.insert(
+        ent,
+        ColliderComponent::new(
+            Ball::new(LASER_PROJECTILE_RADIUS * WORLD_UNIT_RATIO),
+            Vector2::zeros(),
+            collision::projectile_groups(),
+            0.0,
+        )
+        .with_ccd(true),
+    );
+
+    ent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        brick::{BrickComponent, BRICK_DEFAULT_POINTS},
+        testing::{self, DT},
+        Point2d,
+    };
+    use gfx::input::VirtualKeyCode;
+
+    fn projectiles(world: &World) -> Vec<Entity> {
+        let ents = world.entities();
+        let projectiles = world.read_storage::<ProjectileComponent>();
+        (&ents, &projectiles).join().map(|(ent, _)| ent).collect()
+    }
+
+    #[test]
+    fn projectile_damages_a_brick_and_is_used_up() {
+        let (mut game, paddle_ent) = testing::empty_game();
+        game.world
+            .write_storage::<LaserPaddleComponent>()
+            .insert(paddle_ent, LaserPaddleComponent::new())
+            .unwrap();
+        // Out of the way of the projectiles, so only the made up collision reaches it
+        let brick_ent = testing::spawn_brick(
+            &mut game.world,
+            Vector2d::new(0.0, 60.0),
+            BrickComponent::new(2, BRICK_DEFAULT_POINTS),
+        );
+
+        game.world
+            .insert(testing::pressed(&[VirtualKeyCode::Space]));
+        game.tick(DT);
+        game.world.insert(InputState::new());
+
+        let fired = projectiles(&game.world);
+        assert_eq!(fired.len(), 2);
+
+        let projectile_ent = fired[0];
+        let position = testing::position(&game.world, projectile_ent);
+        testing::collide(
+            &game.world,
+            brick_ent,
+            projectile_ent,
+            Vector2d::new(0.0, 1.0),
+            Point2d::from(position),
+        );
+        game.tick(DT);
+
+        assert!(!game.world.is_alive(projectile_ent));
+        assert_eq!(
+            game.world
+                .read_storage::<BrickComponent>()
+                .get(brick_ent)
+                .unwrap()
+                .hp,
+            1
+        );
+    }
+}
@@ -1,152 +1,625 @@
 use crate::game::{
-    ball::SpawnBallEvent,
-    brick::{self, BrickComponent},
+    assets::Assets,
+    audio::{self, AudioState},
+    ball::{self, BallComponent, BallLostEvent, SpawnBallEvent},
+    bindings::InputBindings,
+    brick::{self, BrickComponent, Combo, Scores},
+    collision,
+    difficulty::DifficultyState,
     paddle::{self, PlayerPaddleComponent},
-    physics::ColliderComponent,
+    physics::{ColliderComponent, PhysicsMaterial, PhysicsState},
+    powerup::PowerUpKind,
     render::SpriteComponent,
+    rng::RngResource,
     transform::TransformComponent,
     Point2f, Vector2d, Vector2f, WORLD_UNIT_RATIO,
 };
 use gfx::{color::*, renderer::Transparency, sprite::SpriteRegion};
+use log::{debug, info, warn};
 use nalgebra::Vector2;
 use ncollide2d::shape::Cuboid;
+use serde::{Deserialize, Serialize};
 use shrev::EventChannel;
 use specs::prelude::*;
+use std::{error::Error, path::PathBuf, time::SystemTime};
 
 pub const PLAYER_DEFAULT_BALLS: u32 = 3;
 pub const LEVEL_BRICKS_Y_OFFSET: f64 = 22.0;
 pub const LEVEL_BRICKS_WIDTH: u32 = 10;
 pub const LEVEL_BRICKS_HEIGHT: u32 = 5;
+pub const LEVELS_DIR: &str = "res/levels";
+pub const SAFETY_NET_TICKS: u32 = 60 * 10;
+// How often the layout file is checked for changes, while hot reloading is enabled
+pub const LEVEL_HOT_RELOAD_INTERVAL_TICKS: u32 = 30;
 
 #[derive(Default)]
 pub struct LevelState {
+    pub level: u32,
     pub score: u32,
-    pub lives: u32,
     pub player_paddle_ent: Option<Entity>,
     pub load_level_event: Option<LoadLevelEvent>,
-    pub level_width: u32,
-    pub level_height: u32,
+    // Layout file to spawn bricks from, relative to LEVELS_DIR. If None, the default brick grid is used.
+    pub layout_path: Option<String>,
 }
 
 impl LevelState {
-    pub fn new(
-        level_width: u32,
-        level_height: u32,
-        load_level_event: LoadLevelEvent,
-    ) -> LevelState {
+    pub fn new(load_level_event: LoadLevelEvent) -> LevelState {
         LevelState {
+            level: 1,
             score: 0,
-            lives: 3,
             player_paddle_ent: None,
             load_level_event: Some(load_level_event),
-            level_width,
-            level_height,
+            layout_path: None,
         }
     }
 
     pub fn reset(&mut self, player_paddle_ent: Entity) {
         self.score = 0;
-        self.lives = PLAYER_DEFAULT_BALLS;
         self.player_paddle_ent = Some(player_paddle_ent);
         self.load_level_event = None;
     }
 }
 
-#[derive(Clone, Copy)]
-pub struct LoadLevelEvent;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadLevelEvent {
+    // Starts over with a fresh score and lives
+    NewGame,
+    // Moves on to the next level, keeping the score and lives
+    NextLevel,
+    // Plays the current level again from the start, keeping the score
+    RestartLevel,
+}
+
+// Sent once every breakable brick in the level is gone
+#[derive(Clone, Copy, Debug)]
+pub struct LevelCompleteEvent {
+    pub level: u32,
+}
+
+// Where the play field sits in the view, in pixels. Everything in the level (bricks, paddles, balls and walls)
+// is positioned relative to the top left of the play field, so the view can leave space around it for the HUD.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlayField {
+    pub x: f64,
+    pub y: f64,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PlayField {
+    pub fn new(x: f64, y: f64, width: u32, height: u32) -> Self {
+        PlayField {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // A play field that covers the whole view
+    pub fn fill(view_width: u32, view_height: u32) -> Self {
+        PlayField::new(0.0, 0.0, view_width, view_height)
+    }
+
+    // Camera position that puts the play field where it belongs in the view
+    pub fn camera_position(&self) -> Vector2d {
+        Vector2d::new(-self.x, -self.y)
+    }
+}
+
+// Sizes of the play field and the pieces in it, in pixels. The defaults match the sprite sizes.
+#[derive(Clone, Debug)]
+pub struct LevelConfig {
+    pub field_width: u32,
+    pub field_height: u32,
+    pub brick_width: f64,
+    pub brick_height: f64,
+    pub paddle_width: f64,
+    pub paddle_height: f64,
+    pub paddle_scale: Vector2f,
+    // Number of paddles, one for each player
+    pub players: u32,
+    // Number of balls in play when a level starts. The first is held on the paddle, and the rest are already
+    // on their way up from the middle of the field.
+    pub starting_balls: u32,
+}
+
+impl LevelConfig {
+    pub fn new(field_width: u32, field_height: u32) -> Self {
+        LevelConfig {
+            field_width,
+            field_height,
+            brick_width: brick::BRICK_SPRITE_WIDTH as f64,
+            brick_height: brick::BRICK_SPRITE_HEIGHT as f64,
+            paddle_width: paddle::PADDLE_HIT_BOX_WIDTH,
+            paddle_height: paddle::PADDLE_HIT_BOX_HEIGHT,
+            paddle_scale: Vector2f::new(paddle::PADDLE_SCALE_X, paddle::PADDLE_SCALE_Y),
+            players: 1,
+            starting_balls: 1,
+        }
+    }
+
+    // Where the given player's paddle starts. Paddles are spread evenly across the field.
+    pub fn paddle_spawn_x(&self, player_id: u32) -> f64 {
+        self.field_width as f64 * (player_id + 1) as f64 / (self.players + 1) as f64
+    }
+
+    // Half extents of a brick collider, in world units
+    pub fn brick_half_extents(&self) -> Vector2<f64> {
+        Vector2::new(
+            (self.brick_width / 2.0) * WORLD_UNIT_RATIO,
+            (self.brick_height / 2.0) * WORLD_UNIT_RATIO,
+        )
+    }
+
+    // Offset from a brick's position (its top left corner) to its center
+    pub fn brick_center_offset(&self) -> Vector2d {
+        Vector2d::new(self.brick_width / 2.0, self.brick_height / 2.0)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BrickDesc {
+    pub position: (f64, f64),
+    pub hp: i32,
+    // Region of the brick sprite on the spritesheet, as (x, y, w, h)
+    pub region: (u32, u32, u32, u32),
+    pub points: u32,
+    // Regions to show as the brick is damaged, indexed by remaining hp - 1
+    #[serde(default)]
+    pub damage_regions: Vec<(u32, u32, u32, u32)>,
+    #[serde(default)]
+    pub indestructible: bool,
+    #[serde(default)]
+    pub explosive: bool,
+    // Power-up the brick always drops when broken, like Some(MultiBall)
+    #[serde(default)]
+    pub contains_powerup: Option<PowerUpKind>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LevelLayout {
+    pub bricks: Vec<BrickDesc>,
+}
+
+impl LevelLayout {
+    pub fn load(path: &str) -> Result<LevelLayout, Box<dyn Error>> {
+        let source = std::fs::read_to_string(resolve_level_path(path))?;
+        let layout = ron::de::from_str(&source)?;
+
+        Ok(layout)
+    }
+}
+
+// Level paths are relative to the levels directory, unless they are absolute.
+pub fn resolve_level_path(path: &str) -> PathBuf {
+    PathBuf::from(LEVELS_DIR).join(path)
+}
+
+// Layout file for the given level number, relative to the levels directory
+pub fn level_layout_path(level: u32) -> String {
+    format!("level-{}.ron", level)
+}
+
+pub struct Lives {
+    pub count: u32,
+}
+
+impl Lives {
+    pub fn new(count: u32) -> Self {
+        Lives { count }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GamePhase {
+    // Waiting for the player to start a game
+    MainMenu,
+    Playing,
+    Paused,
+    LevelComplete,
+    GameOver,
+    // Every level has been completed
+    Victory,
+}
+
+impl GamePhase {
+    // True if the level has ended in a win, and nothing should move anymore
+    pub fn is_level_won(self) -> bool {
+        self == GamePhase::LevelComplete || self == GamePhase::Victory
+    }
+
+    // Whether the gameplay systems run. Everything else (like moving on to the next level) is left to
+    // the menu dispatcher, which runs in every phase.
+    pub fn is_simulating(self) -> bool {
+        self == GamePhase::Playing
+    }
+}
+
+impl Default for GamePhase {
+    fn default() -> Self {
+        GamePhase::Playing
+    }
+}
+
+// Marks the walls around the level, including the bottom one
+pub struct WallComponent;
+
+impl Component for WallComponent {
+    type Storage = NullStorage<Self>;
+}
+
+impl Default for WallComponent {
+    fn default() -> Self {
+        WallComponent
+    }
+}
+
+// Marks the wall below the paddle. Unless it has been made solid, any ball that touches it is lost.
+pub struct BottomWallComponent;
+
+impl Component for BottomWallComponent {
+    type Storage = NullStorage<Self>;
+}
+
+impl Default for BottomWallComponent {
+    fn default() -> Self {
+        BottomWallComponent
+    }
+}
+
+// Takes a life once the last ball in play is lost, and hands every paddle a new ball if there are any left
+#[derive(Default)]
+pub struct LivesSystem {
+    ball_lost_event_reader: Option<ReaderId<BallLostEvent>>,
+}
+
+impl<'a> System<'a> for LivesSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, Lives>,
+        Write<'a, GamePhase>,
+        Read<'a, EventChannel<BallLostEvent>>,
+        Write<'a, EventChannel<SpawnBallEvent>>,
+        ReadStorage<'a, BallComponent>,
+        ReadStorage<'a, PlayerPaddleComponent>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.ball_lost_event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<BallLostEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(
+        &mut self,
+        (
+            ents,
+            mut lives,
+            mut phase,
+            ball_lost_events,
+            mut spawn_ball_events,
+            balls,
+            paddles,
+        ): Self::SystemData,
+    ) {
+        let balls_lost = ball_lost_events
+            .read(&mut self.ball_lost_event_reader.as_mut().unwrap())
+            .count();
+        if balls_lost == 0 {
+            return;
+        }
+
+        // Lost balls are deleted, but they still show up here until the world is maintained
+        let active_balls = ball::count_active_balls(&ents, &balls);
+        if balls_lost < active_balls {
+            return;
+        }
+
+        lives.count = lives.count.saturating_sub(1);
+        info!(target: "level", "{} balls remaining.", lives.count);
+        if lives.count == 0 {
+            *phase = GamePhase::GameOver;
+            info!(target: "level", "Game over!");
+        } else {
+            // Spawn another ball on each paddle, held there until it is launched
+            for (paddle_ent, _) in (&ents, &paddles).join() {
+                spawn_ball_events.single_write(SpawnBallEvent {
+                    position: Vector2d::zeros(),
+                    linear_velocity: Vector2d::zeros(),
+                    owning_paddle_ent: Some(paddle_ent),
+                    radius: ball::BALL_DEFAULT_RADIUS,
+                    owner: None,
+                });
+            }
+        }
+    }
+}
+
+// Whether the bottom wall is solid (balls bounce off of it) or a sensor (balls fall into it and are lost)
+#[derive(Default)]
+pub struct BottomWallState {
+    pub solid: bool,
+    // While above 0, the wall is held solid by the safety net. It goes back to being a sensor once this runs out.
+    pub safety_net_ticks: u32,
+}
+
+impl BottomWallState {
+    pub fn set_safety_net(&mut self, ticks: u32) {
+        self.solid = true;
+        self.safety_net_ticks = ticks;
+    }
+}
+
+// Keeps the bottom wall colliders in line with the BottomWallState
+pub struct BottomWallSystem;
+
+impl<'a> System<'a> for BottomWallSystem {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, BottomWallState>,
+        ReadStorage<'a, BottomWallComponent>,
+        WriteStorage<'a, ColliderComponent>,
+    );
+
+    fn run(&mut self, (ents, mut state, bottom_walls, mut colliders): Self::SystemData) {
+        if state.safety_net_ticks > 0 {
+            state.safety_net_ticks -= 1;
+            if state.safety_net_ticks == 0 {
+                state.solid = false;
+                debug!(target: "level", "[BottomWallSystem] Safety net is gone");
+            }
+        }
+
+        // Only fetch the colliders mutably when they actually change, since that flags them for a physics update
+        let is_sensor = !state.solid;
+        let changed_walls: Vec<Entity> = (&ents, &bottom_walls, &colliders)
+            .join()
+            .filter(|(_, _, collider)| collider.sensor != is_sensor)
+            .map(|(ent, _, _)| ent)
+            .collect();
+
+        for ent in changed_walls {
+            colliders.get_mut(ent).unwrap().sensor = is_sensor;
+        }
+    }
+}
+
+// Ends the level once every breakable brick is gone. Indestructible bricks don't count.
+#[derive(Default)]
+pub struct WinConditionSystem;
+
+impl<'a> System<'a> for WinConditionSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, LevelState>,
+        Write<'a, GamePhase>,
+        Write<'a, EventChannel<LevelCompleteEvent>>,
+        ReadStorage<'a, BrickComponent>,
+    );
+
+    fn run(
+        &mut self,
+        (ents, level, mut phase, mut level_complete_events, bricks): Self::SystemData,
+    ) {
+        if *phase != GamePhase::Playing {
+            return;
+        }
+
+        if brick::count_breakable_bricks(&ents, &bricks) == 0 {
+            info!(target: "level", "Level {} complete!", level.level);
+            *phase = GamePhase::LevelComplete;
+            level_complete_events.single_write(LevelCompleteEvent { level: level.level });
+        }
+    }
+}
+
+// Moves on to the next level layout once a level is complete. If there isn't one, the game is won.
+#[derive(Default)]
+pub struct LevelProgressionSystem {
+    level_complete_event_reader: Option<ReaderId<LevelCompleteEvent>>,
+}
+
+impl<'a> System<'a> for LevelProgressionSystem {
+    type SystemData = (
+        Write<'a, LevelState>,
+        Write<'a, GamePhase>,
+        Read<'a, EventChannel<LevelCompleteEvent>>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.level_complete_event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<LevelCompleteEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(&mut self, (mut level, mut phase, level_complete_events): Self::SystemData) {
+        for event in
+            level_complete_events.read(&mut self.level_complete_event_reader.as_mut().unwrap())
+        {
+            let next_level = event.level + 1;
+            let next_layout_path = level_layout_path(next_level);
+            if !resolve_level_path(&next_layout_path).exists() {
+                info!(
+                    target: "level",
+                    "[LevelProgressionSystem] No layout for level {}. Game won!",
+                    next_level
+                );
+                *phase = GamePhase::Victory;
+                continue;
+            }
+
+            info!(target: "level", "[LevelProgressionSystem] Moving on to level {}", next_level);
+            level.level = next_level;
+            level.layout_path = Some(next_layout_path);
+            level.load_level_event = Some(LoadLevelEvent::NextLevel);
+        }
+    }
+}
 
 pub fn load_level(world: &mut World) {
-    println!("Loading level...");
+    debug!(target: "level", "Loading level...");
+
+    let event = world
+        .write_resource::<LevelState>()
+        .load_level_event
+        .take()
+        .unwrap_or(LoadLevelEvent::NewGame);
 
     world.delete_all();
 
-    let solid_collision_groups = ncollide2d::pipeline::CollisionGroups::new().with_membership(&[1]);
+    // Tear down the physics objects of the old level right away, so none of them are still around
+    // when the new level spawns
+    world
+        .write_resource::<PhysicsState>()
+        .remove_dead_entities(&world.entities());
 
-    let (level_width, level_height) = {
-        let level = world.read_resource::<LevelState>();
-        (level.level_width, level.level_height)
+    let (level_width, level_height, starting_balls) = {
+        let config = world.read_resource::<LevelConfig>();
+        (
+            config.field_width,
+            config.field_height,
+            config.starting_balls,
+        )
     };
 
-    // Spawn player paddle
-    let paddle_position = Vector2d::new(level_width as f64 / 2.0, level_height as f64 - 10.0);
-    let player_paddle_ent = world
-        .create_entity()
-        .with(TransformComponent::new(
-            paddle_position,
-            Point2f::new(30.0, 16.0),
-            Vector2f::new(paddle::PADDLE_SCALE_X, paddle::PADDLE_SCALE_Y),
-        ))
-        .with(ColliderComponent::new(
-            Cuboid::new(Vector2::new(
-                (paddle::PADDLE_HIT_BOX_WIDTH / 2.0) * WORLD_UNIT_RATIO,
-                (paddle::PADDLE_HIT_BOX_HEIGHT / 2.0) * WORLD_UNIT_RATIO,
-            )),
-            Vector2::zeros(),
-            solid_collision_groups,
-            1.0,
-        ))
-        .with(PlayerPaddleComponent::new(level_width))
-        .with(SpriteComponent {
-            color: COLOR_WHITE,
-            spritesheet_tex_id: 2,
-            region: SpriteRegion {
-                x: 0,
-                y: 0,
-                w: paddle::PADDLE_SPRITE_WIDTH,
-                h: paddle::PADDLE_SPRITE_HEIGHT,
-            },
-            layer: 1,
-            transparency: Transparency::Opaque,
-        })
-        .build();
+    let paddle_x = world.read_resource::<LevelConfig>().paddle_spawn_x(0);
+    let paddle_ents = spawn_arena(world, paddle_x);
+    let player_paddle_ent = paddle_ents[0];
 
     // Spawn bricks
-    for y in 0..LEVEL_BRICKS_HEIGHT {
-        for x in 0..LEVEL_BRICKS_WIDTH {
-            let position = Vector2d::new(
-                x as f64 * brick::BRICK_SPRITE_WIDTH as f64,
-                LEVEL_BRICKS_Y_OFFSET + (y as f64 * brick::BRICK_SPRITE_HEIGHT as f64),
+    let layout_path = world.read_resource::<LevelState>().layout_path.clone();
+    let layout = layout_path.and_then(|path| match LevelLayout::load(&path) {
+        Ok(layout) => Some(layout),
+        Err(e) => {
+            warn!(
+                target: "level",
+                "Failed to load level layout {}! Using the default layout instead. Error: {}",
+                path, e
             );
+            None
+        }
+    });
 
-            world
-                .create_entity()
-                .with(TransformComponent::new(
-                    position,
-                    Point2f::origin(),
-                    Vector2f::new(1.0, 1.0),
-                ))
-                .with(ColliderComponent::new(
-                    Cuboid::new(Vector2::new(0.5, 0.25)),
-                    Vector2::new(16.0, 8.0),
-                    solid_collision_groups,
-                    0.0,
-                ))
-                .with(BrickComponent::new(brick::BRICK_DEFAULT_HP))
-                .with(SpriteComponent {
-                    color: COLOR_WHITE,
-                    spritesheet_tex_id: 2,
-                    region: SpriteRegion {
-                        x: 96,
-                        y: 0,
-                        w: brick::BRICK_SPRITE_WIDTH,
-                        h: brick::BRICK_SPRITE_HEIGHT,
-                    },
-                    layer: 2,
-                    transparency: Transparency::Opaque,
-                })
-                .build();
+    if let Some(layout) = layout {
+        for desc in layout.bricks.iter() {
+            spawn_brick_from_desc(world, desc);
         }
+    } else {
+        generate_grid(
+            world,
+            LEVEL_BRICKS_HEIGHT,
+            LEVEL_BRICKS_WIDTH,
+            Vector2d::zeros(),
+            Vector2d::new(0.0, LEVEL_BRICKS_Y_OFFSET),
+            |_, _| brick::BRICK_DEFAULT_HP,
+        );
     }
 
     // Spawn initial ball
+    let ball_velocity = ball::random_ball_velocity(
+        &mut world.write_resource::<RngResource>(),
+        ball::BALL_DEFAULT_FORCE,
+    );
     world
         .write_resource::<EventChannel<SpawnBallEvent>>()
         .single_write(SpawnBallEvent {
             position: Vector2d::new(level_width as f64 / 2.0, level_height as f64 / 2.0),
-            linear_velocity: Vector2d::new(2.5, -2.5),
+            linear_velocity: ball_velocity,
             owning_paddle_ent: Some(player_paddle_ent),
+            radius: ball::BALL_DEFAULT_RADIUS,
+            owner: None,
         });
 
+    // Any other starting balls are launched straight away, at random upward angles. They belong to the first player,
+    // who also holds the first ball.
+    if starting_balls == 0 {
+        warn!(target: "level", "There must be at least 1 starting ball! Only the held ball will be spawned.");
+    }
+
+    let extra_balls = (starting_balls.max(1) as usize - 1).min(ball::MAX_ACTIVE_BALLS - 1);
+    for _ in 0..extra_balls {
+        let ball_velocity = ball::random_ball_velocity(
+            &mut world.write_resource::<RngResource>(),
+            ball::BALL_DEFAULT_FORCE,
+        );
+        world
+            .write_resource::<EventChannel<SpawnBallEvent>>()
+            .single_write(SpawnBallEvent {
+                position: Vector2d::new(level_width as f64 / 2.0, level_height as f64 / 2.0),
+                linear_velocity: ball_velocity,
+                owning_paddle_ent: None,
+                radius: ball::BALL_DEFAULT_RADIUS,
+                owner: Some(0),
+            });
+    }
+
+    // Every other player starts with a ball too
+    for &paddle_ent in paddle_ents.iter().skip(1) {
+        let ball_velocity = ball::random_ball_velocity(
+            &mut world.write_resource::<RngResource>(),
+            ball::BALL_DEFAULT_FORCE,
+        );
+        world
+            .write_resource::<EventChannel<SpawnBallEvent>>()
+            .single_write(SpawnBallEvent {
+                position: Vector2d::zeros(),
+                linear_velocity: ball_velocity,
+                owning_paddle_ent: Some(paddle_ent),
+                radius: ball::BALL_DEFAULT_RADIUS,
+                owner: None,
+            });
+    }
+
+    match event {
+        LoadLevelEvent::NewGame => {
+            world
+                .write_resource::<LevelState>()
+                .reset(player_paddle_ent);
+            world.write_resource::<Lives>().count = PLAYER_DEFAULT_BALLS;
+            world.write_resource::<Scores>().reset();
+        }
+        LoadLevelEvent::RestartLevel => {
+            world.write_resource::<LevelState>().player_paddle_ent = Some(player_paddle_ent);
+            world.write_resource::<Lives>().count = PLAYER_DEFAULT_BALLS;
+        }
+        LoadLevelEvent::NextLevel => {
+            world.write_resource::<LevelState>().player_paddle_ent = Some(player_paddle_ent);
+        }
+    }
+
+    *world.write_resource::<GamePhase>() = GamePhase::Playing;
+    *world.write_resource::<BottomWallState>() = BottomWallState::default();
+    world.write_resource::<DifficultyState>().reset();
+    world.write_resource::<Combo>().reset();
+
+    // Start the level music
+    if let Some(mut audio) = world.try_fetch_mut::<AudioState>() {
+        audio.play_music(audio::MUSIC_LEVEL_PATH, true);
+    }
+
+    world.maintain();
+}
+
+// Spawns the player paddles and the walls around the level. The first player's paddle is spawned at the
+// given x position, and the rest at their usual spots. Returns the paddle entities, in player order.
+pub fn spawn_arena(world: &mut World, paddle_x: f64) -> Vec<Entity> {
+    let config = world.read_resource::<LevelConfig>().clone();
+    let (level_width, level_height) = (config.field_width, config.field_height);
+
+    // Spawn player paddles
+    let mut paddle_ents = vec![spawn_paddle(world, &config, paddle_x, 0)];
+    for player_id in 1..config.players {
+        let x = config.paddle_spawn_x(player_id);
+        paddle_ents.push(spawn_paddle(world, &config, x, player_id));
+    }
+
     // Spawn Left wall
     world
         .create_entity()
@@ -154,12 +627,16 @@ pub fn load_level(world: &mut World) {
             position: Vector2d::new(-20.0, 0.0),
             ..Default::default()
         })
-        .with(ColliderComponent::new(
-            Cuboid::new(Vector2::new(20.0 * WORLD_UNIT_RATIO, 50.0)),
-            Vector2::zeros(),
-            solid_collision_groups,
-            1.0,
-        ))
+        .with(
+            ColliderComponent::new(
+                Cuboid::new(Vector2::new(20.0 * WORLD_UNIT_RATIO, 50.0)),
+                Vector2::zeros(),
+                collision::wall_groups(),
+                1.0,
+            )
+            .with_material(PhysicsMaterial::WALL),
+        )
+        .with(WallComponent)
         .build();
 
     // Spawn Top wall
@@ -169,12 +646,16 @@ pub fn load_level(world: &mut World) {
             position: Vector2d::new(0.0, -20.0),
             ..Default::default()
         })
-        .with(ColliderComponent::new(
-            Cuboid::new(Vector2::new(50.0, 20.0 * WORLD_UNIT_RATIO)),
-            Vector2::zeros(),
-            solid_collision_groups,
-            1.0,
-        ))
+        .with(
+            ColliderComponent::new(
+                Cuboid::new(Vector2::new(50.0, 20.0 * WORLD_UNIT_RATIO)),
+                Vector2::zeros(),
+                collision::wall_groups(),
+                1.0,
+            )
+            .with_material(PhysicsMaterial::WALL),
+        )
+        .with(WallComponent)
         .build();
 
     // Spawn Right wall
@@ -184,17 +665,704 @@ pub fn load_level(world: &mut World) {
             position: Vector2d::new(level_width as f64 + 20.0, 0.0),
             ..Default::default()
         })
-        .with(ColliderComponent::new(
-            Cuboid::new(Vector2::new(20.0 * WORLD_UNIT_RATIO, 50.0)),
-            Vector2::zeros(),
-            solid_collision_groups,
-            1.0,
-        ))
+        .with(
+            ColliderComponent::new(
+                Cuboid::new(Vector2::new(20.0 * WORLD_UNIT_RATIO, 50.0)),
+                Vector2::zeros(),
+                collision::wall_groups(),
+                1.0,
+            )
+            .with_material(PhysicsMaterial::WALL),
+        )
+        .with(WallComponent)
         .build();
 
+    // Spawn Bottom wall
     world
-        .write_resource::<LevelState>()
-        .reset(player_paddle_ent);
+        .create_entity()
+        .with(TransformComponent {
+            position: Vector2d::new(0.0, level_height as f64 + 20.0),
+            ..Default::default()
+        })
+        .with(
+            ColliderComponent::new(
+                Cuboid::new(Vector2::new(50.0, 20.0 * WORLD_UNIT_RATIO)),
+                Vector2::zeros(),
+                collision::bottom_wall_groups(),
+                1.0,
+            )
+            .with_material(PhysicsMaterial::WALL)
+            .as_sensor(),
+        )
+        .with(WallComponent)
+        .with(BottomWallComponent)
+        .build();
 
-    world.maintain();
+    paddle_ents
+}
+
+// With more than one player, each paddle gets its own controls. A single player uses the shared bindings.
+fn spawn_paddle(world: &mut World, config: &LevelConfig, x: f64, player_id: u32) -> Entity {
+    let mut player_paddle = PlayerPaddleComponent::new().with_width(config.paddle_width);
+    if config.players > 1 {
+        player_paddle = player_paddle.with_player(player_id, InputBindings::for_player(player_id));
+    }
+
+    let sprites_tex_id = world.read_resource::<Assets>().texture("sprites");
+    let paddle_position = Vector2d::new(x, config.field_height as f64 - 10.0);
+
+    // The paddle has no rigidbody, so its collider is attached to the static ground body, and balls can never push it.
+    // It only moves when its transform is moved (by the PlayerPaddleSystem), which moves the collider along with it.
+    world
+        .create_entity()
+        .with(TransformComponent::new(
+            paddle_position,
+            Point2f::new(30.0, 16.0),
+            config.paddle_scale,
+        ))
+        .with(
+            ColliderComponent::new(
+                Cuboid::new(Vector2::new(
+                    (config.paddle_width / 2.0) * WORLD_UNIT_RATIO,
+                    (config.paddle_height / 2.0) * WORLD_UNIT_RATIO,
+                )),
+                Vector2::zeros(),
+                collision::paddle_groups(),
+                1.0,
+            )
+            .with_material(PhysicsMaterial::PADDLE),
+        )
+        .with(player_paddle)
+        .with(SpriteComponent {
+            color: COLOR_WHITE,
+            spritesheet_tex_id: sprites_tex_id,
+            region: SpriteRegion {
+                x: 0,
+                y: 0,
+                w: paddle::PADDLE_SPRITE_WIDTH,
+                h: paddle::PADDLE_SPRITE_HEIGHT,
+            },
+            layer: 1,
+            transparency: Transparency::Opaque,
+            flip_x: false,
+            flip_y: false,
+        })
+        .build()
+}
+
+// Watches the current layout file, so edits to it show up in the running game
+#[derive(Default)]
+pub struct LevelHotReload {
+    pub enabled: bool,
+    ticks_until_check: u32,
+    watched_path: Option<String>,
+    last_modified: Option<SystemTime>,
+    last_contents: Option<Vec<u8>>,
+}
+
+impl LevelHotReload {
+    // Checks the layout file every so often. Returns true if it changed since the last check.
+    // Two writes close together can leave the file with the same modification time, so the contents
+    // are compared as well.
+    fn poll(&mut self, layout_path: Option<&str>) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        if self.ticks_until_check > 0 {
+            self.ticks_until_check -= 1;
+            return false;
+        }
+
+        self.ticks_until_check = LEVEL_HOT_RELOAD_INTERVAL_TICKS;
+
+        let path = match layout_path {
+            Some(path) => path,
+            None => {
+                self.watched_path = None;
+                return false;
+            }
+        };
+
+        let resolved_path = resolve_level_path(path);
+        let modified = std::fs::metadata(&resolved_path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let contents = std::fs::read(&resolved_path).ok();
+
+        // A newly loaded level is watched from however it is right now
+        if self.watched_path.as_deref() != Some(path) {
+            self.watched_path = Some(path.to_string());
+            self.last_modified = modified;
+            self.last_contents = contents;
+            return false;
+        }
+
+        if contents.is_some() && (modified != self.last_modified || contents != self.last_contents)
+        {
+            self.last_modified = modified;
+            self.last_contents = contents;
+            return true;
+        }
+
+        false
+    }
+}
+
+// Respawns the bricks from the current layout file if it changed on disk.
+// Everything else (the balls, paddles, score, and so on) is left as it is.
+pub fn hot_reload_level(world: &mut World) {
+    let layout_path = world.read_resource::<LevelState>().layout_path.clone();
+    let changed = world
+        .write_resource::<LevelHotReload>()
+        .poll(layout_path.as_deref());
+
+    let path = match layout_path {
+        Some(path) if changed => path,
+        _ => return,
+    };
+
+    let layout = match LevelLayout::load(&path) {
+        Ok(layout) => layout,
+        Err(e) => {
+            warn!(
+                target: "level",
+                "[LevelHotReload] Failed to reload level layout {}! Keeping the current bricks. Error: {}",
+                path, e
+            );
+            return;
+        }
+    };
+
+    // Delete the old bricks right away, along with their physics objects, so the new ones don't overlap them
+    let old_bricks: Vec<Entity> = {
+        let ents = world.entities();
+        let bricks = world.read_storage::<BrickComponent>();
+        (&ents, &bricks).join().map(|(ent, _)| ent).collect()
+    };
+
+    if let Err(e) = world.delete_entities(&old_bricks) {
+        warn!(
+            target: "level",
+            "[LevelHotReload] Failed to delete old bricks! Error: {:?}",
+            e
+        );
+    }
+
+    world
+        .write_resource::<PhysicsState>()
+        .remove_dead_entities(&world.entities());
+
+    for desc in layout.bricks.iter() {
+        spawn_brick_from_desc(world, desc);
+    }
+
+    info!(
+        target: "level",
+        "[LevelHotReload] Reloaded {} bricks from {}",
+        layout.bricks.len(),
+        path
+    );
+}
+
+pub fn spawn_brick_from_desc(world: &mut World, desc: &BrickDesc) -> Entity {
+    let (x, y, w, h) = desc.region;
+    let brick = if desc.indestructible {
+        BrickComponent::new_indestructible()
+    } else {
+        BrickComponent::new(desc.hp, desc.points)
+            .with_damage_regions(
+                desc.damage_regions
+                    .iter()
+                    .map(|&(x, y, w, h)| SpriteRegion { x, y, w, h })
+                    .collect(),
+            )
+            .with_explosive(desc.explosive)
+            .with_powerup(desc.contains_powerup)
+    };
+
+    spawn_brick(
+        world,
+        Vector2d::new(desc.position.0, desc.position.1),
+        brick,
+        SpriteRegion { x, y, w, h },
+    )
+}
+
+// Spawns a grid of bricks, with the top left brick at the origin. The spacing is the gap between neighbouring bricks.
+// The hp of each brick is decided by hp_fn(row, column), and cells with no hp are left empty.
+pub fn generate_grid<F>(
+    world: &mut World,
+    rows: u32,
+    columns: u32,
+    spacing: Vector2d,
+    origin: Vector2d,
+    mut hp_fn: F,
+) -> Vec<Entity>
+where
+    F: FnMut(u32, u32) -> i32,
+{
+    let (cell_width, cell_height) = {
+        let config = world.read_resource::<LevelConfig>();
+        (
+            config.brick_width + spacing.x,
+            config.brick_height + spacing.y,
+        )
+    };
+
+    let mut bricks = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let hp = hp_fn(row, column);
+            if hp <= 0 {
+                continue;
+            }
+
+            let position =
+                origin + Vector2d::new(column as f64 * cell_width, row as f64 * cell_height);
+
+            bricks.push(spawn_brick(
+                world,
+                position,
+                BrickComponent::new(hp, brick::BRICK_DEFAULT_POINTS),
+                SpriteRegion {
+                    x: 96,
+                    y: 0,
+                    w: brick::BRICK_SPRITE_WIDTH,
+                    h: brick::BRICK_SPRITE_HEIGHT,
+                },
+            ));
+        }
+    }
+
+    bricks
+}
+
+pub fn spawn_brick(
+    world: &mut World,
+    position: Vector2d,
+    brick: BrickComponent,
+    region: SpriteRegion,
+) -> Entity {
+    // The sprite is stretched to fit the configured brick size
+    let sprites_tex_id = world.read_resource::<Assets>().texture("sprites");
+    let (half_extents, offset, scale) = {
+        let config = world.read_resource::<LevelConfig>();
+        (
+            config.brick_half_extents(),
+            config.brick_center_offset(),
+            Vector2f::new(
+                (config.brick_width / brick::BRICK_SPRITE_WIDTH as f64) as f32,
+                (config.brick_height / brick::BRICK_SPRITE_HEIGHT as f64) as f32,
+            ),
+        )
+    };
+
+    world
+        .create_entity()
+        .with(TransformComponent::new(position, Point2f::origin(), scale))
+        .with(
+            ColliderComponent::new(
+                Cuboid::new(half_extents),
+                offset,
+                collision::brick_groups(),
+                0.0,
+            )
+            .with_material(PhysicsMaterial::BRICK),
+        )
+        .with(brick)
+        .with(SpriteComponent {
+            color: COLOR_WHITE,
+            spritesheet_tex_id: sprites_tex_id,
+            region,
+            layer: 2,
+            transparency: Transparency::Opaque,
+            flip_x: false,
+            flip_y: false,
+        })
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        camera::Camera,
+        testing::{self, DT},
+        GameState,
+    };
+
+    #[test]
+    fn losing_the_last_ball_costs_a_life() {
+        let (mut game, _) = testing::empty_game();
+
+        // Falling straight into the bottom wall, well away from the paddle
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(40.0, 225.0),
+            Vector2d::new(0.0, ball::BALL_DEFAULT_FORCE),
+        );
+        game.tick(DT);
+        let ball_ent = testing::balls(&game.world)[0];
+
+        game.run_headless(30, DT);
+
+        assert_eq!(
+            game.world.read_resource::<Lives>().count,
+            PLAYER_DEFAULT_BALLS - 1
+        );
+        assert!(!game.world.is_alive(ball_ent));
+    }
+
+    #[test]
+    fn level_is_spawned_from_its_layout_file() {
+        let path = testing::temp_path("fixture-level.ron");
+        std::fs::write(
+            &path,
+            r#"(
+    bricks: [
+        (position: (0.0, 22.0), hp: 1, region: (96, 0, 32, 16), points: 100),
+        (position: (32.0, 22.0), hp: 2, region: (96, 0, 32, 16), points: 100),
+        (position: (64.0, 22.0), hp: 3, region: (96, 0, 32, 16), points: 100),
+    ],
+)
+"#,
+        )
+        .unwrap();
+
+        let mut game = testing::headless_game(0);
+        game.load_level(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let bricks = game.world.read_storage::<BrickComponent>();
+        let transforms = game.world.read_storage::<TransformComponent>();
+        let mut spawned: Vec<(f64, i32)> = (&bricks, &transforms)
+            .join()
+            .map(|(brick, transform)| (transform.position.x, brick.hp))
+            .collect();
+        spawned.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        assert_eq!(spawned, vec![(0.0, 1), (32.0, 2), (64.0, 3)]);
+    }
+
+    #[test]
+    fn breaking_the_last_bricks_completes_the_level() {
+        let mut game = testing::headless_game(0);
+        let paddle_x = game.world.read_resource::<LevelConfig>().paddle_spawn_x(0);
+        let paddle_ent = spawn_arena(&mut game.world, paddle_x)[0];
+        game.world.write_resource::<LevelState>().reset(paddle_ent);
+        let bricks = vec![
+            testing::spawn_brick(
+                &mut game.world,
+                Vector2d::new(64.0, 40.0),
+                BrickComponent::new(1, 100),
+            ),
+            testing::spawn_brick(
+                &mut game.world,
+                Vector2d::new(128.0, 40.0),
+                BrickComponent::new(1, 100),
+            ),
+        ];
+        game.tick(DT);
+        assert_eq!(*game.world.read_resource::<GamePhase>(), GamePhase::Playing);
+
+        game.world.delete_entities(&bricks).unwrap();
+        game.tick(DT);
+
+        assert_eq!(
+            *game.world.read_resource::<GamePhase>(),
+            GamePhase::LevelComplete
+        );
+        assert_eq!(game.world.read_resource::<LevelState>().level, 2);
+    }
+
+    #[test]
+    fn losing_every_ball_costs_one_life_and_respawns_one_ball() {
+        let (mut game, paddle_ent) = testing::empty_game();
+
+        // Both fall into the bottom wall on either side of the paddle, a few ticks apart
+        for &(x, y) in &[(40.0, 225.0), (280.0, 210.0)] {
+            testing::spawn_ball(
+                &game.world,
+                Vector2d::new(x, y),
+                Vector2d::new(0.0, ball::BALL_DEFAULT_FORCE),
+            );
+        }
+        game.tick(DT);
+        assert_eq!(testing::balls(&game.world).len(), 2);
+
+        game.run_headless(40, DT);
+
+        assert_eq!(
+            game.world.read_resource::<Lives>().count,
+            PLAYER_DEFAULT_BALLS - 1
+        );
+        let balls = testing::balls(&game.world);
+        assert_eq!(balls.len(), 1);
+        assert_eq!(
+            game.world
+                .read_storage::<BallComponent>()
+                .get(balls[0])
+                .unwrap()
+                .holding_paddle_ent,
+            Some(paddle_ent)
+        );
+    }
+
+    #[test]
+    fn grid_places_bricks_with_spacing_and_hp_per_cell() {
+        let (mut game, _) = testing::empty_game();
+        let (brick_width, brick_height) = {
+            let config = game.world.read_resource::<LevelConfig>();
+            (config.brick_width, config.brick_height)
+        };
+
+        let grid = generate_grid(
+            &mut game.world,
+            3,
+            4,
+            Vector2d::new(2.0, 4.0),
+            Vector2d::new(10.0, 20.0),
+            |row, column| (row + column + 1) as i32,
+        );
+
+        assert_eq!(grid.len(), 12);
+        let bricks = game.world.read_storage::<BrickComponent>();
+        for row in 0..3 {
+            for column in 0..4 {
+                let ent = grid[(row * 4 + column) as usize];
+                let expected_position = Vector2d::new(
+                    10.0 + column as f64 * (brick_width + 2.0),
+                    20.0 + row as f64 * (brick_height + 4.0),
+                );
+                assert_eq!(testing::position(&game.world, ent), expected_position);
+                assert_eq!(bricks.get(ent).unwrap().hp, (row + column + 1) as i32);
+            }
+        }
+    }
+
+    fn collider_half_extents(world: &World, ent: Entity) -> Vector2<f64> {
+        *world
+            .read_storage::<ColliderComponent>()
+            .get(ent)
+            .unwrap()
+            .shape
+            .as_shape::<Cuboid<f64>>()
+            .unwrap()
+            .half_extents()
+    }
+
+    #[test]
+    fn larger_configured_bricks_get_larger_colliders() {
+        let (mut game, _) = testing::empty_game();
+        let brick = || BrickComponent::new(1, brick::BRICK_DEFAULT_POINTS);
+        let region = SpriteRegion {
+            x: 96,
+            y: 0,
+            w: brick::BRICK_SPRITE_WIDTH,
+            h: brick::BRICK_SPRITE_HEIGHT,
+        };
+        let default_ent = spawn_brick(&mut game.world, Vector2d::new(0.0, 40.0), brick(), region);
+
+        {
+            let mut config = game.world.write_resource::<LevelConfig>();
+            config.brick_width *= 2.0;
+            config.brick_height *= 1.5;
+        }
+        let large_ent = spawn_brick(&mut game.world, Vector2d::new(0.0, 80.0), brick(), region);
+
+        let default_half_extents = collider_half_extents(&game.world, default_ent);
+        let large_half_extents = collider_half_extents(&game.world, large_ent);
+        assert!((large_half_extents.x - default_half_extents.x * 2.0).abs() < 1e-9);
+        assert!((large_half_extents.y - default_half_extents.y * 1.5).abs() < 1e-9);
+    }
+
+    fn sorted_positions(mut positions: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+        positions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        positions
+    }
+
+    #[test]
+    fn completing_level_one_loads_level_two() {
+        let mut game = testing::headless_game(0);
+        game.tick(DT);
+        assert_eq!(game.world.read_resource::<LevelState>().level, 1);
+
+        let level_one_bricks: Vec<Entity> = {
+            let ents = game.world.entities();
+            let bricks = game.world.read_storage::<BrickComponent>();
+            (&ents, &bricks).join().map(|(ent, _)| ent).collect()
+        };
+        game.world.delete_entities(&level_one_bricks).unwrap();
+        game.tick(DT);
+        game.tick(DT);
+
+        assert_eq!(game.world.read_resource::<LevelState>().level, 2);
+        assert_eq!(*game.world.read_resource::<GamePhase>(), GamePhase::Playing);
+
+        let layout = LevelLayout::load(&level_layout_path(2)).unwrap();
+        let expected = sorted_positions(layout.bricks.iter().map(|desc| desc.position).collect());
+        let bricks = game.world.read_storage::<BrickComponent>();
+        let transforms = game.world.read_storage::<TransformComponent>();
+        let spawned = sorted_positions(
+            (&bricks, &transforms)
+                .join()
+                .map(|(_, transform)| (transform.position.x, transform.position.y))
+                .collect(),
+        );
+        assert_eq!(spawned, expected);
+    }
+
+    // Drops a ball into the bottom wall, away from the paddle.
+    // Returns the ball's velocity if it survived, and the lives left.
+    fn drop_ball_into_bottom_wall(solid: bool) -> (Option<Vector2d>, u32) {
+        let (mut game, _) = testing::empty_game();
+        game.world.write_resource::<BottomWallState>().solid = solid;
+        game.tick(DT);
+
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(40.0, 215.0),
+            Vector2d::new(0.0, ball::BALL_DEFAULT_FORCE),
+        );
+        game.tick(DT);
+        let ball_ent = testing::balls(&game.world)[0];
+        game.run_headless(30, DT);
+
+        let velocity = if game.world.is_alive(ball_ent) {
+            Some(testing::ball_velocity(&game.world, ball_ent))
+        } else {
+            None
+        };
+
+        (velocity, game.world.read_resource::<Lives>().count)
+    }
+
+    #[test]
+    fn balls_bounce_off_a_solid_bottom_and_fall_through_a_sensor() {
+        let (velocity, lives) = drop_ball_into_bottom_wall(true);
+        assert!(velocity.unwrap().y < 0.0);
+        assert_eq!(lives, PLAYER_DEFAULT_BALLS);
+
+        let (velocity, lives) = drop_ball_into_bottom_wall(false);
+        assert!(velocity.is_none());
+        assert_eq!(lives, PLAYER_DEFAULT_BALLS - 1);
+    }
+
+    fn brick_positions(world: &World) -> Vec<(f64, f64)> {
+        let bricks = world.read_storage::<BrickComponent>();
+        let transforms = world.read_storage::<TransformComponent>();
+        sorted_positions(
+            (&bricks, &transforms)
+                .join()
+                .map(|(_, transform)| (transform.position.x, transform.position.y))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn editing_the_layout_file_replaces_only_the_bricks() {
+        let path = testing::temp_path("hot-reload-level.ron");
+        std::fs::write(
+            &path,
+            r#"(
+    bricks: [
+        (position: (0.0, 22.0), hp: 1, region: (96, 0, 32, 16), points: 100),
+        (position: (32.0, 22.0), hp: 1, region: (96, 0, 32, 16), points: 100),
+        (position: (64.0, 22.0), hp: 1, region: (96, 0, 32, 16), points: 100),
+    ],
+)
+"#,
+        )
+        .unwrap();
+
+        let mut game = testing::headless_game(0);
+        game.load_level(&path);
+        game.world.write_resource::<LevelHotReload>().enabled = true;
+        game.run_headless(3, DT);
+        let paddle_ent = game
+            .world
+            .read_resource::<LevelState>()
+            .player_paddle_ent
+            .unwrap();
+        let balls = testing::balls(&game.world);
+        let colliders = game.world.read_resource::<PhysicsState>().collider_count();
+
+        std::fs::write(
+            &path,
+            r#"(
+    bricks: [
+        (position: (100.0, 50.0), hp: 2, region: (96, 0, 32, 16), points: 100),
+        (position: (200.0, 50.0), hp: 2, region: (96, 0, 32, 16), points: 100),
+    ],
+)
+"#,
+        )
+        .unwrap();
+        game.world
+            .write_resource::<LevelHotReload>()
+            .ticks_until_check = 0;
+        hot_reload_level(&mut game.world);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            brick_positions(&game.world),
+            vec![(100.0, 50.0), (200.0, 50.0)]
+        );
+        assert!(game.world.is_alive(paddle_ent));
+        assert_eq!(testing::balls(&game.world), balls);
+
+        // The old bricks' colliders are gone, and the new ones are added on the next tick
+        game.tick(DT);
+        assert_eq!(
+            game.world.read_resource::<PhysicsState>().collider_count(),
+            colliders - 1
+        );
+    }
+
+    fn balls_at_start(starting_balls: u32) -> usize {
+        let mut game = testing::headless_game(0);
+        game.world.write_resource::<LevelConfig>().starting_balls = starting_balls;
+        game.tick(DT);
+
+        testing::balls(&game.world).len()
+    }
+
+    #[test]
+    fn level_starts_with_the_configured_number_of_balls() {
+        assert_eq!(balls_at_start(1), 1);
+        assert_eq!(balls_at_start(3), 3);
+
+        // There is always at least the held ball
+        assert_eq!(balls_at_start(0), 1);
+    }
+
+    #[test]
+    fn walls_sit_on_the_edges_of_a_custom_play_field() {
+        let mut game = GameState::with_play_field(
+            testing::FIELD_WIDTH,
+            testing::FIELD_HEIGHT,
+            PlayField::new(40.0, 20.0, 200, 160),
+            0,
+        );
+        game.world.write_resource::<PhysicsState>().set_timestep(DT);
+        game.tick(DT);
+
+        let walls = game.world.read_storage::<WallComponent>();
+        let transforms = game.world.read_storage::<TransformComponent>();
+        let positions = sorted_positions(
+            (&walls, &transforms)
+                .join()
+                .map(|(_, transform)| (transform.position.x, transform.position.y))
+                .collect(),
+        );
+        // Left, top, bottom and right, just outside the 200x160 field
+        assert_eq!(
+            positions,
+            vec![(-20.0, 0.0), (0.0, -20.0), (0.0, 180.0), (220.0, 0.0)]
+        );
+        assert_eq!(
+            game.world.read_resource::<Camera>().position,
+            Vector2d::new(-40.0, -20.0)
+        );
+    }
 }
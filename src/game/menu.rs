@@ -0,0 +1,68 @@
+use crate::game::{
+    bindings::{Action, InputBindings},
+    level::{self, GamePhase, LevelState, LoadLevelEvent},
+};
+use gfx::input::InputState;
+use specs::prelude::*;
+
+// Handles the input that moves the game between phases: starting from the main menu, pausing,
+// and starting over once the game is over. This runs every tick, even while the simulation is stopped.
+// Level loads are only requested here, and happen at the start of the next tick.
+pub struct MenuSystem;
+
+impl<'a> System<'a> for MenuSystem {
+    type SystemData = (
+        Read<'a, InputState>,
+        Read<'a, InputBindings>,
+        Write<'a, GamePhase>,
+        Write<'a, LevelState>,
+    );
+
+    fn run(&mut self, (input, bindings, mut phase, mut level): Self::SystemData) {
+        // A level is already on its way
+        if level.load_level_event.is_some() {
+            return;
+        }
+
+        let start_pressed = bindings.was_pressed(&input, Action::Launch)
+            || bindings.was_pressed(&input, Action::Restart);
+
+        match *phase {
+            GamePhase::MainMenu => {
+                if start_pressed {
+                    start_new_game(&mut level);
+                }
+            }
+            GamePhase::Playing => {
+                if bindings.was_pressed(&input, Action::Pause) {
+                    *phase = GamePhase::Paused;
+                }
+            }
+            GamePhase::Paused => {
+                if bindings.was_pressed(&input, Action::Pause) {
+                    *phase = GamePhase::Playing;
+                }
+            }
+            GamePhase::GameOver => {
+                if bindings.was_pressed(&input, Action::Restart) {
+                    start_new_game(&mut level);
+                } else if bindings.was_pressed(&input, Action::RestartLevel) {
+                    level.load_level_event = Some(LoadLevelEvent::RestartLevel);
+                }
+            }
+            GamePhase::Victory => {
+                if bindings.was_pressed(&input, Action::Restart) {
+                    start_new_game(&mut level);
+                }
+            }
+            GamePhase::LevelComplete => {}
+        }
+    }
+}
+
+// Starts over from the first level. Loading the level puts the game into the Playing phase.
+fn start_new_game(level: &mut LevelState) {
+    level.level = 1;
+    level.layout_path = Some(level::level_layout_path(1));
+    level.load_level_event = Some(LoadLevelEvent::NewGame);
+}
@@ -1,24 +1,67 @@
+pub mod assets;
 pub mod audio;
 pub mod ball;
+pub mod bindings;
 pub mod brick;
+pub mod camera;
+pub mod collision;
+pub mod debug;
+pub mod difficulty;
+pub mod effect;
+pub mod laser;
 pub mod level;
+pub mod menu;
 pub mod paddle;
 pub mod physics;
+pub mod popup;
+pub mod powerup;
 pub mod render;
+pub mod replay;
+pub mod rng;
+pub mod save;
+#[cfg(test)]
+pub mod testing;
+pub mod trail;
 pub mod transform;
+pub mod tween;
 
-use audio::AudioAssetDb;
-use ball::{BallSystem, SpawnBallSystem};
-use brick::BrickSystem;
-use level::{LevelState, LoadLevelEvent};
-use paddle::PlayerPaddleSystem;
+use assets::Assets;
+use audio::{AudioAssetDb, AudioState, AudioSystem};
+use ball::{BallSpinSettings, BallSpinSystem, BallSystem, SpawnBallSystem};
+use bindings::InputBindings;
+use brick::{BrickSystem, Combo, Scores, COMBO_DEFAULT_MAX_MULTIPLIER};
+use camera::{Camera, ScreenShake, ScreenShakeSystem};
+use collision::CollisionSettings;
+use debug::{DebugDraw, DebugDrawSystem};
+use difficulty::{DifficultyState, DifficultySystem};
+use effect::TimedEffectSystem;
+use gfx::{font::Font, input::InputState, window::DeltaTime};
+use laser::{LaserPaddleComponent, LaserSystem};
+use level::{
+    BottomWallState, BottomWallSystem, GamePhase, LevelConfig, LevelHotReload,
+    LevelProgressionSystem, LevelState, Lives, LivesSystem, LoadLevelEvent, PlayField,
+    WinConditionSystem, PLAYER_DEFAULT_BALLS,
+};
+use log::info;
+use menu::MenuSystem;
+use paddle::{
+    MagnetPaddleComponent, MagnetSystem, PaddleSizeEffectComponent, PaddleSizeSystem,
+    PlayerPaddleComponent, PlayerPaddleSystem,
+};
 use physics::{
-    ColliderSendPhysicsSystem, PhysicsState, RigidbodyReceivePhysicsSystem,
+    ColliderSendPhysicsSystem, PhysicsCleanupSystem, PhysicsState, RigidbodyReceivePhysicsSystem,
     RigidbodySendPhysicsSystem, WorldStepPhysicsSystem,
 };
-use render::{RenderState, SpriteRenderSystem};
+use popup::{ScorePopupRenderSystem, ScorePopupSystem};
+use powerup::{PowerUpState, PowerUpSystem};
+use render::{AimIndicatorRenderSystem, HudRenderSystem, RenderState, SpriteRenderSystem};
+use replay::{Replay, ReplayPlayer, ReplayRecorder};
+use rng::RngResource;
 use specs::prelude::*;
+use std::error::Error;
+use trail::{BallTrailRenderSystem, BallTrailSystem};
 use transform::TransformComponent;
+use tween::ColorTweenSystem;
 
 pub type Vector2f = nalgebra::Vector2<f32>;
 pub type Vector2d = nalgebra::Vector2<f64>;
@@ -27,28 +70,142 @@ pub type Point2d = nalgebra::Point2<f64>;
 
 pub const PIXELS_PER_WORLD_UNIT: u32 = 32;
 pub const WORLD_UNIT_RATIO: f64 = (1.0 / PIXELS_PER_WORLD_UNIT as f64);
+pub const SAVE_GAME_PATH: &str = "save.ron";
+pub const REPLAY_PATH: &str = "replay.ron";
+pub const MIN_TIME_SCALE: f64 = 0.01;
+pub const MAX_TIME_SCALE: f64 = 10.0;
+
+// Scales how fast the simulation runs, so 0.5 is half speed. This can't be zero or negative, use GamePhase::Paused
+// to stop.
+pub struct TimeScale(f64);
+
+impl TimeScale {
+    pub fn new(scale: f64) -> Self {
+        let mut time_scale = TimeScale(1.0);
+        time_scale.set(scale);
+
+        time_scale
+    }
+
+    pub fn get(&self) -> f64 {
+        self.0
+    }
+
+    // Out of range values (and NaN) are clamped into range
+    pub fn set(&mut self, scale: f64) {
+        self.0 = scale.max(MIN_TIME_SCALE).min(MAX_TIME_SCALE);
+    }
+}
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        TimeScale(1.0)
+    }
+}
 
 pub struct GameState<'a, 'b> {
     pub world: World,
     pub tick_dispatcher: Dispatcher<'a, 'b>,
+    // Runs every tick, even while the tick dispatcher is stopped
+    pub menu_dispatcher: Dispatcher<'a, 'b>,
     pub physics_dispatcher: Dispatcher<'a, 'b>,
+    pub render_dispatcher: Dispatcher<'a, 'b>,
 }
 
 impl<'a, 'b> GameState<'a, 'b> {
     pub fn new(width: u32, height: u32) -> GameState<'a, 'b> {
+        use rand::Rng;
+        GameState::new_seeded(width, height, rand::thread_rng().gen())
+    }
+
+    // A game with a paddle for each player. Players share the score and lives.
+    pub fn new_multiplayer(width: u32, height: u32, players: u32) -> GameState<'a, 'b> {
+        use rand::Rng;
+        GameState::new_multiplayer_seeded(width, height, players, rand::thread_rng().gen())
+    }
+
+    pub fn new_multiplayer_seeded(
+        width: u32,
+        height: u32,
+        players: u32,
+        seed: u64,
+    ) -> GameState<'a, 'b> {
+        let state = GameState::new_seeded(width, height, seed);
+        state.world.write_resource::<LevelConfig>().players = players.max(1);
+
+        state
+    }
+
+    // The play field covers the whole view
+    pub fn new_seeded(width: u32, height: u32, seed: u64) -> GameState<'a, 'b> {
+        GameState::with_play_field(width, height, PlayField::fill(width, height), seed)
+    }
+
+    // A view of the given size, with the level played out inside the given play field.
+    // The walls sit on the edges of the play field, and anything around it is left for the HUD.
+    pub fn with_play_field(
+        width: u32,
+        height: u32,
+        play_field: PlayField,
+        seed: u64,
+    ) -> GameState<'a, 'b> {
         let mut world = World::new();
 
         let mut tick_dispatcher = DispatcherBuilder::new()
             .with(PlayerPaddleSystem, "player_paddle", &[])
-            .with(BallSystem::default(), "ball", &[])
-            .with(BrickSystem::default(), "brick", &[])
+            .with(
+                TimedEffectSystem::<PaddleSizeEffectComponent>::default(),
+                "paddle_size_timer",
+                &[],
+            )
+            .with(
+                TimedEffectSystem::<LaserPaddleComponent>::default(),
+                "laser_timer",
+                &[],
+            )
+            .with(
+                PaddleSizeSystem,
+                "paddle_size",
+                &["player_paddle", "paddle_size_timer"],
+            )
+            .with(
+                LaserSystem::default(),
+                "laser",
+                &["player_paddle", "laser_timer"],
+            )
+            .with(
+                TimedEffectSystem::<MagnetPaddleComponent>::default(),
+                "magnet_timer",
+                &[],
+            )
+            .with(MagnetSystem, "magnet", &["player_paddle", "magnet_timer"])
+            .with(BallSpinSystem, "ball_spin", &[])
+            .with(BallSystem::default(), "ball", &["ball_spin", "magnet"])
+            .with(DifficultySystem, "difficulty", &["ball"])
+            .with(LivesSystem::default(), "lives", &["ball"])
+            .with(BrickSystem::default(), "brick", &["ball"])
+            .with(WinConditionSystem, "win_condition", &["brick"])
+            .with(AudioSystem::default(), "audio", &["brick"])
+            .with(PowerUpSystem::default(), "powerup", &["brick"])
+            .with(BottomWallSystem, "bottom_wall", &["powerup"])
+            .with(ScreenShakeSystem::default(), "screen_shake", &["brick"])
+            .with(ColorTweenSystem, "color_tween", &["brick"])
+            .with(ScorePopupSystem, "score_popup", &[])
+            .with(BallTrailSystem, "ball_trail", &[])
             .with_thread_local(SpawnBallSystem::default())
-            .with_thread_local(SpriteRenderSystem::default())
             .build();
 
         tick_dispatcher.setup(&mut world);
 
+        let mut menu_dispatcher = DispatcherBuilder::new()
+            .with(LevelProgressionSystem::default(), "level_progression", &[])
+            .with(MenuSystem, "menu", &["level_progression"])
+            .build();
+
+        menu_dispatcher.setup(&mut world);
+
         let mut physics_dispatcher = DispatcherBuilder::new()
+            .with_thread_local(PhysicsCleanupSystem)
             .with_thread_local(RigidbodySendPhysicsSystem::default())
             .with_thread_local(ColliderSendPhysicsSystem::default())
             .with_thread_local(WorldStepPhysicsSystem)
@@ -57,16 +214,384 @@ impl<'a, 'b> GameState<'a, 'b> {
 
         physics_dispatcher.setup(&mut world);
 
+        let mut render_dispatcher = DispatcherBuilder::new()
+            .with_thread_local(BallTrailRenderSystem::default())
+            .with_thread_local(SpriteRenderSystem::default())
+            .with_thread_local(AimIndicatorRenderSystem::default())
+            .with_thread_local(ScorePopupRenderSystem::default())
+            .with_thread_local(DebugDrawSystem)
+            .with_thread_local(HudRenderSystem::default())
+            .build();
+
+        render_dispatcher.setup(&mut world);
+
+        // Textures are only registered here. They get uploaded once the renderer is ready.
+        let mut assets = Assets::new();
+        assets.load_texture("costanza", "res/textures/costanza.png");
+        assets.load_texture("sprites", "res/textures/sprites.png");
+        assets.load_texture("font", "res/textures/font.png");
+        assets.load_texture("background", "res/textures/bg.png");
+
         // Resources
+        world.insert(InputState::new());
         world.insert(RenderState::new());
-        world.insert(LevelState::new(width, height, LoadLevelEvent));
+        world.insert(Font::new(assets.texture("font"), 8, 16, 16));
+        world.insert(assets);
+        let mut camera = Camera::new(width, height);
+        camera.position = play_field.camera_position();
+        world.insert(camera);
+        world.insert(ScreenShake::new());
+        world.insert(LevelConfig::new(play_field.width, play_field.height));
+        world.insert(play_field);
+        world.insert(LevelState::new(LoadLevelEvent::NewGame));
+        world.insert(Lives::new(PLAYER_DEFAULT_BALLS));
+        world.insert(GamePhase::Playing);
+        world.insert(InputBindings::default());
+        world.insert(BottomWallState::default());
+        world.insert(LevelHotReload::default());
+        world.insert(TimeScale::default());
+        world.insert(CollisionSettings::default());
+        world.insert(BallSpinSettings::default());
+        world.insert(DebugDraw::default());
+        world.insert(RngResource::new(seed));
+        world.insert(ReplayRecorder::default());
+        world.insert(ReplayPlayer::default());
+        world.insert(PowerUpState::new());
+        world.insert(DifficultyState::new());
+        world.insert(Combo::new(COMBO_DEFAULT_MAX_MULTIPLIER));
+        world.insert(Scores::default());
         world.insert(PhysicsState::new());
         world.insert(AudioAssetDb::new());
+        world.insert(AudioState::new());
 
         GameState {
             world,
             tick_dispatcher,
+            menu_dispatcher,
             physics_dispatcher,
+            render_dispatcher,
+        }
+    }
+
+    // Loads the given layout file (relative to the levels directory) and spawns the level from it right away.
+    pub fn load_level(&mut self, path: &str) {
+        {
+            let mut level = self.world.write_resource::<LevelState>();
+            level.layout_path = Some(path.to_string());
+            level.load_level_event = Some(LoadLevelEvent::NewGame);
+        }
+
+        level::load_level(&mut self.world);
+    }
+
+    // Plays the current level again from the start. The score is kept, but the lives are reset.
+    pub fn restart_level(&mut self) {
+        self.world.write_resource::<LevelState>().load_level_event =
+            Some(LoadLevelEvent::RestartLevel);
+
+        level::load_level(&mut self.world);
+    }
+
+    // Starts a new game from the first level
+    pub fn restart_game(&mut self) {
+        {
+            let mut level = self.world.write_resource::<LevelState>();
+            level.level = 1;
+            level.layout_path = Some(level::level_layout_path(1));
+            level.load_level_event = Some(LoadLevelEvent::NewGame);
+        }
+
+        level::load_level(&mut self.world);
+    }
+
+    // Starts a new game from the current seed, and records the input of every tick from here on
+    pub fn start_recording(&mut self) {
+        let seed = self.world.read_resource::<RngResource>().seed;
+        self.world.insert(RngResource::new(seed));
+        self.restart_game();
+
+        self.world.write_resource::<ReplayRecorder>().start(seed);
+        info!(target: "replay", "Recording replay with seed {}", seed);
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.world.read_resource::<ReplayRecorder>().is_recording()
+    }
+
+    // Stops recording, and returns the recorded replay (if there was one)
+    pub fn stop_recording(&mut self) -> Option<Replay> {
+        self.world.write_resource::<ReplayRecorder>().stop()
+    }
+
+    // Starts a new game from the replay's seed, and plays back its input instead of the live input
+    pub fn play_replay(&mut self, replay: Replay) {
+        self.stop_recording();
+        self.world.insert(RngResource::new(replay.seed));
+        self.restart_game();
+
+        self.world.write_resource::<ReplayPlayer>().play(replay);
+    }
+
+    // Runs a single simulation tick. None of this needs a window, so it can also be used to simulate headlessly.
+    pub fn tick(&mut self, dt: DeltaTime) {
+        self.world.insert::<DeltaTime>(dt);
+
+        // Recorded input replaces the live input while a replay is playing
+        let replay_input = self.world.write_resource::<ReplayPlayer>().next_input();
+        if let Some(input) = replay_input {
+            self.world.insert::<InputState>(input);
+        }
+
+        {
+            let input = self.world.read_resource::<InputState>();
+            self.world.write_resource::<ReplayRecorder>().record(&input);
+        }
+
+        // Handle any level loads
+        let load_level_pending = self
+            .world
+            .read_resource::<LevelState>()
+            .load_level_event
+            .is_some();
+        if load_level_pending {
+            level::load_level(&mut self.world);
+        }
+
+        level::hot_reload_level(&mut self.world);
+
+        // Gameplay only runs while playing. The menu still handles input in every other phase.
+        let is_simulating = self.world.read_resource::<GamePhase>().is_simulating();
+        if is_simulating {
+            self.tick_dispatcher.dispatch(&mut self.world);
+            self.physics_dispatcher.dispatch(&mut self.world);
+        }
+
+        self.menu_dispatcher.dispatch(&mut self.world);
+
+        self.world.maintain();
+    }
+
+    // Whether the cursor should be grabbed, for mouse control of any paddle
+    pub fn wants_cursor_grab(&self, focused: bool) -> bool {
+        let paused = !self.world.read_resource::<GamePhase>().is_simulating();
+        let paddles = self.world.read_storage::<PlayerPaddleComponent>();
+        paddles
+            .join()
+            .any(|paddle| paddle::wants_cursor_grab(paddle.control_mode, paused, focused))
+    }
+
+    // Leaves the current level in the background, until the player starts a game from the menu
+    pub fn show_main_menu(&mut self) {
+        *self.world.write_resource::<GamePhase>() = GamePhase::MainMenu;
+    }
+
+    // Runs the given number of ticks with no input and no rendering
+    pub fn run_headless(&mut self, ticks: u32, dt: DeltaTime) {
+        for _ in 0..ticks {
+            self.tick(dt);
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        save::save_game(&self.world, path)
+    }
+
+    // Replaces the current game with the one saved at the given path
+    pub fn load(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        save::load_game(&mut self.world, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::DT;
+    use super::*;
+    use gfx::input::VirtualKeyCode;
+
+    fn positions(world: &World) -> Vec<(Entity, Vector2d)> {
+        let ents = world.entities();
+        let transforms = world.read_storage::<TransformComponent>();
+        (&ents, &transforms)
+            .join()
+            .map(|(ent, transform)| (ent, transform.position))
+            .collect()
+    }
+
+    #[test]
+    fn nothing_moves_while_paused() {
+        let mut game = testing::headless_game(0);
+        game.tick(DT);
+
+        // Launch the ball, and let everything get moving
+        game.world
+            .insert(testing::pressed(&[VirtualKeyCode::Space]));
+        game.tick(DT);
+        game.world.insert(InputState::new());
+        game.run_headless(10, DT);
+
+        *game.world.write_resource::<GamePhase>() = GamePhase::Paused;
+        let before = positions(&game.world);
+        game.run_headless(10, DT);
+
+        assert_eq!(positions(&game.world), before);
+    }
+
+    #[test]
+    fn long_headless_game_keeps_balls_in_the_field() {
+        let mut game = testing::headless_game(0);
+        game.tick(DT);
+        game.world
+            .insert(testing::pressed(&[VirtualKeyCode::Space]));
+        game.tick(DT);
+        game.world.insert(InputState::new());
+
+        // Balls can sink into the bottom wall for a tick before they are lost
+        let bottom = testing::FIELD_HEIGHT as f64 + 40.0;
+        for _ in 0..600 {
+            game.tick(DT);
+            for ball_ent in testing::balls(&game.world) {
+                let position = testing::position(&game.world, ball_ent);
+                assert!(position.x >= 0.0 && position.x <= testing::FIELD_WIDTH as f64);
+                assert!(position.y >= 0.0 && position.y <= bottom);
+            }
         }
     }
+
+    // Position and hp of every brick, from top left to bottom right
+    fn bricks(world: &World) -> Vec<(f64, f64, i32)> {
+        let bricks = world.read_storage::<brick::BrickComponent>();
+        let transforms = world.read_storage::<TransformComponent>();
+        let mut states: Vec<(f64, f64, i32)> = (&bricks, &transforms)
+            .join()
+            .map(|(brick, transform)| (transform.position.y, transform.position.x, brick.hp))
+            .collect();
+        states.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        states
+    }
+
+    #[test]
+    fn restarting_the_game_resets_the_score_and_bricks() {
+        let mut game = testing::headless_game(0);
+        game.restart_game();
+        game.tick(DT);
+        let initial_bricks = bricks(&game.world);
+        let initial_colliders = game.world.read_resource::<PhysicsState>().collider_count();
+        assert!(!initial_bricks.is_empty());
+
+        // Break a few bricks, and score some points
+        let broken: Vec<Entity> = {
+            let ents = game.world.entities();
+            let bricks = game.world.read_storage::<brick::BrickComponent>();
+            (&ents, &bricks)
+                .join()
+                .map(|(ent, _)| ent)
+                .take(3)
+                .collect()
+        };
+        game.world.delete_entities(&broken).unwrap();
+        game.world.write_resource::<LevelState>().score = 500;
+        game.run_headless(10, DT);
+
+        game.restart_game();
+        game.tick(DT);
+
+        assert_eq!(game.world.read_resource::<LevelState>().score, 0);
+        assert_eq!(bricks(&game.world), initial_bricks);
+        assert_eq!(
+            game.world.read_resource::<PhysicsState>().collider_count(),
+            initial_colliders
+        );
+    }
+
+    // Where every ball is, and the score so far
+    fn replay_outcome(game: &GameState) -> (Vec<Vector2d>, u32) {
+        let positions = testing::balls(&game.world)
+            .into_iter()
+            .map(|ball_ent| testing::position(&game.world, ball_ent))
+            .collect();
+
+        (positions, game.world.read_resource::<LevelState>().score)
+    }
+
+    #[test]
+    fn replay_plays_out_the_same_as_the_recording() {
+        let mut game = testing::headless_game(7);
+        game.start_recording();
+        for tick in 0..100 {
+            let keys: &[VirtualKeyCode] = match tick {
+                5 => &[VirtualKeyCode::Space],
+                10..=30 => &[VirtualKeyCode::D],
+                40..=60 => &[VirtualKeyCode::A],
+                _ => &[],
+            };
+            game.world.insert(testing::pressed(keys));
+            game.tick(DT);
+        }
+        let replay = game.stop_recording().unwrap();
+        assert_eq!(replay.inputs.len(), 100);
+        let recorded = replay_outcome(&game);
+        assert!(!recorded.0.is_empty());
+
+        // The replay brings its own seed
+        let mut replayed_game = testing::headless_game(99);
+        replayed_game.play_replay(replay);
+        replayed_game.run_headless(100, DT);
+
+        assert_eq!(replay_outcome(&replayed_game), recorded);
+    }
+
+    #[test]
+    fn main_menu_leaves_the_world_alone_until_playing() {
+        let (mut game, _) = testing::empty_game();
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(160.0, 120.0),
+            Vector2d::new(0.0, -ball::BALL_DEFAULT_FORCE),
+        );
+        game.run_headless(2, DT);
+        let ball_ent = testing::balls(&game.world)[0];
+
+        game.show_main_menu();
+        let before = positions(&game.world);
+        let score = game.world.read_resource::<LevelState>().score;
+        game.run_headless(10, DT);
+
+        assert_eq!(positions(&game.world), before);
+        assert_eq!(game.world.read_resource::<LevelState>().score, score);
+
+        *game.world.write_resource::<GamePhase>() = GamePhase::Playing;
+        let position = testing::position(&game.world, ball_ent);
+        game.run_headless(2, DT);
+
+        assert!(testing::position(&game.world, ball_ent).y < position.y);
+    }
+
+    #[test]
+    fn game_over_freezes_the_world() {
+        let (mut game, _) = testing::empty_game();
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(160.0, 120.0),
+            Vector2d::new(0.0, -ball::BALL_DEFAULT_FORCE),
+        );
+        game.run_headless(2, DT);
+
+        *game.world.write_resource::<GamePhase>() = GamePhase::GameOver;
+        let before = positions(&game.world);
+        let score = game.world.read_resource::<LevelState>().score;
+        let elapsed = game.world.read_resource::<DifficultyState>().elapsed;
+        game.run_headless(10, DT);
+
+        assert_eq!(positions(&game.world), before);
+        assert_eq!(game.world.read_resource::<LevelState>().score, score);
+        assert_eq!(
+            game.world.read_resource::<DifficultyState>().elapsed,
+            elapsed
+        );
+        assert_eq!(
+            *game.world.read_resource::<GamePhase>(),
+            GamePhase::GameOver
+        );
+    }
 }
@@ -1,10 +1,16 @@
 use crate::game::{
     ball::BallComponent,
-    level::{LevelState, LoadLevelEvent},
+    bindings::{Action, InputBindings},
+    camera::Camera,
+    level::LevelConfig,
+    physics::ColliderComponent,
     transform::TransformComponent,
-    Vector2d,
+    TimeScale, Vector2d, WORLD_UNIT_RATIO,
 };
-use gfx::input::{InputState, VirtualKeyCode};
+use gfx::input::InputState;
+use log::debug;
+use nalgebra::Vector2;
+use ncollide2d::shape::{Cuboid, ShapeHandle};
 use specs::prelude::*;
 
 pub const PADDLE_HIT_BOX_WIDTH: f64 = 57.0;
@@ -13,35 +19,176 @@ pub const PADDLE_SPRITE_WIDTH: u32 = 64;
 pub const PADDLE_SPRITE_HEIGHT: u32 = 32;
 pub const PADDLE_SCALE_X: f32 = 1.0;
 pub const PADDLE_SCALE_Y: f32 = 1.0;
+pub const PADDLE_DEFAULT_MAX_BOUNCE_ANGLE: f64 = std::f64::consts::FRAC_PI_3;
+pub const PADDLE_EXPAND_SCALE: f64 = 1.5;
+pub const PADDLE_SHRINK_SCALE: f64 = 0.6;
+pub const PADDLE_SIZE_EFFECT_TICKS: u32 = 60 * 15;
+pub const MAGNET_EFFECT_TICKS: u32 = 60 * 10;
+// Horizontal acceleration for every pixel the ball is away from the paddle, per tick
+pub const MAGNET_DEFAULT_STRENGTH: f64 = 0.0005;
+// Most the magnet can change a ball's horizontal velocity by in one tick
+pub const MAGNET_MAX_PULL: f64 = 0.1;
+// In pixels per tick. Keyboard movement ramps up to the max speed, and coasts back down when released.
+pub const PADDLE_DEFAULT_MAX_SPEED: f64 = 8.0;
+pub const PADDLE_DEFAULT_ACCELERATION: f64 = 2.0;
+pub const PADDLE_DEFAULT_DECELERATION: f64 = 2.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlMode {
+    Keyboard,
+    Mouse,
+}
+
+// The cursor is only held in the window while it is steering a paddle, and the game is running
+pub fn wants_cursor_grab(control_mode: ControlMode, paused: bool, focused: bool) -> bool {
+    control_mode == ControlMode::Mouse && !paused && focused
+}
+
+// Players are numbered from 0, in the order their paddles are spawned
+pub type PlayerId = u32;
 
 pub struct PlayerPaddleComponent {
+    pub player_id: PlayerId,
+    // Controls for this paddle's player. If None, the shared InputBindings are used.
+    pub bindings: Option<InputBindings>,
     pub held_ball_ent: Option<Entity>,
     pub held_ball_position: Vector2d,
-    pub level_width: u32,
+    // Horizontal offset of the held ball from the center of the paddle, which biases the launch angle
+    pub held_ball_offset_x: f64,
+    // Maximum angle (in radians, away from straight up) that a ball hitting the edge of the paddle will bounce at
+    pub max_bounce_angle: f64,
+    // If true, balls keep their speed when bouncing off the paddle. Otherwise they are reset to the default speed.
+    pub preserve_ball_speed: bool,
+    pub control_mode: ControlMode,
+    // Current width of the paddle hit box. This only changes while a size effect is active.
+    pub width: f64,
+    pub max_speed: f64,
+    // How much the speed can change each tick, when speeding up and when slowing down (or turning around)
+    pub acceleration: f64,
+    pub deceleration: f64,
+    // Horizontal speed from keyboard (or gamepad) control, before the time scale is applied
+    speed_x: f64,
     movement_linear_velocity: Vector2d,
 }
 
 impl PlayerPaddleComponent {
-    pub fn new(level_width: u32) -> Self {
+    pub fn new() -> Self {
         PlayerPaddleComponent {
+            player_id: 0,
+            bindings: None,
             held_ball_ent: None,
             held_ball_position: Vector2d::zeros(),
-            level_width,
+            held_ball_offset_x: 0.0,
+            max_bounce_angle: PADDLE_DEFAULT_MAX_BOUNCE_ANGLE,
+            preserve_ball_speed: true,
+            control_mode: ControlMode::Keyboard,
+            width: PADDLE_HIT_BOX_WIDTH,
+            max_speed: PADDLE_DEFAULT_MAX_SPEED,
+            acceleration: PADDLE_DEFAULT_ACCELERATION,
+            deceleration: PADDLE_DEFAULT_DECELERATION,
+            speed_x: 0.0,
             movement_linear_velocity: Vector2d::zeros(),
         }
     }
+
+    pub fn with_width(mut self, width: f64) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn with_player(mut self, player_id: PlayerId, bindings: InputBindings) -> Self {
+        self.player_id = player_id;
+        self.bindings = Some(bindings);
+        self
+    }
+
+    // The bindings that trigger the given action for this paddle. The player's own bindings are used for
+    // every action they bind, and the shared InputBindings for the rest.
+    pub fn bindings<'b>(&'b self, shared: &'b InputBindings, action: Action) -> &'b InputBindings {
+        match self.bindings.as_ref() {
+            Some(player_bindings) if player_bindings.binds(action) => player_bindings,
+            _ => shared,
+        }
+    }
+
+    // Moves the speed towards the one the input asks for, by at most a tick's worth of acceleration
+    fn accelerate(&mut self, target_speed_x: f64, time_scale: f64) {
+        let speeding_up =
+            target_speed_x.abs() > self.speed_x.abs() && target_speed_x * self.speed_x >= 0.0;
+        let rate = if speeding_up {
+            self.acceleration
+        } else {
+            self.deceleration
+        } * time_scale;
+
+        let change = nalgebra::clamp(target_speed_x - self.speed_x, -rate, rate);
+        self.speed_x += change;
+    }
+
+    // How far the paddle moved this tick
+    pub fn movement_velocity(&self) -> Vector2d {
+        self.movement_linear_velocity
+    }
+
+    // Velocity a held ball would be launched with right now. It goes off away from the paddle center,
+    // the same way a paddle bounce would, and picks up some of the paddle's movement.
+    pub fn launch_velocity(&self) -> Vector2d {
+        let offset_ratio = nalgebra::clamp(self.held_ball_offset_x / (self.width / 2.0), -1.0, 1.0);
+
+        let mut velocity = self.movement_linear_velocity * 0.5;
+        velocity.x += offset_ratio * crate::game::ball::BALL_DEFAULT_FORCE;
+        velocity.y = -crate::game::ball::BALL_DEFAULT_FORCE;
+
+        velocity
+    }
 }
 
 impl Component for PlayerPaddleComponent {
     type Storage = VecStorage<Self>;
 }
 
+// Scales the width of a paddle. The paddle goes back to its normal size once this is removed,
+// which a TimedEffectComponent takes care of.
+pub struct PaddleSizeEffectComponent {
+    pub scale: f64,
+}
+
+impl PaddleSizeEffectComponent {
+    pub fn new(scale: f64) -> Self {
+        PaddleSizeEffectComponent { scale }
+    }
+}
+
+impl Component for PaddleSizeEffectComponent {
+    type Storage = VecStorage<Self>;
+}
+
+// Gently pulls the nearest falling ball towards the paddle. A TimedEffectComponent removes this once it runs out.
+pub struct MagnetPaddleComponent {
+    pub strength: f64,
+}
+
+impl MagnetPaddleComponent {
+    pub fn new() -> Self {
+        MagnetPaddleComponent {
+            strength: MAGNET_DEFAULT_STRENGTH,
+        }
+    }
+}
+
+impl Component for MagnetPaddleComponent {
+    type Storage = VecStorage<Self>;
+}
+
 pub struct PlayerPaddleSystem;
 
 impl<'a> System<'a> for PlayerPaddleSystem {
     type SystemData = (
-        Write<'a, LevelState>,
-        Read<'a, InputState>,
+        ReadExpect<'a, LevelConfig>,
+        ReadExpect<'a, InputState>,
+        Read<'a, InputBindings>,
+        ReadExpect<'a, Camera>,
+        Read<'a, TimeScale>,
         WriteStorage<'a, TransformComponent>,
         WriteStorage<'a, PlayerPaddleComponent>,
         WriteStorage<'a, BallComponent>,
@@ -49,61 +196,503 @@ impl<'a> System<'a> for PlayerPaddleSystem {
 
     fn run(
         &mut self,
-        (mut level, input, mut transforms, mut paddles, mut balls): Self::SystemData,
+        (
+            config,
+            input,
+            bindings,
+            camera,
+            time_scale,
+            mut transforms,
+            mut paddles,
+            mut balls,
+        ): Self::SystemData,
     ) {
         for (transform, paddle) in (&mut transforms, &mut paddles).join() {
-            let speed = 8.0;
+            // Switch between keyboard and mouse control
+            if paddle
+                .bindings(&bindings, Action::ToggleControlMode)
+                .was_pressed(&input, Action::ToggleControlMode)
+            {
+                paddle.control_mode = match paddle.control_mode {
+                    ControlMode::Keyboard => ControlMode::Mouse,
+                    ControlMode::Mouse => ControlMode::Keyboard,
+                };
+            }
+
+            // Keys accelerate the paddle up to its max speed, which slows down with the rest of the game.
+            // A gamepad stick moves it proportionally to how far the stick is pushed.
             paddle.movement_linear_velocity = Vector2d::zeros();
 
-            if input.is_key_held(VirtualKeyCode::A) || input.is_key_held(VirtualKeyCode::Left) {
-                paddle.movement_linear_velocity.x -= speed;
-            }
+            match paddle.control_mode {
+                ControlMode::Keyboard => {
+                    // Each paddle only responds to its own player's controls. Both move actions
+                    // come from the same bindings, so the axis does too.
+                    let target_speed_x = paddle
+                        .bindings(&bindings, Action::MoveLeft)
+                        .move_axis(&input)
+                        * paddle.max_speed;
+                    paddle.accelerate(target_speed_x, time_scale.get());
+                    paddle.movement_linear_velocity.x = paddle.speed_x * time_scale.get();
+                }
+                ControlMode::Mouse => {
+                    paddle.speed_x = 0.0;
 
-            if input.is_key_held(VirtualKeyCode::D) || input.is_key_held(VirtualKeyCode::Right) {
-                paddle.movement_linear_velocity.x += speed;
+                    // Move straight to the cursor
+                    if let Some((cursor_x, cursor_y)) = input.cursor_position() {
+                        let cursor_position = camera.screen_to_world(cursor_x, cursor_y);
+                        paddle.movement_linear_velocity.x =
+                            cursor_position.x - transform.position.x;
+                    }
+                }
             }
 
             transform.position += paddle.movement_linear_velocity;
 
-            // Restrain paddle to the level
-            let paddle_x_min = 2.0;
-            let paddle_x_max = paddle.level_width as f64 - 2.0;
-            let paddle_half_width = PADDLE_HIT_BOX_WIDTH / 2.0;
-            if (transform.position.x - paddle_half_width) < paddle_x_min {
-                transform.position.x = paddle_x_min + paddle_half_width;
-            } else if (transform.position.x + paddle_half_width) > paddle_x_max {
-                transform.position.x = paddle_x_max - paddle_half_width;
+            // Restrain paddle to the level, so it can never slide past the side walls
+            let paddle_half_width = paddle.width / 2.0;
+            let paddle_x_min = 2.0 + paddle_half_width;
+            let paddle_x_max = config.field_width as f64 - 2.0 - paddle_half_width;
+            let clamped_x = nalgebra::clamp(transform.position.x, paddle_x_min, paddle_x_max);
+            if (clamped_x - transform.position.x).abs() > std::f64::EPSILON {
+                // Stop dead against the wall, rather than building up speed into it
+                paddle.speed_x = 0.0;
             }
 
+            transform.position.x = clamped_x;
+
             paddle.held_ball_position = transform.position
                 + Vector2d::new(
-                    0.0,
-                    (-PADDLE_HIT_BOX_HEIGHT as f64 / 2.0)
-                        - crate::game::ball::BALL_COLLIDER_RADIUS
-                        - 2.0,
+                    paddle.held_ball_offset_x,
+                    (-config.paddle_height / 2.0) - crate::game::ball::BALL_DEFAULT_RADIUS - 2.0,
                 );
-
-            // If the game is over, and the player presses 'R', begin a new game
-            if (level.lives == 0) && input.is_key_pressed(VirtualKeyCode::R) {
-                level.load_level_event = Some(LoadLevelEvent);
-            }
         }
 
         // Handle paddles that are holding a ball
         for mut paddle in (&mut paddles).join() {
             if let Some(ball_ent) = paddle.held_ball_ent {
-                if input.is_key_pressed(VirtualKeyCode::Space) {
+                if paddle
+                    .bindings(&bindings, Action::Launch)
+                    .was_pressed(&input, Action::Launch)
+                {
                     paddle.held_ball_ent = None;
 
                     let ball = balls.get_mut(ball_ent).expect(
                         "Failed to set held_ball_ent position! Entity had no BallComponent!",
                     );
 
-                    ball.holding_paddle_ent = None;
-                    ball.velocity.linear = paddle.movement_linear_velocity * 0.5;
-                    ball.velocity.linear.y = -crate::game::ball::BALL_DEFAULT_FORCE;
+                    ball.launch(paddle.launch_velocity(), Some(paddle.player_id));
                 }
             }
         }
     }
 }
+
+pub struct PaddleSizeSystem;
+
+impl<'a> System<'a> for PaddleSizeSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, LevelConfig>,
+        WriteStorage<'a, TransformComponent>,
+        WriteStorage<'a, ColliderComponent>,
+        WriteStorage<'a, PlayerPaddleComponent>,
+        ReadStorage<'a, PaddleSizeEffectComponent>,
+    );
+
+    fn run(
+        &mut self,
+        (ents, config, mut transforms, mut colliders, mut paddles, size_effects): Self::SystemData,
+    ) {
+        for (ent, transform, paddle, size_effect) in
+            (&ents, &mut transforms, &mut paddles, size_effects.maybe()).join()
+        {
+            let scale = size_effect.map_or(1.0, |size_effect| size_effect.scale);
+
+            let width = config.paddle_width * scale;
+            if (paddle.width - width).abs() < std::f64::EPSILON {
+                continue;
+            }
+
+            paddle.width = width;
+            transform.scale.x = config.paddle_scale.x * scale as f32;
+
+            // Only fetch the collider mutably when it actually changes, since that flags it for a physics update
+            if let Some(collider) = colliders.get_mut(ent) {
+                collider.shape = ShapeHandle::new(Cuboid::new(Vector2::new(
+                    (width / 2.0) * WORLD_UNIT_RATIO,
+                    (config.paddle_height / 2.0) * WORLD_UNIT_RATIO,
+                )));
+            }
+
+            debug!(target: "paddle", "[PaddleSizeSystem] Paddle width is now {}", width);
+        }
+    }
+}
+
+// Bends the path of the ball nearest to each magnet paddle. Only the horizontal velocity changes,
+// so the ball keeps falling at the same rate and can't be held in place.
+pub struct MagnetSystem;
+
+impl<'a> System<'a> for MagnetSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, TimeScale>,
+        ReadStorage<'a, TransformComponent>,
+        ReadStorage<'a, MagnetPaddleComponent>,
+        WriteStorage<'a, BallComponent>,
+    );
+
+    fn run(&mut self, (ents, time_scale, transforms, magnets, mut balls): Self::SystemData) {
+        for (paddle_transform, magnet) in (&transforms, &magnets).join() {
+            let paddle_position = paddle_transform.position;
+
+            // Only balls on their way down to the paddle are pulled
+            let nearest_ball = (&ents, &transforms, &balls)
+                .join()
+                .filter(|(_, _, ball)| ball.is_launched() && ball.velocity.linear.y > 0.0)
+                .map(|(ent, transform, _)| (ent, (transform.position - paddle_position).norm()))
+                .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+                .map(|(ent, _)| ent);
+
+            let ball_ent = match nearest_ball {
+                Some(ent) => ent,
+                None => continue,
+            };
+
+            let ball_x = transforms.get(ball_ent).unwrap().position.x;
+            let pull = nalgebra::clamp(
+                (paddle_position.x - ball_x) * magnet.strength,
+                -MAGNET_MAX_PULL,
+                MAGNET_MAX_PULL,
+            );
+
+            let ball = balls.get_mut(ball_ent).unwrap();
+            ball.velocity.linear.x += pull * time_scale.get();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        ball,
+        physics::PhysicsState,
+        testing::{self, DT},
+        GameState,
+    };
+    use gfx::input::VirtualKeyCode;
+
+    #[test]
+    fn paddle_stops_at_the_left_wall() {
+        let (mut game, paddle_ent) = testing::empty_game();
+
+        let mut input = InputState::new();
+        input.handle_key(VirtualKeyCode::A, true);
+        game.world.insert(input);
+
+        let min_x = 2.0 + PADDLE_HIT_BOX_WIDTH / 2.0;
+        for _ in 0..60 {
+            game.tick(DT);
+            let x = testing::position(&game.world, paddle_ent).x;
+            assert!(x >= min_x, "paddle went past the wall, to x = {}", x);
+        }
+
+        assert_eq!(testing::position(&game.world, paddle_ent).x, min_x);
+    }
+
+    #[test]
+    fn held_ball_follows_the_paddle_until_launched() {
+        let (mut game, paddle_ent) = testing::empty_game();
+        testing::spawn_held_ball(&game.world, paddle_ent);
+        game.tick(DT);
+        let ball_ent = testing::balls(&game.world)[0];
+
+        let mut input = InputState::new();
+        input.handle_key(VirtualKeyCode::D, true);
+        game.world.insert(input);
+        for _ in 0..10 {
+            game.tick(DT);
+            let paddle_position = testing::position(&game.world, paddle_ent);
+            let ball_position = testing::position(&game.world, ball_ent);
+            assert!((ball_position.x - paddle_position.x).abs() < 1e-6);
+            assert!((ball_position.y - (paddle_position.y - 9.5)).abs() < 1e-6);
+        }
+
+        let spawn_x = game.world.read_resource::<LevelConfig>().paddle_spawn_x(0);
+        assert!(testing::position(&game.world, paddle_ent).x > spawn_x);
+
+        let mut input = InputState::new();
+        input.handle_key(VirtualKeyCode::Space, true);
+        game.world.insert(input);
+        game.tick(DT);
+
+        let paddles = game.world.read_storage::<PlayerPaddleComponent>();
+        let balls = game.world.read_storage::<BallComponent>();
+        let ball = balls.get(ball_ent).unwrap();
+        assert!(paddles.get(paddle_ent).unwrap().held_ball_ent.is_none());
+        assert!(ball.is_launched());
+        assert!(ball.velocity.linear.y < 0.0);
+    }
+
+    #[test]
+    fn paddle_follows_the_mouse() {
+        let (mut game, paddle_ent) = testing::empty_game();
+        game.world
+            .write_storage::<PlayerPaddleComponent>()
+            .get_mut(paddle_ent)
+            .unwrap()
+            .control_mode = ControlMode::Mouse;
+
+        for &cursor_x in [100.0, 250.0].iter() {
+            let mut input = InputState::new();
+            input.handle_cursor_moved(cursor_x, 120.0);
+            game.world.insert(input);
+            game.tick(DT);
+
+            assert_eq!(
+                testing::position(&game.world, paddle_ent).x,
+                cursor_x as f64
+            );
+        }
+    }
+
+    #[test]
+    fn held_ball_is_aimed_away_from_the_paddle_center() {
+        let mut paddle = PlayerPaddleComponent::new();
+
+        let direction = paddle.launch_velocity().normalize();
+        assert!((direction - Vector2d::new(0.0, -1.0)).norm() < 1e-9);
+
+        // Held on the right edge, the ball goes off at 45 degrees to the right
+        paddle.held_ball_offset_x = paddle.width / 2.0;
+        let direction = paddle.launch_velocity().normalize();
+        let angle = direction.x.atan2(-direction.y);
+        assert!((angle - std::f64::consts::FRAC_PI_4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rebound_move_left_key_replaces_the_old_one() {
+        let (mut game, paddle_ent) = testing::empty_game();
+        game.world
+            .write_resource::<InputBindings>()
+            .rebind(Action::MoveLeft, VirtualKeyCode::J);
+        let start_x = testing::position(&game.world, paddle_ent).x;
+
+        game.world.insert(testing::pressed(&[VirtualKeyCode::A]));
+        game.run_headless(10, DT);
+        assert_eq!(testing::position(&game.world, paddle_ent).x, start_x);
+
+        game.world.insert(testing::pressed(&[VirtualKeyCode::J]));
+        game.run_headless(10, DT);
+        assert!(testing::position(&game.world, paddle_ent).x < start_x);
+    }
+
+    // How far the paddle moves over a few ticks at full speed, with the gamepad stick held at the given value
+    fn distance_at_stick(axis_x: f32) -> f64 {
+        let (mut game, paddle_ent) = testing::empty_game();
+        let mut input = InputState::new();
+        input.handle_gamepad_axis_x(axis_x);
+        game.world.insert(input);
+
+        // Get up to speed first
+        game.run_headless(5, DT);
+        let start_x = testing::position(&game.world, paddle_ent).x;
+        game.run_headless(3, DT);
+
+        testing::position(&game.world, paddle_ent).x - start_x
+    }
+
+    #[test]
+    fn paddle_speed_is_proportional_to_the_stick() {
+        let half_distance = distance_at_stick(0.5);
+        assert!(half_distance > 0.0);
+        assert!((distance_at_stick(1.0) - half_distance * 2.0).abs() < 1e-9);
+        assert!((distance_at_stick(-0.5) + half_distance).abs() < 1e-9);
+    }
+
+    // The x position of each player's paddle, in player order
+    fn paddle_positions(world: &World) -> Vec<(PlayerId, f64)> {
+        let paddles = world.read_storage::<PlayerPaddleComponent>();
+        let transforms = world.read_storage::<TransformComponent>();
+        let mut positions: Vec<(PlayerId, f64)> = (&paddles, &transforms)
+            .join()
+            .map(|(paddle, transform)| (paddle.player_id, transform.position.x))
+            .collect();
+        positions.sort_by_key(|&(player_id, _)| player_id);
+
+        positions
+    }
+
+    fn two_player_game<'a, 'b>() -> GameState<'a, 'b> {
+        let mut game =
+            GameState::new_multiplayer_seeded(testing::FIELD_WIDTH, testing::FIELD_HEIGHT, 2, 0);
+        game.world.write_resource::<PhysicsState>().set_timestep(DT);
+        game.tick(DT);
+
+        game
+    }
+
+    fn control_modes(world: &World) -> Vec<ControlMode> {
+        let paddles = world.read_storage::<PlayerPaddleComponent>();
+        let mut modes: Vec<(PlayerId, ControlMode)> = paddles
+            .join()
+            .map(|paddle| (paddle.player_id, paddle.control_mode))
+            .collect();
+        modes.sort_by_key(|&(player_id, _)| player_id);
+
+        modes.into_iter().map(|(_, mode)| mode).collect()
+    }
+
+    #[test]
+    fn each_player_toggles_only_their_own_control_mode() {
+        let mut game = two_player_game();
+
+        game.world.insert(testing::pressed(&[VirtualKeyCode::Down]));
+        game.tick(DT);
+
+        assert_eq!(
+            control_modes(&game.world),
+            vec![ControlMode::Keyboard, ControlMode::Mouse]
+        );
+    }
+
+    #[test]
+    fn actions_a_player_has_no_binding_for_use_the_shared_bindings() {
+        let shared = InputBindings::default();
+        let paddle = PlayerPaddleComponent::new().with_player(2, InputBindings::for_player(2));
+        assert_eq!(
+            paddle
+                .bindings(&shared, Action::Launch)
+                .keys(Action::Launch),
+            &[VirtualKeyCode::Space]
+        );
+
+        let paddle = PlayerPaddleComponent::new().with_player(1, InputBindings::for_player(1));
+        assert_eq!(
+            paddle
+                .bindings(&shared, Action::Launch)
+                .keys(Action::Launch),
+            &[VirtualKeyCode::Up]
+        );
+    }
+
+    #[test]
+    fn each_paddle_only_answers_to_its_own_player() {
+        let mut game = two_player_game();
+
+        let start = paddle_positions(&game.world);
+        assert_eq!(start.len(), 2);
+        game.world.insert(testing::pressed(&[VirtualKeyCode::D]));
+        game.run_headless(5, DT);
+        let moved = paddle_positions(&game.world);
+        assert!(moved[0].1 > start[0].1);
+        assert_eq!(moved[1].1, start[1].1);
+
+        // Let the first paddle come to a stop
+        game.world.insert(InputState::new());
+        game.run_headless(10, DT);
+        let start = paddle_positions(&game.world);
+        game.world.insert(testing::pressed(&[VirtualKeyCode::Left]));
+        game.run_headless(5, DT);
+        let moved = paddle_positions(&game.world);
+        assert_eq!(moved[0].1, start[0].1);
+        assert!(moved[1].1 < start[1].1);
+    }
+
+    fn paddle_speed(world: &World, paddle_ent: Entity) -> f64 {
+        world
+            .read_storage::<PlayerPaddleComponent>()
+            .get(paddle_ent)
+            .unwrap()
+            .movement_velocity()
+            .x
+    }
+
+    #[test]
+    fn paddle_speeds_up_to_max_and_coasts_to_a_stop() {
+        let (mut game, paddle_ent) = testing::empty_game();
+
+        game.world.insert(testing::pressed(&[VirtualKeyCode::D]));
+        let mut speeds = Vec::new();
+        for _ in 0..6 {
+            game.tick(DT);
+            speeds.push(paddle_speed(&game.world, paddle_ent));
+        }
+        assert_eq!(speeds[0], PADDLE_DEFAULT_ACCELERATION);
+        assert!(speeds.windows(2).all(|pair| pair[1] >= pair[0]));
+        assert_eq!(speeds[speeds.len() - 1], PADDLE_DEFAULT_MAX_SPEED);
+
+        game.world.insert(InputState::new());
+        let mut speeds = Vec::new();
+        for _ in 0..5 {
+            game.tick(DT);
+            speeds.push(paddle_speed(&game.world, paddle_ent));
+        }
+        assert_eq!(
+            speeds[0],
+            PADDLE_DEFAULT_MAX_SPEED - PADDLE_DEFAULT_DECELERATION
+        );
+        assert!(speeds.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert_eq!(speeds[speeds.len() - 1], 0.0);
+    }
+
+    #[test]
+    fn magnet_pulls_the_ball_towards_the_paddle_without_slowing_its_fall() {
+        let (mut game, paddle_ent) = testing::empty_game();
+        game.world
+            .write_storage::<MagnetPaddleComponent>()
+            .insert(paddle_ent, MagnetPaddleComponent::new())
+            .unwrap();
+
+        // Well to the left of the paddle, falling straight down
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(60.0, 80.0),
+            Vector2d::new(0.0, ball::BALL_DEFAULT_FORCE),
+        );
+        game.tick(DT);
+        let ball_ent = testing::balls(&game.world)[0];
+
+        game.run_headless(10, DT);
+
+        let velocity = testing::ball_velocity(&game.world, ball_ent);
+        assert!(velocity.x > 0.0);
+        assert!((velocity.y - ball::BALL_DEFAULT_FORCE).abs() < 0.1);
+    }
+
+    #[test]
+    fn cursor_is_grabbed_only_for_mouse_control_while_playing_and_focused() {
+        assert!(wants_cursor_grab(ControlMode::Mouse, false, true));
+        assert!(!wants_cursor_grab(ControlMode::Mouse, true, true));
+        assert!(!wants_cursor_grab(ControlMode::Mouse, false, false));
+        assert!(!wants_cursor_grab(ControlMode::Keyboard, false, true));
+    }
+
+    #[test]
+    fn ball_slamming_into_the_paddle_does_not_push_it() {
+        let (mut game, paddle_ent) = testing::empty_game();
+        let paddle_x = testing::position(&game.world, paddle_ent).x;
+
+        // Coming in fast and at an angle, onto the right half of the paddle
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(paddle_x, 190.0),
+            Vector2d::new(6.0, ball::BALL_MAX_LINEAR_VELOCITY * 0.8),
+        );
+        game.tick(DT);
+        let ball_ent = testing::balls(&game.world)[0];
+
+        let mut bounced = false;
+        for _ in 0..30 {
+            game.tick(DT);
+            assert_eq!(testing::position(&game.world, paddle_ent).x, paddle_x);
+            if testing::ball_velocity(&game.world, ball_ent).y < 0.0 {
+                bounced = true;
+                break;
+            }
+        }
+
+        assert!(bounced, "ball never hit the paddle");
+    }
+}
@@ -1,22 +1,25 @@
 use crate::game::*;
+use log::{debug, error, warn};
 use nalgebra::{Isometry2, Vector2};
 use ncollide2d::{
     pipeline::{CollisionGroups, ContactEvent},
+    query::Proximity,
     shape::{Shape, ShapeHandle},
 };
 use nphysics2d::{
     force_generator::DefaultForceGeneratorSet,
     joint::DefaultJointConstraintSet,
+    material::{BasicMaterial, MaterialHandle},
     math::Velocity,
     object::{
-        Body, BodyPartHandle, BodySet, BodyStatus, ColliderDesc, DefaultBodyHandle, DefaultBodySet,
-        DefaultColliderHandle, DefaultColliderSet, Ground, RigidBodyDesc,
+        Body, BodyPartHandle, BodySet, BodyStatus, Collider, ColliderDesc, DefaultBodyHandle,
+        DefaultBodySet, DefaultColliderHandle, DefaultColliderSet, Ground, RigidBodyDesc,
     },
     world::{DefaultGeometricalWorld, DefaultMechanicalWorld},
 };
 use shrev::EventChannel;
-use specs::prelude::*;
-use std::collections::HashMap;
+use specs::{prelude::*, world::EntitiesRes};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug)]
 pub enum CollisionType {
@@ -29,11 +32,54 @@ pub struct CollisionEvent {
     pub collider_handle_a: DefaultColliderHandle,
     pub entity_b: Option<Entity>,
     pub collider_handle_b: DefaultColliderHandle,
+    // Contact normal at the deepest contact point. This always points from entity_a's collider towards entity_b's,
+    // whichever order the physics world reported the pair in. None if the contact had no points.
     pub normal: Option<Vector2<f64>>,
+    // Deepest contact point on entity_a's collider, in pixels. None if the contact had no points.
     pub collision_point: Option<Point2d>,
     pub ty: CollisionType,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProximityType {
+    Entered,
+    Exited,
+}
+
+// Sent when a sensor starts or stops overlapping another collider.
+// Like CollisionEvents, these are sent once for each entity involved, with entity_a being that entity.
+pub struct ProximityEvent {
+    pub entity_a: Option<Entity>,
+    pub collider_handle_a: DefaultColliderHandle,
+    pub entity_b: Option<Entity>,
+    pub collider_handle_b: DefaultColliderHandle,
+    pub ty: ProximityType,
+}
+
+// The integration parameters that are worth tuning. Fewer iterations and substeps make stepping
+// cheaper, at the cost of accuracy, which can help with dense levels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhysicsConfig {
+    pub max_ccd_position_iterations: usize,
+    pub max_ccd_substeps: usize,
+    pub max_velocity_iterations: usize,
+    pub max_position_iterations: usize,
+    // 0.0 disables warmstarting, 1.0 uses the full impulses from the last step
+    pub warmstart_coeff: f64,
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        PhysicsConfig {
+            max_ccd_position_iterations: 10,
+            max_ccd_substeps: 1,
+            max_velocity_iterations: 8,
+            max_position_iterations: 3,
+            warmstart_coeff: 1.0,
+        }
+    }
+}
+
 pub struct PhysicsState {
     pub lerp: f64,
     pub bodies: DefaultBodySet<f64>,
@@ -45,21 +91,21 @@ pub struct PhysicsState {
     ent_body_handles: HashMap<u32, DefaultBodyHandle>,
     ent_collider_handles: HashMap<u32, DefaultColliderHandle>,
     ground_body_handle: DefaultBodyHandle,
+    // Unscaled length of a physics step
+    timestep: f64,
 }
 
 impl PhysicsState {
     pub fn new() -> Self {
+        // Balls should travel in straight lines between bounces, so there is no gravity by default
+        PhysicsState::with_gravity(Vector2::zeros())
+    }
+
+    pub fn with_gravity(gravity: Vector2<f64>) -> Self {
         let mut bodies = DefaultBodySet::new();
         let colliders = DefaultColliderSet::new();
 
-        let gravity = Vector2::new(0.0, -9.81);
-        let mut mechanical_world = DefaultMechanicalWorld::new(gravity);
-        mechanical_world
-            .integration_parameters
-            .max_ccd_position_iterations = 10;
-
-        mechanical_world.integration_parameters.max_ccd_substeps = 1;
-
+        let mechanical_world = DefaultMechanicalWorld::new(gravity);
         let geometrical_world = DefaultGeometricalWorld::new();
         let joint_constraints = DefaultJointConstraintSet::new();
         let force_generators = DefaultForceGeneratorSet::new();
@@ -67,8 +113,9 @@ impl PhysicsState {
         let body_handles = HashMap::new();
         let collider_handles = HashMap::new();
         let ground_body_handle = bodies.insert(Ground::new());
+        let timestep = mechanical_world.timestep();
 
-        PhysicsState {
+        let mut state = PhysicsState {
             lerp: 0.0,
             bodies,
             colliders,
@@ -79,10 +126,123 @@ impl PhysicsState {
             ent_body_handles: body_handles,
             ent_collider_handles: collider_handles,
             ground_body_handle,
+            timestep,
+        };
+
+        state.configure(PhysicsConfig::default());
+
+        state
+    }
+
+    pub fn configure(&mut self, config: PhysicsConfig) {
+        let params = &mut self.mechanical_world.integration_parameters;
+        params.max_ccd_position_iterations = config.max_ccd_position_iterations;
+        params.max_ccd_substeps = config.max_ccd_substeps;
+        params.max_velocity_iterations = config.max_velocity_iterations;
+        params.max_position_iterations = config.max_position_iterations;
+        params.warmstart_coeff = config.warmstart_coeff;
+    }
+
+    pub fn config(&self) -> PhysicsConfig {
+        let params = &self.mechanical_world.integration_parameters;
+        PhysicsConfig {
+            max_ccd_position_iterations: params.max_ccd_position_iterations,
+            max_ccd_substeps: params.max_ccd_substeps,
+            max_velocity_iterations: params.max_velocity_iterations,
+            max_position_iterations: params.max_position_iterations,
+            warmstart_coeff: params.warmstart_coeff,
         }
     }
 
-    pub fn step(&mut self) {
+    // Sizes of the body and collider sets (including the ground body), and the number of entities with a
+    // body or collider handle. These go back to where they were once everything spawned since is deleted,
+    // so they can be used to spot leaks.
+    pub fn body_count(&self) -> usize {
+        self.bodies.iter().count()
+    }
+
+    pub fn collider_count(&self) -> usize {
+        self.colliders.iter().count()
+    }
+
+    pub fn tracked_entity_count(&self) -> usize {
+        self.ent_body_handles
+            .keys()
+            .chain(self.ent_collider_handles.keys())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    pub fn gravity(&self) -> Vector2<f64> {
+        self.mechanical_world.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vector2<f64>) {
+        self.mechanical_world.gravity = gravity;
+    }
+
+    // The physics timestep should match the fixed tick dt, so the simulation runs in real time
+    pub fn set_timestep(&mut self, dt: f64) {
+        self.timestep = dt;
+        self.mechanical_world.set_timestep(dt);
+    }
+
+    // Removes the bodies and colliders of any entities that have been deleted, along with their handles
+    pub fn remove_dead_entities(&mut self, entities: &EntitiesRes) {
+        let dead_body_ids: Vec<u32> = self
+            .ent_body_handles
+            .iter()
+            .filter(|(_, rb_handle)| {
+                self.bodies
+                    .rigid_body(**rb_handle)
+                    .and_then(|body| body.user_data())
+                    .and_then(|data| data.downcast_ref::<Entity>())
+                    .map_or(true, |ent| !entities.is_alive(*ent))
+            })
+            .map(|(ent_id, _)| *ent_id)
+            .collect();
+
+        let dead_collider_ids: Vec<u32> = self
+            .ent_collider_handles
+            .iter()
+            .filter(|(_, collider_handle)| {
+                self.colliders
+                    .get(**collider_handle)
+                    .and_then(|collider| collider.user_data())
+                    .and_then(|data| data.downcast_ref::<Entity>())
+                    .map_or(true, |ent| !entities.is_alive(*ent))
+            })
+            .map(|(ent_id, _)| *ent_id)
+            .collect();
+
+        for ent_id in dead_collider_ids {
+            if let Some(collider_handle) = self.ent_collider_handles.remove(&ent_id) {
+                self.colliders.remove(collider_handle);
+                debug!(
+                    target: "physics",
+                    "[PhysicsCleanupSystem] Removed collider of deleted entity. Entity Id = {}",
+                    ent_id
+                );
+            }
+        }
+
+        for ent_id in dead_body_ids {
+            if let Some(rb_handle) = self.ent_body_handles.remove(&ent_id) {
+                self.bodies.remove(rb_handle);
+                debug!(
+                    target: "physics",
+                    "[PhysicsCleanupSystem] Removed rigidbody of deleted entity. Entity Id = {}",
+                    ent_id
+                );
+            }
+        }
+    }
+
+    // Steps the simulation by the timestep, scaled by the given time scale.
+    // Bodies move the same way at any scale, just over more or fewer steps.
+    pub fn step(&mut self, time_scale: f64) {
+        self.mechanical_world
+            .set_timestep(self.timestep * time_scale);
         self.mechanical_world.step(
             &mut self.geometrical_world,
             &mut self.bodies,
@@ -126,12 +286,58 @@ impl Component for RigidbodyComponent {
     type Storage = FlaggedStorage<Self, VecStorage<Self>>;
 }
 
+// How bouncy and slippery a collider's surface is. Colliders of the same kind share one of the presets,
+// so they can all be tuned in one place.
+// Only bodies that the physics solver moves are affected by these. Balls aren't, since the BallSystem works out
+// their bounces itself, and overwrites their velocity every tick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhysicsMaterial {
+    pub restitution: f64,
+    pub friction: f64,
+}
+
+impl PhysicsMaterial {
+    // Walls give back everything that hits them
+    pub const WALL: PhysicsMaterial = PhysicsMaterial {
+        restitution: 1.0,
+        friction: 0.0,
+    };
+    // The paddle takes a little energy out of hits
+    pub const PADDLE: PhysicsMaterial = PhysicsMaterial {
+        restitution: 0.9,
+        friction: 0.1,
+    };
+    // Bricks neither add nor take away energy, so they bounce like walls until tuned otherwise
+    pub const BRICK: PhysicsMaterial = PhysicsMaterial {
+        restitution: 1.0,
+        friction: 0.0,
+    };
+
+    pub fn new(restitution: f64, friction: f64) -> Self {
+        PhysicsMaterial {
+            restitution,
+            friction,
+        }
+    }
+}
+
+// Same as the nphysics default material
+impl Default for PhysicsMaterial {
+    fn default() -> Self {
+        PhysicsMaterial::new(0.0, 0.5)
+    }
+}
+
 pub struct ColliderComponent {
     pub shape: ShapeHandle<f64>,
     pub offset: Vector2<f64>,
     pub collision_groups: CollisionGroups,
     pub density: f64,
+    // Off by default. Only fast, small colliders (balls and projectiles) turn it on, never static geometry.
     pub ccd_enabled: bool,
+    pub material: PhysicsMaterial,
+    // Sensors don't physically collide with anything. They only report overlaps, as ProximityEvents.
+    pub sensor: bool,
 }
 
 impl ColliderComponent {
@@ -147,15 +353,45 @@ impl ColliderComponent {
             collision_groups,
             density,
             // CCD seems kinda buggy at the moment https://github.com/rustsim/nphysics/issues/255
+            // Without it, fast bodies can tunnel through thin colliders. See BALL_MAX_LINEAR_VELOCITY.
             ccd_enabled: false,
+            material: PhysicsMaterial::default(),
+            sensor: false,
         }
     }
+
+    pub fn as_sensor(mut self) -> Self {
+        self.sensor = true;
+        self
+    }
+
+    pub fn with_ccd(mut self, ccd_enabled: bool) -> Self {
+        self.ccd_enabled = ccd_enabled;
+        self
+    }
+
+    pub fn with_material(mut self, material: PhysicsMaterial) -> Self {
+        self.material = material;
+        self
+    }
 }
 
 impl Component for ColliderComponent {
     type Storage = FlaggedStorage<Self, VecStorage<Self>>;
 }
 
+// Deleted entities only lose their components (and send removal events) once the world is maintained.
+// This removes their bodies and colliders straight away, so nothing is left behind in the physics world.
+pub struct PhysicsCleanupSystem;
+
+impl<'a> System<'a> for PhysicsCleanupSystem {
+    type SystemData = (Entities<'a>, WriteExpect<'a, PhysicsState>);
+
+    fn run(&mut self, (entities, mut physics): Self::SystemData) {
+        physics.remove_dead_entities(&entities);
+    }
+}
+
 #[derive(Default)]
 pub struct RigidbodySendPhysicsSystem {
     pub inserted_bodies: BitSet,
@@ -211,16 +447,33 @@ impl<'a> System<'a> for RigidbodySendPhysicsSystem {
             }
         }
 
-        // Handle removed rigidbodies
+        // Handle removed rigidbodies. Deleted entities will usually have been cleaned up already, by the PhysicsCleanupSystem.
         for ent_id in (&self.removed_bodies).join() {
             if let Some(rb_handle) = physics.ent_body_handles.remove(&ent_id) {
                 physics.bodies.remove(rb_handle);
-                println!(
+                debug!(
+                    target: "physics",
                     "[RigidbodySendPhysicsSystem] Removed rigidbody. Entity Id = {}",
                     ent_id
                 );
-            } else {
-                eprintln!("[RigidbodySendPhysicsSystem] Failed to remove rigidbody because it didn't exist! Entity Id = {}", ent_id);
+            }
+        }
+
+        // Drop bodies for entities whose rigidbody is gone, even if the removal event was missed
+        let stale_ids: Vec<u32> = physics
+            .ent_body_handles
+            .keys()
+            .filter(|ent_id| !rigidbodies.mask().contains(**ent_id))
+            .cloned()
+            .collect();
+        for ent_id in stale_ids {
+            if let Some(rb_handle) = physics.ent_body_handles.remove(&ent_id) {
+                physics.bodies.remove(rb_handle);
+                debug!(
+                    target: "physics",
+                    "[RigidbodySendPhysicsSystem] Removed stale rigidbody. Entity Id = {}",
+                    ent_id
+                );
             }
         }
 
@@ -233,14 +486,24 @@ impl<'a> System<'a> for RigidbodySendPhysicsSystem {
         )
             .join()
         {
+            // An existing body is only a real duplicate if it was created for this same component. Otherwise it
+            // belongs to an older entity that had this id, and was never cleaned up.
             if let Some(rb_handle) = physics.ent_body_handles.remove(&ent.id()) {
-                eprintln!("[RigidbodySendPhysicsSystem] Duplicate rigidbody found in physics world! Removing it. Entity Id = {}, Handle = {:?}", ent_id, rb_handle);
                 physics.bodies.remove(rb_handle);
+                if rigidbody.handle == Some(rb_handle) {
+                    warn!(target: "physics", "[RigidbodySendPhysicsSystem] Duplicate rigidbody found in physics world! Removing it. Entity Id = {}, Handle = {:?}", ent_id, rb_handle);
+                } else {
+                    debug!(
+                        target: "physics",
+                        "[RigidbodySendPhysicsSystem] Removed stale rigidbody. Entity Id = {}, Handle = {:?}",
+                        ent_id, rb_handle
+                    );
+                }
             }
 
             let rigid_body = RigidBodyDesc::new()
                 .translation(transform.position * WORLD_UNIT_RATIO)
-                .rotation(0.0)
+                .rotation(transform.rotation as f64)
                 .gravity_enabled(false)
                 .status(rigidbody.status)
                 .velocity(rigidbody.velocity)
@@ -255,7 +518,8 @@ impl<'a> System<'a> for RigidbodySendPhysicsSystem {
             let rb_handle = physics.bodies.insert(rigid_body);
             rigidbody.handle = Some(rb_handle);
             physics.ent_body_handles.insert(ent.id(), rb_handle);
-            println!(
+            debug!(
+                target: "physics",
                 "[RigidbodySendPhysicsSystem] Inserted rigidbody. Entity Id = {}, Handle = {:?}",
                 ent_id, rb_handle
             );
@@ -268,7 +532,7 @@ impl<'a> System<'a> for RigidbodySendPhysicsSystem {
                 rb.set_velocity(rigidbody.velocity);
                 rb.set_status(rigidbody.status);
             } else {
-                eprintln!("[RigidbodySendPhysicsSystem] Failed to update rigidbody because it didn't exist! Entity Id = {}", ent_id);
+                error!(target: "physics", "[RigidbodySendPhysicsSystem] Failed to update rigidbody because it didn't exist! Entity Id = {}", ent_id);
             }
         }
 
@@ -283,9 +547,12 @@ impl<'a> System<'a> for RigidbodySendPhysicsSystem {
         {
             if let Some(rb_handle) = physics.ent_body_handles.get(&ent.id()).cloned() {
                 let rb = physics.bodies.rigid_body_mut(rb_handle).unwrap();
-                rb.set_position(Isometry2::new(transform.position * WORLD_UNIT_RATIO, 0.0));
+                rb.set_position(Isometry2::new(
+                    transform.position * WORLD_UNIT_RATIO,
+                    transform.rotation as f64,
+                ));
             } else {
-                eprintln!("[RigidbodySendPhysicsSystem] Failed to update rigidbody because it didn't exist! Entity Id = {}", ent.id());
+                error!(target: "physics", "[RigidbodySendPhysicsSystem] Failed to update rigidbody because it didn't exist! Entity Id = {}", ent.id());
             }
         }
     }
@@ -305,6 +572,8 @@ pub struct ColliderSendPhysicsSystem {
     pub modified_colliders: BitSet,
     pub removed_colliders: BitSet,
     pub modified_transforms: BitSet,
+    // Inserted colliders that are waiting on their entity's rigidbody to be inserted first
+    pub pending_colliders: BitSet,
     pub transform_reader_id: Option<ReaderId<ComponentEvent>>,
     pub collider_reader_id: Option<ReaderId<ComponentEvent>>,
 }
@@ -358,69 +627,85 @@ impl<'a> System<'a> for ColliderSendPhysicsSystem {
             }
         }
 
-        // Handle removed colliders
+        // Handle removed colliders. Deleted entities will usually have been cleaned up already, by the PhysicsCleanupSystem.
         for ent_id in (&self.removed_colliders).join() {
             if let Some(collider_handle) = physics.ent_collider_handles.remove(&ent_id) {
                 physics.colliders.remove(collider_handle);
-                println!(
+                debug!(
+                    target: "physics",
                     "[ColliderSendPhysicsSystem] Removed collider. Entity Id = {}",
                     ent_id
                 );
-            } else {
-                eprintln!("[ColliderSendPhysicsSystem] Failed to remove collider because it didn't exist! Entity Id = {}", ent_id);
             }
         }
 
+        // Retry any colliders that were waiting on a rigidbody last time
+        let pending_colliders = std::mem::replace(&mut self.pending_colliders, BitSet::new());
+        for ent_id in (&pending_colliders).join() {
+            self.inserted_colliders.add(ent_id);
+        }
+
         // Handle inserted colliders
         for (ent, transform, collider, _) in
             (&entities, &transforms, &colliders, &self.inserted_colliders).join()
         {
+            // The RigidbodySendPhysicsSystem always runs first, but if this entity's body still isn't in the physics
+            // world, the collider would end up attached to the ground. Wait for the body instead.
+            if rigidbodies.contains(ent) && !physics.ent_body_handles.contains_key(&ent.id()) {
+                debug!(
+                    target: "physics",
+                    "[ColliderSendPhysicsSystem] Deferred collider until its rigidbody is inserted. Entity Id = {}",
+                    ent.id()
+                );
+                self.pending_colliders.add(ent.id());
+                continue;
+            }
+
             if let Some(collider_handle) = physics.ent_collider_handles.remove(&ent.id()) {
-                eprintln!("[ColliderSendPhysicsSystem] Duplicate collider found in physics world! Removing it. Entity Id = {}, Handle = {:?}", ent.id(), collider_handle);
+                warn!(target: "physics", "[ColliderSendPhysicsSystem] Duplicate collider found in physics world! Removing it. Entity Id = {}, Handle = {:?}", ent.id(), collider_handle);
                 physics.colliders.remove(collider_handle);
             }
 
-            // If this entity has a rigidbody, we need to attach the collider to it.
-            // Otherwise we just attach it to the "ground".
-            let (parent_body_handle, translation) =
-                if let Some(rb_handle) = physics.ent_body_handles.get(&ent.id()) {
-                    (rb_handle.clone(), collider.offset)
-                } else {
-                    (
-                        physics.ground_body_handle.clone(),
-                        (transform.position + collider.offset) * WORLD_UNIT_RATIO,
-                    )
-                };
-
-            let collider = ColliderDesc::new(collider.shape.clone())
-                .density(collider.density)
-                .translation(translation)
-                .margin(0.02)
-                .ccd_enabled(collider.ccd_enabled)
-                .collision_groups(collider.collision_groups.clone())
-                .user_data(ent)
-                .build(BodyPartHandle(parent_body_handle, 0));
+            let collider = build_collider(&physics, ent, transform, collider);
             let collider_handle = physics.colliders.insert(collider);
             physics
                 .ent_collider_handles
                 .insert(ent.id(), collider_handle);
-            println!(
+            debug!(
+                target: "physics",
                 "[ColliderSendPhysicsSystem] Inserted collider. Entity Id = {}, Handle = {:?}",
                 ent.id(),
                 collider_handle
             );
         }
 
-        // Handle modified colliders
-        for (ent, _, _) in (&entities, &colliders, &self.modified_colliders).join() {
-            if let Some(_) = physics.ent_collider_handles.get(&ent.id()).cloned() {
-                // TODO
-                println!(
-                    "[ColliderSendPhysicsSystem] Modified collider: {}",
-                    ent.id()
+        // Handle modified colliders, by rebuilding them from scratch.
+        // Pending colliders are skipped, since they will be built from the latest component once they're inserted.
+        for (ent, transform, collider, _, _) in (
+            &entities,
+            &transforms,
+            &colliders,
+            &self.modified_colliders,
+            !&self.pending_colliders,
+        )
+            .join()
+        {
+            if let Some(old_collider_handle) = physics.ent_collider_handles.remove(&ent.id()) {
+                physics.colliders.remove(old_collider_handle);
+
+                let collider = build_collider(&physics, ent, transform, collider);
+                let collider_handle = physics.colliders.insert(collider);
+                physics
+                    .ent_collider_handles
+                    .insert(ent.id(), collider_handle);
+                debug!(
+                    target: "physics",
+                    "[ColliderSendPhysicsSystem] Rebuilt modified collider. Entity Id = {}, Handle = {:?}",
+                    ent.id(),
+                    collider_handle
                 );
             } else {
-                eprintln!("[ColliderSendPhysicsSystem] Failed to update collider because it didn't exist! Entity Id = {}", ent.id());
+                error!(target: "physics", "[ColliderSendPhysicsSystem] Failed to update collider because it didn't exist! Entity Id = {}", ent.id());
             }
         }
 
@@ -438,10 +723,10 @@ impl<'a> System<'a> for ColliderSendPhysicsSystem {
                 let phys_collider = physics.colliders.get_mut(collider_handle).unwrap();
                 phys_collider.set_position(Isometry2::new(
                     (transform.position + collider.offset) * WORLD_UNIT_RATIO,
-                    0.0,
+                    transform.rotation as f64,
                 ));
             } else {
-                eprintln!("[RigidbodySendPhysicsSystem] Failed to update rigidbody because it didn't exist! Entity Id = {}", ent.id());
+                error!(target: "physics", "[RigidbodySendPhysicsSystem] Failed to update rigidbody because it didn't exist! Entity Id = {}", ent.id());
             }
         }
     }
@@ -455,90 +740,188 @@ impl<'a> System<'a> for ColliderSendPhysicsSystem {
     }
 }
 
+// Builds the physics collider for an entity.
+// If this entity has a rigidbody, the collider is attached to it. Otherwise it's just attached to the "ground".
+fn build_collider(
+    physics: &PhysicsState,
+    ent: Entity,
+    transform: &TransformComponent,
+    collider: &ColliderComponent,
+) -> Collider<f64, DefaultBodyHandle> {
+    let (parent_body_handle, translation, rotation) =
+        if let Some(rb_handle) = physics.ent_body_handles.get(&ent.id()) {
+            (rb_handle.clone(), collider.offset, 0.0)
+        } else {
+            (
+                physics.ground_body_handle.clone(),
+                (transform.position + collider.offset) * WORLD_UNIT_RATIO,
+                transform.rotation as f64,
+            )
+        };
+
+    ColliderDesc::new(collider.shape.clone())
+        .density(collider.density)
+        .translation(translation)
+        .rotation(rotation)
+        .margin(0.02)
+        .ccd_enabled(collider.ccd_enabled)
+        .material(MaterialHandle::new(BasicMaterial::new(
+            collider.material.restitution,
+            collider.material.friction,
+        )))
+        .sensor(collider.sensor)
+        .collision_groups(collider.collision_groups.clone())
+        .user_data(ent)
+        .build(BodyPartHandle(parent_body_handle, 0))
+}
+
+// Turns one step's contact events into CollisionEvents, one for each collider of every pair that started touching.
+// A pair can report more than one started contact in a single step, in either order.
+// Only the first one is turned into collision events, so nothing gets handled twice.
+fn started_collision_events<'e>(
+    geometrical_world: &DefaultGeometricalWorld<f64>,
+    colliders: &DefaultColliderSet<f64>,
+    contact_events: impl IntoIterator<Item = &'e ContactEvent<DefaultColliderHandle>>,
+) -> Vec<CollisionEvent> {
+    let mut started_pairs: HashSet<(DefaultColliderHandle, DefaultColliderHandle)> = HashSet::new();
+    let mut collision_events = Vec::new();
+
+    for event in contact_events {
+        match event {
+            ContactEvent::Started(handle1, handle2) => {
+                if started_pairs.contains(&(*handle1, *handle2))
+                    || started_pairs.contains(&(*handle2, *handle1))
+                {
+                    continue;
+                }
+
+                started_pairs.insert((*handle1, *handle2));
+
+                //println!("contact started: handle1: {:?}, handle2: {:?}", handle1, handle2);
+                if let Some((handle_a, collider_a, handle_b, collider_b, _, manifold)) =
+                    geometrical_world.contact_pair(colliders, *handle1, *handle2, false)
+                {
+                    let entity_a = collider_a
+                        .user_data()
+                        .unwrap()
+                        .downcast_ref::<Entity>()
+                        .cloned();
+                    let entity_b = collider_b
+                        .user_data()
+                        .unwrap()
+                        .downcast_ref::<Entity>()
+                        .cloned();
+
+                    let (normal, collision_a_point, collision_b_point) = if let Some(c) =
+                        manifold.deepest_contact().cloned()
+                    {
+                        let collision_a_point = c.contact.world1 * (PIXELS_PER_WORLD_UNIT as f64);
+                        let collision_b_point = c.contact.world2 * (PIXELS_PER_WORLD_UNIT as f64);
+                        (
+                            Some(c.contact.normal.into_inner()),
+                            Some(collision_a_point),
+                            Some(collision_b_point),
+                        )
+                    } else {
+                        (None, None, None)
+                    };
+
+                    collision_events.push(CollisionEvent {
+                        entity_a,
+                        collider_handle_a: handle_a,
+                        entity_b,
+                        collider_handle_b: handle_b,
+                        normal,
+                        collision_point: collision_a_point,
+                        ty: CollisionType::Started,
+                    });
+
+                    // The manifold normal points from the first collider to the second, so it's flipped
+                    // for the second collider's event
+                    collision_events.push(CollisionEvent {
+                        entity_a: entity_b,
+                        collider_handle_a: handle_b,
+                        entity_b: entity_a,
+                        collider_handle_b: handle_a,
+                        normal: normal.map(|normal| -normal),
+                        collision_point: collision_b_point,
+                        ty: CollisionType::Started,
+                    });
+                } else {
+                    warn!(target: "physics", "No contact pair found for collision!");
+                }
+            }
+            ContactEvent::Stopped(handle1, handle2) => {
+                //println!("contact stopped: handle1: {:?}, handle2: {:?}", handle1, handle2);
+                // TODO
+            }
+        }
+    }
+
+    collision_events
+}
+
 #[derive(Default)]
 pub struct WorldStepPhysicsSystem;
 
 impl<'a> System<'a> for WorldStepPhysicsSystem {
     type SystemData = (
         WriteExpect<'a, PhysicsState>,
+        Read<'a, TimeScale>,
         WriteExpect<'a, EventChannel<CollisionEvent>>,
+        Write<'a, EventChannel<ProximityEvent>>,
     );
 
-    fn run(&mut self, (mut physics, mut collision_events): Self::SystemData) {
-        physics.step();
+    fn run(
+        &mut self,
+        (mut physics, time_scale, mut collision_events, mut proximity_events): Self::SystemData,
+    ) {
+        physics.step(time_scale.get());
         //println!("step");
 
-        for event in physics.geometrical_world.contact_events() {
-            let new_collision_events = match event {
-                ContactEvent::Started(handle1, handle2) => {
-                    //println!("contact started: handle1: {:?}, handle2: {:?}", handle1, handle2);
-                    if let Some((handle_a, collider_a, handle_b, collider_b, _, manifold)) = physics
-                        .geometrical_world
-                        .contact_pair(&physics.colliders, *handle1, *handle2, false)
-                    {
-                        let entity_a = collider_a
-                            .user_data()
-                            .unwrap()
-                            .downcast_ref::<Entity>()
-                            .cloned();
-                        let entity_b = collider_b
-                            .user_data()
-                            .unwrap()
-                            .downcast_ref::<Entity>()
-                            .cloned();
-
-                        let (normal, collision_a_point, collision_b_point) =
-                            if let Some(c) = manifold.deepest_contact().cloned() {
-                                let collision_a_point =
-                                    c.contact.world1 * (PIXELS_PER_WORLD_UNIT as f64);
-                                let collision_b_point =
-                                    c.contact.world2 * (PIXELS_PER_WORLD_UNIT as f64);
-                                (
-                                    Some(c.contact.normal.into_inner()),
-                                    Some(collision_a_point),
-                                    Some(collision_b_point),
-                                )
-                            } else {
-                                (None, None, None)
-                            };
-
-                        let event_a = CollisionEvent {
-                            entity_a,
-                            collider_handle_a: handle_a,
-                            entity_b,
-                            collider_handle_b: handle_b,
-                            normal,
-                            collision_point: collision_a_point,
-                            ty: CollisionType::Started,
-                        };
-
-                        let event_b = CollisionEvent {
-                            entity_a: entity_b,
-                            collider_handle_a: handle_b,
-                            entity_b: entity_a,
-                            collider_handle_b: handle_a,
-                            normal,
-                            collision_point: collision_b_point,
-                            ty: CollisionType::Started,
-                        };
-
-                        Some(vec![event_a, event_b])
-                    } else {
-                        eprintln!("No contact pair found for collision!");
+        let new_collision_events = started_collision_events(
+            &physics.geometrical_world,
+            &physics.colliders,
+            physics.geometrical_world.contact_events(),
+        );
+        collision_events.iter_write(new_collision_events);
 
-                        None
-                    }
-                }
-                ContactEvent::Stopped(handle1, handle2) => {
-                    //println!("contact stopped: handle1: {:?}, handle2: {:?}", handle1, handle2);
-                    // TODO
-                    None
-                }
+        for event in physics.geometrical_world.proximity_events() {
+            let ty = match (event.prev_status, event.new_status) {
+                (Proximity::Intersecting, Proximity::Intersecting) => continue,
+                (_, Proximity::Intersecting) => ProximityType::Entered,
+                (Proximity::Intersecting, _) => ProximityType::Exited,
+                _ => continue,
             };
 
-            if let Some(events) = new_collision_events {
-                collision_events.iter_write(events);
-            }
+            let collider_entity = |handle: DefaultColliderHandle| {
+                physics
+                    .colliders
+                    .get(handle)
+                    .and_then(|collider| collider.user_data())
+                    .and_then(|data| data.downcast_ref::<Entity>())
+                    .cloned()
+            };
+            let entity_a = collider_entity(event.collider1);
+            let entity_b = collider_entity(event.collider2);
+
+            let event_a = ProximityEvent {
+                entity_a,
+                collider_handle_a: event.collider1,
+                entity_b,
+                collider_handle_b: event.collider2,
+                ty,
+            };
+
+            let event_b = ProximityEvent {
+                entity_a: entity_b,
+                collider_handle_a: event.collider2,
+                entity_b: entity_a,
+                collider_handle_b: event.collider1,
+                ty,
+            };
+
+            proximity_events.iter_write(vec![event_a, event_b]);
         }
     }
 }
@@ -560,8 +943,738 @@ impl<'a> System<'a> for RigidbodyReceivePhysicsSystem {
 
                 transform.position =
                     body.position().translation.vector * PIXELS_PER_WORLD_UNIT as f64;
+                transform.rotation = body.position().rotation.angle() as f32;
                 rigidbody.velocity = body.velocity().clone();
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        assets::Assets,
+        brick::{BrickComponent, BRICK_DEFAULT_POINTS},
+        laser,
+        level::WallComponent,
+        testing::{self, DT},
+    };
+    use ncollide2d::shape::{Ball, Cuboid};
+
+    // Looks at the collider the physics world has for the given entity
+    fn with_physics_collider<R>(
+        world: &World,
+        ent: Entity,
+        f: impl FnOnce(&Collider<f64, DefaultBodyHandle>) -> R,
+    ) -> R {
+        let physics = world.read_resource::<PhysicsState>();
+        let handle = physics.ent_collider_handles[&ent.id()];
+        f(physics.colliders.get(handle).unwrap())
+    }
+
+    // A body with a collider that collides with everything
+    fn spawn_body<S: Shape<f64>>(
+        world: &mut World,
+        position: Vector2d,
+        shape: S,
+        linear_velocity: Vector2<f64>,
+        status: BodyStatus,
+    ) -> Entity {
+        world
+            .create_entity()
+            .with(TransformComponent::new(
+                position,
+                Point2f::origin(),
+                Vector2f::new(1.0, 1.0),
+            ))
+            .with(RigidbodyComponent::new(1.0, linear_velocity, 100.0, status))
+            .with(ColliderComponent::new(
+                shape,
+                Vector2::zeros(),
+                CollisionGroups::new(),
+                1.0,
+            ))
+            .build()
+    }
+
+    #[test]
+    fn zero_gravity_keeps_bodies_moving_in_a_straight_line() {
+        let mut physics = PhysicsState::new();
+        physics.set_timestep(1.0 / 60.0);
+        let handle = physics.bodies.insert(
+            RigidBodyDesc::new()
+                .mass(1.0)
+                .velocity(Velocity::linear(2.0, 0.0))
+                .build(),
+        );
+
+        for _ in 0..10 {
+            physics.step(1.0);
+        }
+
+        let body = physics.bodies.rigid_body(handle).unwrap();
+        assert_eq!(body.velocity().linear.y, 0.0);
+        assert_eq!(body.position().translation.vector.y, 0.0);
+        assert!(body.position().translation.vector.x > 0.0);
+    }
+
+    #[test]
+    fn changed_collider_shape_is_sent_to_the_physics_world() {
+        let (mut game, _) = testing::empty_game();
+        let brick_ent = testing::spawn_brick(
+            &mut game.world,
+            Vector2d::new(100.0, 60.0),
+            BrickComponent::new(1, BRICK_DEFAULT_POINTS),
+        );
+        game.tick(DT);
+
+        let half_extents = Vector2::new(1.0, 0.25);
+        game.world
+            .write_storage::<ColliderComponent>()
+            .get_mut(brick_ent)
+            .unwrap()
+            .shape = ShapeHandle::new(Cuboid::new(half_extents));
+        game.tick(DT);
+
+        let physics_half_extents = with_physics_collider(&game.world, brick_ent, |collider| {
+            *collider
+                .shape()
+                .as_shape::<Cuboid<f64>>()
+                .unwrap()
+                .half_extents()
+        });
+        assert_eq!(physics_half_extents, half_extents);
+    }
+
+    #[test]
+    fn rotation_round_trips_through_the_physics_world() {
+        let (mut game, _) = testing::empty_game();
+
+        let mut transform = TransformComponent::new(
+            Vector2d::new(160.0, 120.0),
+            Point2f::origin(),
+            Vector2f::new(1.0, 1.0),
+        );
+        transform.rotation = 0.5;
+        // Moving a little as well, so the body doesn't fall asleep
+        let mut rigidbody =
+            RigidbodyComponent::new(1.0, Vector2::new(1.0, 0.0), 10.0, BodyStatus::Dynamic);
+        rigidbody.velocity.angular = 2.0;
+        let ent = game
+            .world
+            .create_entity()
+            .with(transform)
+            .with(rigidbody)
+            .build();
+
+        for _ in 0..30 {
+            game.tick(DT);
+        }
+
+        let transforms = game.world.read_storage::<TransformComponent>();
+        let rotation = transforms.get(ent).unwrap().rotation;
+        assert!((rotation - 1.5).abs() < 1e-3);
+    }
+
+    // Adds a body to the physics world directly, with a collider that collides with everything.
+    // The collider's user data is a new entity, like the colliders ColliderSendPhysicsSystem makes.
+    fn insert_collider<S: Shape<f64>>(
+        physics: &mut PhysicsState,
+        world: &mut World,
+        position: Vector2<f64>,
+        shape: S,
+        status: BodyStatus,
+    ) -> (Entity, DefaultColliderHandle) {
+        let ent = world.create_entity().build();
+        let body_handle = physics.bodies.insert(
+            RigidBodyDesc::new()
+                .position(Isometry2::translation(position.x, position.y))
+                .status(status)
+                .mass(1.0)
+                .build(),
+        );
+        let collider_handle = physics.colliders.insert(
+            ColliderDesc::new(ShapeHandle::new(shape))
+                .user_data(ent)
+                .build(BodyPartHandle(body_handle, 0)),
+        );
+
+        (ent, collider_handle)
+    }
+
+    #[test]
+    fn duplicate_started_contacts_send_one_event_pair_for_the_deepest_contact() {
+        let mut world = World::new();
+        let mut physics = PhysicsState::new();
+        physics.set_timestep(1.0 / 60.0);
+
+        // Two boxes of the same width meeting face to face touch at more than one point
+        let (floor_ent, floor_handle) = insert_collider(
+            &mut physics,
+            &mut world,
+            Vector2::new(0.0, 1.0),
+            Cuboid::new(Vector2::new(1.0, 0.25)),
+            BodyStatus::Static,
+        );
+        let (slab_ent, slab_handle) = insert_collider(
+            &mut physics,
+            &mut world,
+            Vector2::new(0.0, 0.51),
+            Cuboid::new(Vector2::new(1.0, 0.25)),
+            BodyStatus::Dynamic,
+        );
+        physics.step(1.0);
+
+        let contact_events = [
+            ContactEvent::Started(floor_handle, slab_handle),
+            ContactEvent::Started(slab_handle, floor_handle),
+        ];
+        let collision_events = started_collision_events(
+            &physics.geometrical_world,
+            &physics.colliders,
+            contact_events.iter(),
+        );
+        assert_eq!(collision_events.len(), 2);
+
+        let (handle_a, _, _, _, _, manifold) = physics
+            .geometrical_world
+            .contact_pair(&physics.colliders, floor_handle, slab_handle, false)
+            .unwrap();
+        assert!(manifold.len() > 1);
+        let deepest = manifold.deepest_contact().unwrap().contact;
+        for event in &collision_events {
+            let (normal, point) = if event.collider_handle_a == handle_a {
+                (deepest.normal.into_inner(), deepest.world1)
+            } else {
+                (-deepest.normal.into_inner(), deepest.world2)
+            };
+            assert_eq!(event.normal, Some(normal));
+            assert_eq!(
+                event.collision_point,
+                Some(point * (PIXELS_PER_WORLD_UNIT as f64))
+            );
+        }
+
+        let mut pairs: Vec<(Option<Entity>, Option<Entity>)> = collision_events
+            .iter()
+            .map(|event| (event.entity_a, event.entity_b))
+            .collect();
+        pairs.sort();
+        let mut expected = vec![
+            (Some(floor_ent), Some(slab_ent)),
+            (Some(slab_ent), Some(floor_ent)),
+        ];
+        expected.sort();
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn contact_point_is_where_the_ball_touches() {
+        let (mut game, _) = testing::empty_game();
+        let mut reader = game
+            .world
+            .write_resource::<EventChannel<CollisionEvent>>()
+            .register_reader();
+
+        // The top of the floor is at y = 112
+        let floor_ent = spawn_body(
+            &mut game.world,
+            Vector2d::new(160.0, 120.0),
+            Cuboid::new(Vector2::new(1.0, 0.25)),
+            Vector2::zeros(),
+            BodyStatus::Static,
+        );
+        // Slow enough that it barely sinks in before the contact is found
+        let ball_ent = spawn_body(
+            &mut game.world,
+            Vector2d::new(160.0, 100.0),
+            Ball::new(0.25),
+            Vector2::new(0.0, 1.0),
+            BodyStatus::Dynamic,
+        );
+
+        let mut point = None;
+        for _ in 0..60 {
+            game.tick(DT);
+            point = game
+                .world
+                .read_resource::<EventChannel<CollisionEvent>>()
+                .read(&mut reader)
+                .find(|event| event.entity_a == Some(ball_ent) && event.entity_b == Some(floor_ent))
+                .and_then(|event| event.collision_point);
+            if point.is_some() {
+                break;
+            }
+        }
+
+        let point = point.expect("ball never hit the floor");
+        assert!((point.x - 160.0).abs() < 1.0);
+        assert!((point.y - 112.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn sensor_reports_overlaps_without_pushing_back() {
+        let (mut game, _) = testing::empty_game();
+        let mut reader = game
+            .world
+            .write_resource::<EventChannel<ProximityEvent>>()
+            .register_reader();
+
+        let sensor_ent = spawn_body(
+            &mut game.world,
+            Vector2d::new(160.0, 120.0),
+            Cuboid::new(Vector2::new(1.0, 1.0)),
+            Vector2::zeros(),
+            BodyStatus::Static,
+        );
+        game.world
+            .write_storage::<ColliderComponent>()
+            .get_mut(sensor_ent)
+            .unwrap()
+            .sensor = true;
+        let velocity = Vector2::new(0.0, 5.0);
+        let body_ent = spawn_body(
+            &mut game.world,
+            Vector2d::new(160.0, 60.0),
+            Ball::new(0.25),
+            velocity,
+            BodyStatus::Dynamic,
+        );
+
+        let mut entered = false;
+        for _ in 0..60 {
+            game.tick(DT);
+            entered = game
+                .world
+                .read_resource::<EventChannel<ProximityEvent>>()
+                .read(&mut reader)
+                .any(|event| {
+                    event.entity_a == Some(body_ent)
+                        && event.entity_b == Some(sensor_ent)
+                        && event.ty == ProximityType::Entered
+                });
+            if entered {
+                break;
+            }
+        }
+        assert!(entered, "body never entered the sensor");
+
+        game.tick(DT);
+        let rigidbodies = game.world.read_storage::<RigidbodyComponent>();
+        assert_eq!(rigidbodies.get(body_ent).unwrap().velocity.linear, velocity);
+    }
+
+    #[test]
+    fn deleted_bricks_give_back_their_colliders() {
+        let (mut game, _) = testing::empty_game();
+        game.tick(DT);
+        let baseline = game.world.read_resource::<PhysicsState>().collider_count();
+
+        let mut bricks = Vec::new();
+        for row in 0..5 {
+            for column in 0..10 {
+                bricks.push(testing::spawn_brick(
+                    &mut game.world,
+                    Vector2d::new(column as f64 * 32.0, 80.0 + row as f64 * 16.0),
+                    BrickComponent::new(1, BRICK_DEFAULT_POINTS),
+                ));
+            }
+        }
+        game.tick(DT);
+        game.tick(DT);
+        assert_eq!(
+            game.world.read_resource::<PhysicsState>().collider_count(),
+            baseline + 50
+        );
+
+        for chunk in bricks.chunks(10) {
+            game.world.delete_entities(chunk).unwrap();
+            game.tick(DT);
+        }
+        game.tick(DT);
+
+        assert_eq!(
+            game.world.read_resource::<PhysicsState>().collider_count(),
+            baseline
+        );
+    }
+
+    #[test]
+    fn crowd_of_balls_sends_at_most_one_event_per_pair() {
+        let mut world = World::new();
+        let mut physics = PhysicsState::new();
+        physics.set_timestep(1.0 / 60.0);
+
+        // A hundred balls, each overlapping its neighbours
+        for row in 0..10 {
+            for column in 0..10 {
+                insert_collider(
+                    &mut physics,
+                    &mut world,
+                    Vector2::new(column as f64 * 0.4, row as f64 * 0.4),
+                    Ball::new(0.25),
+                    BodyStatus::Dynamic,
+                );
+            }
+        }
+        physics.step(1.0);
+
+        // Every started contact is reported twice, once in each order
+        let contact_events: Vec<ContactEvent<DefaultColliderHandle>> = physics
+            .geometrical_world
+            .contact_events()
+            .iter()
+            .flat_map(|event| match *event {
+                ContactEvent::Started(handle1, handle2) => vec![
+                    ContactEvent::Started(handle1, handle2),
+                    ContactEvent::Started(handle2, handle1),
+                ],
+                ContactEvent::Stopped(..) => vec![],
+            })
+            .collect();
+
+        let collision_events = started_collision_events(
+            &physics.geometrical_world,
+            &physics.colliders,
+            contact_events.iter(),
+        );
+
+        let mut pairs = HashSet::new();
+        for event in &collision_events {
+            assert!(pairs.insert((event.entity_a, event.entity_b)));
+        }
+        // Both events for each of the 180 pairs of neighbours
+        assert_eq!(pairs.len(), 2 * 180);
+    }
+
+    // How far a free body moves in half a second of ticks, at the given time scale
+    fn distance_travelled(time_scale: f64) -> f64 {
+        let (mut game, _) = testing::empty_game();
+        game.world.insert(TimeScale::new(time_scale));
+        let ent = spawn_body(
+            &mut game.world,
+            Vector2d::new(100.0, 120.0),
+            Ball::new(0.25),
+            Vector2::new(2.0, 0.0),
+            BodyStatus::Dynamic,
+        );
+        game.tick(DT);
+        let start = testing::position(&game.world, ent);
+
+        game.run_headless(30, DT);
+
+        (testing::position(&game.world, ent) - start).norm()
+    }
+
+    #[test]
+    fn half_time_scale_moves_half_as_far() {
+        let distance = distance_travelled(1.0);
+        assert!((distance - 32.0).abs() < 1e-6);
+        assert!((distance_travelled(0.5) - distance / 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn collider_built_with_its_body_attaches_to_that_body() {
+        let (mut game, _) = testing::empty_game();
+        let ent = spawn_body(
+            &mut game.world,
+            Vector2d::new(100.0, 120.0),
+            Ball::new(0.25),
+            Vector2::zeros(),
+            BodyStatus::Dynamic,
+        );
+        game.tick(DT);
+
+        let collider_body = with_physics_collider(&game.world, ent, |collider| collider.body());
+        let physics = game.world.read_resource::<PhysicsState>();
+        assert_eq!(collider_body, physics.ent_body_handles[&ent.id()]);
+        assert_ne!(collider_body, physics.ground_body_handle);
+    }
+
+    #[test]
+    fn ball_hitting_a_corner_gets_a_diagonal_normal() {
+        let (mut game, _) = testing::empty_game();
+        let mut reader = game
+            .world
+            .write_resource::<EventChannel<CollisionEvent>>()
+            .register_reader();
+
+        // The top left corner of the slab is at (128, 112)
+        let slab_ent = spawn_body(
+            &mut game.world,
+            Vector2d::new(160.0, 120.0),
+            Cuboid::new(Vector2::new(1.0, 0.25)),
+            Vector2::zeros(),
+            BodyStatus::Static,
+        );
+        // Heading straight for the corner, from up and to the left
+        let ball_ent = spawn_body(
+            &mut game.world,
+            Vector2d::new(108.0, 92.0),
+            Ball::new(0.25),
+            Vector2::new(1.0, 1.0),
+            BodyStatus::Dynamic,
+        );
+
+        let mut normal = None;
+        for _ in 0..120 {
+            game.tick(DT);
+            normal = game
+                .world
+                .read_resource::<EventChannel<CollisionEvent>>()
+                .read(&mut reader)
+                .find(|event| event.entity_a == Some(ball_ent) && event.entity_b == Some(slab_ent))
+                .and_then(|event| event.normal);
+            if normal.is_some() {
+                break;
+            }
+        }
+
+        let normal = normal.expect("ball never hit the corner");
+        assert!(normal.x.abs() > 0.5 && normal.y.abs() > 0.5);
+        assert!(normal.x.signum() == normal.y.signum());
+    }
+
+    #[test]
+    fn configure_sets_the_integration_parameters() {
+        let mut physics = PhysicsState::new();
+        physics.configure(PhysicsConfig {
+            max_ccd_position_iterations: 4,
+            max_ccd_substeps: 2,
+            max_velocity_iterations: 3,
+            max_position_iterations: 1,
+            warmstart_coeff: 0.5,
+        });
+
+        let params = &physics.mechanical_world.integration_parameters;
+        assert_eq!(params.max_ccd_position_iterations, 4);
+        assert_eq!(params.max_ccd_substeps, 2);
+        assert_eq!(params.max_velocity_iterations, 3);
+        assert_eq!(params.max_position_iterations, 1);
+        assert_eq!(params.warmstart_coeff, 0.5);
+    }
+
+    #[test]
+    fn duplicate_rigidbody_is_logged_as_a_physics_warning() {
+        let (mut game, _) = testing::empty_game();
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(160.0, 120.0),
+            Vector2d::new(0.0, -4.0),
+        );
+        game.tick(DT);
+        game.tick(DT);
+        let ball_ent = testing::balls(&game.world)[0];
+        testing::capture_logs();
+
+        // Make it look like the ball's rigidbody was inserted again, while its body is still in the physics world
+        game.world
+            .write_storage::<RigidbodyComponent>()
+            .channel_mut()
+            .single_write(ComponentEvent::Inserted(ball_ent.id()));
+        game.tick(DT);
+
+        let logs = testing::captured_logs();
+        assert!(logs.iter().any(|log| log.level == log::Level::Warn
+            && log.target == "physics"
+            && log.message.contains("Duplicate rigidbody")));
+    }
+
+    fn physics_counts(world: &World) -> (usize, usize, usize) {
+        let physics = world.read_resource::<PhysicsState>();
+
+        (
+            physics.body_count(),
+            physics.collider_count(),
+            physics.tracked_entity_count(),
+        )
+    }
+
+    #[test]
+    fn deleting_a_brick_returns_the_counts_to_baseline() {
+        let (mut game, _) = testing::empty_game();
+        game.tick(DT);
+        let baseline = physics_counts(&game.world);
+
+        let brick_ent = testing::spawn_brick(
+            &mut game.world,
+            Vector2d::new(64.0, 80.0),
+            BrickComponent::new(1, BRICK_DEFAULT_POINTS),
+        );
+        game.tick(DT);
+        game.tick(DT);
+        let (bodies, colliders, tracked) = physics_counts(&game.world);
+        assert_eq!(bodies, baseline.0);
+        assert_eq!(colliders, baseline.1 + 1);
+        assert_eq!(tracked, baseline.2 + 1);
+
+        game.world.delete_entity(brick_ent).unwrap();
+        game.tick(DT);
+        game.tick(DT);
+
+        assert_eq!(physics_counts(&game.world), baseline);
+    }
+
+    // Drops a ball onto a slab, spawning either one first so their colliders are listed in either order.
+    // Returns the normals of the ball's event and the slab's event, from the tick they first touch.
+    fn ball_and_slab_normals(ball_first: bool) -> (Vector2<f64>, Vector2<f64>) {
+        let (mut game, _) = testing::empty_game();
+        let mut reader = game
+            .world
+            .write_resource::<EventChannel<CollisionEvent>>()
+            .register_reader();
+
+        let spawn_ball = |world: &mut World| {
+            spawn_body(
+                world,
+                Vector2d::new(160.0, 90.0),
+                Ball::new(0.25),
+                Vector2::new(0.0, 2.0),
+                BodyStatus::Dynamic,
+            )
+        };
+        let spawn_slab = |world: &mut World| {
+            spawn_body(
+                world,
+                Vector2d::new(160.0, 120.0),
+                Cuboid::new(Vector2::new(1.0, 0.25)),
+                Vector2::zeros(),
+                BodyStatus::Static,
+            )
+        };
+        let (ball_ent, slab_ent) = if ball_first {
+            let ball_ent = spawn_ball(&mut game.world);
+            (ball_ent, spawn_slab(&mut game.world))
+        } else {
+            let slab_ent = spawn_slab(&mut game.world);
+            (spawn_ball(&mut game.world), slab_ent)
+        };
+
+        for _ in 0..120 {
+            game.tick(DT);
+            let normals: Vec<(Option<Entity>, Option<Entity>, Vector2<f64>)> = game
+                .world
+                .read_resource::<EventChannel<CollisionEvent>>()
+                .read(&mut reader)
+                .filter_map(|event| {
+                    event
+                        .normal
+                        .map(|normal| (event.entity_a, event.entity_b, normal))
+                })
+                .collect();
+            let normal_between = |a: Entity, b: Entity| {
+                normals
+                    .iter()
+                    .find(|(entity_a, entity_b, _)| *entity_a == Some(a) && *entity_b == Some(b))
+                    .map(|(_, _, normal)| *normal)
+            };
+            if let (Some(ball_normal), Some(slab_normal)) = (
+                normal_between(ball_ent, slab_ent),
+                normal_between(slab_ent, ball_ent),
+            ) {
+                return (ball_normal, slab_normal);
+            }
+        }
+
+        panic!("ball never hit the slab");
+    }
+
+    #[test]
+    fn normals_point_from_entity_a_to_entity_b_whichever_collider_is_first() {
+        for &ball_first in &[true, false] {
+            let (ball_normal, slab_normal) = ball_and_slab_normals(ball_first);
+
+            // The slab is below the ball
+            assert!((ball_normal - Vector2::new(0.0, 1.0)).norm() < 1e-6);
+            assert_eq!(slab_normal, -ball_normal);
+        }
+    }
+
+    fn spawn_collider(world: &mut World, position: Vector2d, ccd_enabled: bool) -> Entity {
+        world
+            .create_entity()
+            .with(TransformComponent::new(
+                position,
+                Point2f::origin(),
+                Vector2f::new(1.0, 1.0),
+            ))
+            .with(
+                ColliderComponent::new(
+                    Ball::new(0.25),
+                    Vector2::zeros(),
+                    CollisionGroups::new(),
+                    1.0,
+                )
+                .with_ccd(ccd_enabled),
+            )
+            .build()
+    }
+
+    #[test]
+    fn colliders_only_get_ccd_when_asked_for() {
+        let (mut game, _) = testing::empty_game();
+        let without_ccd = spawn_collider(&mut game.world, Vector2d::new(80.0, 120.0), false);
+        let with_ccd = spawn_collider(&mut game.world, Vector2d::new(240.0, 120.0), true);
+        game.tick(DT);
+
+        assert!(!with_physics_collider(
+            &game.world,
+            without_ccd,
+            |collider| collider.is_ccd_enabled()
+        ));
+        assert!(with_physics_collider(&game.world, with_ccd, |collider| {
+            collider.is_ccd_enabled()
+        }));
+    }
+
+    #[test]
+    fn balls_and_projectiles_get_ccd_but_the_paddle_does_not() {
+        let (mut game, paddle_ent) = testing::empty_game();
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(160.0, 150.0),
+            Vector2d::new(0.0, -4.0),
+        );
+        let projectile_ent = {
+            let ents = game.world.entities();
+            let lazy_updater = game.world.read_resource::<LazyUpdate>();
+            let assets = game.world.read_resource::<Assets>();
+            laser::spawn_projectile(&ents, &lazy_updater, &assets, Vector2d::new(80.0, 150.0))
+        };
+        game.tick(DT);
+        game.tick(DT);
+        let ball_ent = testing::balls(&game.world)[0];
+
+        let has_ccd =
+            |ent| with_physics_collider(&game.world, ent, |collider| collider.is_ccd_enabled());
+        assert!(has_ccd(ball_ent));
+        assert!(has_ccd(projectile_ent));
+        assert!(!has_ccd(paddle_ent));
+    }
+
+    #[test]
+    fn walls_get_the_wall_material() {
+        let (mut game, _) = testing::empty_game();
+        game.tick(DT);
+
+        let wall_ents: Vec<Entity> = {
+            let ents = game.world.entities();
+            let walls = game.world.read_storage::<WallComponent>();
+            (&ents, &walls).join().map(|(ent, _)| ent).collect()
+        };
+        assert_eq!(wall_ents.len(), 4);
+        for wall_ent in wall_ents {
+            let (restitution, friction) =
+                with_physics_collider(&game.world, wall_ent, |collider| {
+                    let material = collider
+                        .material()
+                        .downcast_ref::<BasicMaterial<f64>>()
+                        .unwrap();
+                    (material.restitution, material.friction)
+                });
+            assert_eq!(restitution, 1.0);
+            assert_eq!(friction, 0.0);
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use crate::game::{
+    camera::Camera,
+    render::{RenderState, HUD_LAYER, HUD_TEXT_SCALE},
+    Vector2d,
+};
+use gfx::{color::*, font::Font, renderer::Transparency};
+use specs::prelude::*;
+
+pub const SCORE_POPUP_LIFETIME_TICKS: u32 = 40;
+// Pixels per tick. Popups float upwards.
+pub const SCORE_POPUP_RISE_SPEED: f64 = 0.5;
+// Above the gameplay sprites, but under the HUD
+pub const SCORE_POPUP_LAYER: u8 = HUD_LAYER - 2;
+
+// Floating "+100" text that rises from where points were scored, and fades out
+pub struct ScorePopupComponent {
+    pub position: Vector2d,
+    pub velocity: Vector2d,
+    pub value: u32,
+    pub lifetime_ticks: u32,
+    pub ticks_remaining: u32,
+}
+
+impl ScorePopupComponent {
+    pub fn new(position: Vector2d, value: u32) -> Self {
+        ScorePopupComponent {
+            position,
+            velocity: Vector2d::new(0.0, -SCORE_POPUP_RISE_SPEED),
+            value,
+            lifetime_ticks: SCORE_POPUP_LIFETIME_TICKS,
+            ticks_remaining: SCORE_POPUP_LIFETIME_TICKS,
+        }
+    }
+
+    // Goes from 1.0 when the popup spawns, down to 0.0 when it's about to despawn
+    pub fn alpha(&self) -> f32 {
+        if self.lifetime_ticks == 0 {
+            return 0.0;
+        }
+
+        self.ticks_remaining as f32 / self.lifetime_ticks as f32
+    }
+}
+
+impl Component for ScorePopupComponent {
+    type Storage = VecStorage<Self>;
+}
+
+pub fn spawn_score_popup(
+    ents: &Entities,
+    lazy_updater: &LazyUpdate,
+    position: Vector2d,
+    value: u32,
+) -> Entity {
+    let ent = ents.create();
+    lazy_updater.insert(ent, ScorePopupComponent::new(position, value));
+
+    ent
+}
+
+// Moves popups along, and deletes them once they have faded out
+pub struct ScorePopupSystem;
+
+impl<'a> System<'a> for ScorePopupSystem {
+    type SystemData = (Entities<'a>, WriteStorage<'a, ScorePopupComponent>);
+
+    fn run(&mut self, (ents, mut popups): Self::SystemData) {
+        for (ent, popup) in (&ents, &mut popups).join() {
+            popup.ticks_remaining = popup.ticks_remaining.saturating_sub(1);
+            if popup.ticks_remaining == 0 {
+                ents.delete(ent).unwrap();
+                continue;
+            }
+
+            popup.position += popup.velocity;
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ScorePopupRenderSystem;
+
+impl<'a> System<'a> for ScorePopupRenderSystem {
+    type SystemData = (
+        ReadExpect<'a, Font>,
+        ReadExpect<'a, Camera>,
+        Write<'a, RenderState>,
+        ReadStorage<'a, ScorePopupComponent>,
+    );
+
+    fn run(&mut self, (font, camera, mut render, popups): Self::SystemData) {
+        let scale = HUD_TEXT_SCALE * camera.scale();
+
+        render.bind_layer(SCORE_POPUP_LAYER);
+        render.bind_transparency(Transparency::Transparent);
+        for popup in popups.join() {
+            let text = format!("+{}", popup.value);
+
+            // Centered on the popup position
+            let (x, y) = camera.world_to_screen(popup.position);
+            let width = text.len() as f32 * font.glyph_width as f32 * scale;
+            let height = font.glyph_height as f32 * scale;
+
+            render.bind_color(COLOR_WHITE.with_alpha(popup.alpha()));
+            render.draw_text(&font, x - (width / 2.0), y - (height / 2.0), scale, &text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        brick::BrickComponent,
+        testing::{self, DT},
+        Point2d,
+    };
+
+    fn popups(world: &World) -> Vec<(Entity, Vector2d, u32)> {
+        let ents = world.entities();
+        let popups = world.read_storage::<ScorePopupComponent>();
+        (&ents, &popups)
+            .join()
+            .map(|(ent, popup)| (ent, popup.position, popup.value))
+            .collect()
+    }
+
+    #[test]
+    fn broken_brick_shows_its_points_where_it_was_hit() {
+        let (mut game, _) = testing::empty_game();
+        let brick_ent = testing::spawn_brick(
+            &mut game.world,
+            Vector2d::new(100.0, 60.0),
+            BrickComponent::new(1, 100),
+        );
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(160.0, 150.0),
+            Vector2d::new(0.0, -4.0),
+        );
+        game.tick(DT);
+        let ball_ent = testing::balls(&game.world)[0];
+
+        let contact_point = Vector2d::new(110.0, 76.0);
+        testing::collide(
+            &game.world,
+            brick_ent,
+            ball_ent,
+            Vector2d::new(0.0, 1.0),
+            Point2d::from(contact_point),
+        );
+        game.tick(DT);
+
+        let spawned = popups(&game.world);
+        assert_eq!(spawned.len(), 1);
+        let (popup_ent, position, value) = spawned[0];
+        assert_eq!(position, contact_point);
+        assert_eq!(value, 100);
+
+        game.run_headless(SCORE_POPUP_LIFETIME_TICKS - 1, DT);
+        assert!(game.world.is_alive(popup_ent));
+        game.tick(DT);
+        assert!(!game.world.is_alive(popup_ent));
+    }
+}
@@ -0,0 +1,550 @@
+use crate::game::{
+    assets::Assets,
+    ball::{BallComponent, SpawnBallEvent, BALL_DEFAULT_FORCE, MAX_ACTIVE_BALLS},
+    brick::BrickBrokenEvent,
+    collision,
+    effect::TimedEffectComponent,
+    laser::{LaserPaddleComponent, LASER_EFFECT_TICKS},
+    level::{BottomWallComponent, BottomWallState, LevelConfig, Lives, SAFETY_NET_TICKS},
+    paddle::{
+        MagnetPaddleComponent, PaddleSizeEffectComponent, PlayerPaddleComponent,
+        MAGNET_EFFECT_TICKS, PADDLE_EXPAND_SCALE, PADDLE_SHRINK_SCALE, PADDLE_SIZE_EFFECT_TICKS,
+    },
+    physics::{ColliderComponent, ProximityEvent, ProximityType, RigidbodyComponent},
+    render::SpriteComponent,
+    rng::RngResource,
+    transform::TransformComponent,
+    Point2f, Vector2d, Vector2f, WORLD_UNIT_RATIO,
+};
+use gfx::{color::*, renderer::Transparency, sprite::SpriteRegion};
+use log::{debug, info};
+use nalgebra::Vector2;
+use ncollide2d::shape::Cuboid;
+use nphysics2d::object::BodyStatus;
+use serde::{Deserialize, Serialize};
+use shrev::EventChannel;
+use specs::prelude::*;
+
+pub const POWERUP_DEFAULT_DROP_CHANCE: f32 = 0.15;
+pub const POWERUP_FALL_SPEED: f64 = 2.0;
+pub const POWERUP_HIT_BOX_WIDTH: f64 = 16.0;
+pub const POWERUP_HIT_BOX_HEIGHT: f64 = 8.0;
+pub const POWERUP_MULTIBALL_ANGLE: f64 = std::f64::consts::FRAC_PI_6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PowerUpKind {
+    ExtraLife,
+    MultiBall,
+    ExpandPaddle,
+    ShrinkPaddle,
+    LaserPaddle,
+    // Makes the bottom wall solid for a while, so balls bounce off of it instead of being lost
+    SafetyNet,
+    // Pulls the nearest falling ball towards the paddle for a while
+    Magnet,
+}
+
+impl PowerUpKind {
+    pub fn random(rng: &mut RngResource) -> Self {
+        use rand::Rng;
+        let kinds = [
+            PowerUpKind::ExtraLife,
+            PowerUpKind::MultiBall,
+            PowerUpKind::ExpandPaddle,
+            PowerUpKind::ShrinkPaddle,
+            PowerUpKind::LaserPaddle,
+            PowerUpKind::SafetyNet,
+            PowerUpKind::Magnet,
+        ];
+
+        kinds[rng.rng.gen_range(0, kinds.len())]
+    }
+}
+
+pub struct PowerUpState {
+    // Chance (from 0.0 to 1.0) that a broken brick drops a power-up
+    pub drop_chance: f32,
+}
+
+impl PowerUpState {
+    pub fn new() -> Self {
+        PowerUpState {
+            drop_chance: POWERUP_DEFAULT_DROP_CHANCE,
+        }
+    }
+}
+
+// A falling power-up capsule
+pub struct PowerUpComponent {
+    pub kind: PowerUpKind,
+}
+
+impl PowerUpComponent {
+    pub fn new(kind: PowerUpKind) -> Self {
+        PowerUpComponent { kind }
+    }
+}
+
+impl Component for PowerUpComponent {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Default)]
+pub struct PowerUpSystem {
+    brick_broken_event_reader: Option<ReaderId<BrickBrokenEvent>>,
+    proximity_event_reader: Option<ReaderId<ProximityEvent>>,
+}
+
+impl<'a> System<'a> for PowerUpSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, LazyUpdate>,
+        ReadExpect<'a, PowerUpState>,
+        ReadExpect<'a, LevelConfig>,
+        ReadExpect<'a, Assets>,
+        WriteExpect<'a, Lives>,
+        Write<'a, BottomWallState>,
+        WriteExpect<'a, RngResource>,
+        Read<'a, EventChannel<BrickBrokenEvent>>,
+        Read<'a, EventChannel<ProximityEvent>>,
+        Write<'a, EventChannel<SpawnBallEvent>>,
+        ReadStorage<'a, TransformComponent>,
+        ReadStorage<'a, BallComponent>,
+        ReadStorage<'a, PlayerPaddleComponent>,
+        ReadStorage<'a, BottomWallComponent>,
+        ReadStorage<'a, PowerUpComponent>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.brick_broken_event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<BrickBrokenEvent>>()
+                .register_reader(),
+        );
+        self.proximity_event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<ProximityEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(
+        &mut self,
+        (
+            ents,
+            lazy_updater,
+            powerup_state,
+            config,
+            assets,
+            mut lives,
+            mut bottom_wall,
+            mut rng,
+            brick_broken_events,
+            proximity_events,
+            mut spawn_ball_events,
+            transforms,
+            balls,
+            paddles,
+            bottom_walls,
+            powerups,
+        ): Self::SystemData,
+    ) {
+        let mut removed_powerups: BitSet = BitSet::new();
+        // Along with the paddle that caught each one
+        let mut collected_powerups: Vec<(PowerUpKind, Entity)> = Vec::new();
+
+        // Capsules are sensors, so they pass through whatever they touch rather than bouncing off of it
+        for event in proximity_events.read(&mut self.proximity_event_reader.as_mut().unwrap()) {
+            if event.ty != ProximityType::Entered {
+                continue;
+            }
+
+            let (entity_a, entity_b) = match (event.entity_a, event.entity_b) {
+                (Some(a), Some(b)) => (a, b),
+                _ => continue,
+            };
+
+            if let Some(powerup) = powerups.get(entity_a) {
+                // Capsules can only be collected once, even if they touched more than one thing this tick
+                if removed_powerups.contains(entity_a.id()) {
+                    continue;
+                }
+
+                if paddles.get(entity_b).is_some() {
+                    collected_powerups.push((powerup.kind, entity_b));
+                    ents.delete(entity_a).unwrap();
+                    removed_powerups.add(entity_a.id());
+                } else if bottom_walls.get(entity_b).is_some() {
+                    ents.delete(entity_a).unwrap();
+                    removed_powerups.add(entity_a.id());
+                }
+            }
+        }
+
+        for (kind, paddle_ent) in collected_powerups {
+            info!(target: "powerup", "[PowerUpSystem] Collected {:?} power-up", kind);
+
+            match kind {
+                PowerUpKind::ExtraLife => {
+                    lives.count += 1;
+                }
+                PowerUpKind::MultiBall => {
+                    let events = multiball_spawn_events(&transforms, &balls);
+                    spawn_ball_events.iter_write(events);
+                }
+                PowerUpKind::ExpandPaddle | PowerUpKind::ShrinkPaddle => {
+                    let scale = if kind == PowerUpKind::ExpandPaddle {
+                        PADDLE_EXPAND_SCALE
+                    } else {
+                        PADDLE_SHRINK_SCALE
+                    };
+
+                    // A new size effect replaces whatever one is currently active
+                    lazy_updater.insert(paddle_ent, PaddleSizeEffectComponent::new(scale));
+                    lazy_updater.insert(
+                        paddle_ent,
+                        TimedEffectComponent::<PaddleSizeEffectComponent>::new(
+                            PADDLE_SIZE_EFFECT_TICKS,
+                        ),
+                    );
+                }
+                PowerUpKind::LaserPaddle => {
+                    lazy_updater.insert(paddle_ent, LaserPaddleComponent::new());
+                    lazy_updater.insert(
+                        paddle_ent,
+                        TimedEffectComponent::<LaserPaddleComponent>::new(LASER_EFFECT_TICKS),
+                    );
+                }
+                PowerUpKind::SafetyNet => {
+                    bottom_wall.set_safety_net(SAFETY_NET_TICKS);
+                }
+                PowerUpKind::Magnet => {
+                    lazy_updater.insert(paddle_ent, MagnetPaddleComponent::new());
+                    lazy_updater.insert(
+                        paddle_ent,
+                        TimedEffectComponent::<MagnetPaddleComponent>::new(MAGNET_EFFECT_TICKS),
+                    );
+                }
+            }
+        }
+
+        // Bricks with a power-up in them always drop it. The rest roll for a random drop.
+        // This runs after the BrickSystem, so these are the bricks broken this tick, by anything.
+        for event in brick_broken_events.read(&mut self.brick_broken_event_reader.as_mut().unwrap())
+        {
+            let kind = match event.contains_powerup {
+                Some(kind) => kind,
+                None => {
+                    use rand::Rng;
+                    let roll: f32 = rng.rng.gen();
+                    if roll >= powerup_state.drop_chance {
+                        continue;
+                    }
+
+                    PowerUpKind::random(&mut rng)
+                }
+            };
+
+            spawn_powerup(&ents, &lazy_updater, &assets, kind, event.position);
+        }
+
+        // Clean up any capsules that somehow made it past the bottom wall
+        for (ent, transform, _) in (&ents, &transforms, &powerups).join() {
+            if transform.position.y > config.field_height as f64 + 32.0 {
+                ents.delete(ent).unwrap();
+            }
+        }
+    }
+}
+
+pub fn spawn_powerup(
+    ents: &Entities,
+    lazy_updater: &LazyUpdate,
+    assets: &Assets,
+    kind: PowerUpKind,
+    position: Vector2d,
+) -> Entity {
+    let ent = ents.create();
+
+    lazy_updater.insert(
+        ent,
+        TransformComponent::new(position, Point2f::new(16.0, 8.0), Vector2f::new(0.5, 0.5)),
+    );
+
+    lazy_updater.insert(
+        ent,
+        SpriteComponent {
+            color: COLOR_WHITE,
+            spritesheet_tex_id: assets.texture("sprites"),
+            region: SpriteRegion {
+                x: 96,
+                y: 0,
+                w: 32,
+                h: 16,
+            },
+            layer: 2,
+            transparency: Transparency::Opaque,
+            flip_x: false,
+            flip_y: false,
+        },
+    );
+
+    lazy_updater.insert(ent, PowerUpComponent::new(kind));
+
+    lazy_updater.insert(
+        ent,
+        RigidbodyComponent::new(
+            1.0,
+            Vector2::new(0.0, POWERUP_FALL_SPEED),
+            POWERUP_FALL_SPEED,
+            BodyStatus::Dynamic,
+        ),
+    );
+
+    lazy_updater.insert(
+        ent,
+        ColliderComponent::new(
+            Cuboid::new(Vector2::new(
+                (POWERUP_HIT_BOX_WIDTH / 2.0) * WORLD_UNIT_RATIO,
+                (POWERUP_HIT_BOX_HEIGHT / 2.0) * WORLD_UNIT_RATIO,
+            )),
+            Vector2::zeros(),
+            collision::powerup_groups(),
+            0.0,
+        )
+        .as_sensor(),
+    );
+
+    debug!(target: "powerup", "[PowerUpSystem] Spawned {:?} power-up", kind);
+
+    ent
+}
+
+// Splits the first free ball into three, with the two new balls going off at an angle to the original.
+// The new balls belong to the same player as the original.
+// No more balls are added once there are MAX_ACTIVE_BALLS in play.
+pub fn multiball_spawn_events(
+    transforms: &ReadStorage<TransformComponent>,
+    balls: &ReadStorage<BallComponent>,
+) -> Vec<SpawnBallEvent> {
+    let active_balls = balls.join().count();
+    let free_slots = MAX_ACTIVE_BALLS.saturating_sub(active_balls);
+
+    // Prefer a ball that is in play, but fall back to a held one
+    let source = (transforms, balls)
+        .join()
+        .find(|(_, ball)| ball.is_launched())
+        .or_else(|| (transforms, balls).join().next());
+
+    let (position, velocity, radius, owner) = match source {
+        Some((transform, ball)) => {
+            let velocity = if ball.velocity.linear.magnitude() > 0.0 {
+                ball.velocity.linear
+            } else {
+                Vector2d::new(0.0, -BALL_DEFAULT_FORCE)
+            };
+
+            (transform.position, velocity, ball.radius, ball.owner)
+        }
+        None => return Vec::new(),
+    };
+
+    [POWERUP_MULTIBALL_ANGLE, -POWERUP_MULTIBALL_ANGLE]
+        .iter()
+        .take(free_slots)
+        .map(|angle| SpawnBallEvent {
+            position,
+            linear_velocity: nalgebra::Rotation2::new(*angle) * velocity,
+            owning_paddle_ent: None,
+            radius,
+            owner,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        brick::{BrickComponent, BRICK_DEFAULT_POINTS},
+        testing::{self, DT},
+        Point2d,
+    };
+
+    #[test]
+    fn guaranteed_drop_spawns_a_power_up_where_the_brick_was() {
+        let (mut game, _) = testing::empty_game();
+        game.world.write_resource::<PowerUpState>().drop_chance = 1.0;
+
+        let brick_ent = testing::spawn_brick(
+            &mut game.world,
+            Vector2d::new(100.0, 60.0),
+            BrickComponent::new(1, BRICK_DEFAULT_POINTS),
+        );
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(160.0, 150.0),
+            Vector2d::new(0.0, -4.0),
+        );
+        game.tick(DT);
+        let ball_ent = testing::balls(&game.world)[0];
+
+        let center = testing::position(&game.world, brick_ent)
+            + game
+                .world
+                .read_resource::<LevelConfig>()
+                .brick_center_offset();
+        testing::collide(
+            &game.world,
+            brick_ent,
+            ball_ent,
+            Vector2d::new(0.0, 1.0),
+            Point2d::from(center),
+        );
+        game.tick(DT);
+
+        let powerups = game.world.read_storage::<PowerUpComponent>();
+        let transforms = game.world.read_storage::<TransformComponent>();
+        let positions: Vec<Vector2d> = (&powerups, &transforms)
+            .join()
+            .map(|(_, transform)| transform.position)
+            .collect();
+        assert_eq!(positions, vec![center]);
+    }
+
+    #[test]
+    fn multiball_splits_one_ball_into_three() {
+        let (mut game, paddle_ent) = testing::empty_game();
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(160.0, 150.0),
+            Vector2d::new(0.0, -BALL_DEFAULT_FORCE),
+        );
+        game.tick(DT);
+        let ball_ent = testing::balls(&game.world)[0];
+
+        testing::collect_powerup(&mut game.world, paddle_ent, PowerUpKind::MultiBall);
+        game.tick(DT);
+
+        let balls = testing::balls(&game.world);
+        assert_eq!(balls.len(), 3);
+
+        let velocity = testing::ball_velocity(&game.world, ball_ent);
+        let mut angles: Vec<f64> = balls
+            .iter()
+            .filter(|&&ent| ent != ball_ent)
+            .map(|&ent| {
+                let new_velocity = testing::ball_velocity(&game.world, ent);
+                assert!((new_velocity.magnitude() - velocity.magnitude()).abs() < 0.01);
+                nalgebra::Rotation2::rotation_between(&velocity, &new_velocity).angle()
+            })
+            .collect();
+        angles.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert!((angles[0] + POWERUP_MULTIBALL_ANGLE).abs() < 0.01);
+        assert!((angles[1] - POWERUP_MULTIBALL_ANGLE).abs() < 0.01);
+    }
+
+    #[test]
+    fn multiballs_collected_together_stop_at_the_ball_cap() {
+        let (mut game, paddle_ent) = testing::empty_game();
+        for i in 0..(MAX_ACTIVE_BALLS - 1) {
+            testing::spawn_ball(
+                &game.world,
+                Vector2d::new(40.0 + 20.0 * i as f64, 150.0),
+                Vector2d::new(0.0, -BALL_DEFAULT_FORCE),
+            );
+        }
+        game.tick(DT);
+        assert_eq!(testing::balls(&game.world).len(), MAX_ACTIVE_BALLS - 1);
+
+        // Each power-up on its own would still fit one more ball
+        testing::collect_powerup(&mut game.world, paddle_ent, PowerUpKind::MultiBall);
+        testing::collect_powerup(&mut game.world, paddle_ent, PowerUpKind::MultiBall);
+        game.tick(DT);
+        game.tick(DT);
+
+        assert_eq!(testing::balls(&game.world).len(), MAX_ACTIVE_BALLS);
+    }
+
+    fn paddle_half_width(world: &World, paddle_ent: Entity) -> f64 {
+        world
+            .read_storage::<ColliderComponent>()
+            .get(paddle_ent)
+            .unwrap()
+            .shape
+            .as_shape::<Cuboid<f64>>()
+            .unwrap()
+            .half_extents()
+            .x
+    }
+
+    #[test]
+    fn expand_grows_the_paddle_until_it_wears_off() {
+        let (mut game, paddle_ent) = testing::empty_game();
+        let half_width = paddle_half_width(&game.world, paddle_ent);
+
+        testing::collect_powerup(&mut game.world, paddle_ent, PowerUpKind::ExpandPaddle);
+        game.tick(DT);
+        game.tick(DT);
+        assert!(
+            (paddle_half_width(&game.world, paddle_ent) - half_width * PADDLE_EXPAND_SCALE).abs()
+                < 1e-9
+        );
+
+        // Rather than waiting out the whole effect
+        game.world
+            .write_storage::<TimedEffectComponent<PaddleSizeEffectComponent>>()
+            .get_mut(paddle_ent)
+            .unwrap()
+            .ticks_remaining = 2;
+        for _ in 0..4 {
+            game.tick(DT);
+        }
+        assert!((paddle_half_width(&game.world, paddle_ent) - half_width).abs() < 1e-9);
+    }
+
+    // Breaks a brick holding the given power-up, and returns the kinds of every capsule dropped
+    fn dropped_powerups(seed: u64, contains_powerup: Option<PowerUpKind>) -> Vec<PowerUpKind> {
+        let mut game = testing::headless_game(seed);
+        testing::empty_level(&mut game);
+        // Random drops never happen
+        game.world.write_resource::<PowerUpState>().drop_chance = 0.0;
+
+        let brick_ent = testing::spawn_brick(
+            &mut game.world,
+            Vector2d::new(100.0, 60.0),
+            BrickComponent::new(1, BRICK_DEFAULT_POINTS).with_powerup(contains_powerup),
+        );
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(160.0, 150.0),
+            Vector2d::new(0.0, -4.0),
+        );
+        game.tick(DT);
+        let ball_ent = testing::balls(&game.world)[0];
+
+        testing::collide(
+            &game.world,
+            brick_ent,
+            ball_ent,
+            Vector2d::new(0.0, 1.0),
+            Point2d::new(116.0, 68.0),
+        );
+        game.tick(DT);
+
+        let powerups = game.world.read_storage::<PowerUpComponent>();
+        powerups.join().map(|powerup| powerup.kind).collect()
+    }
+
+    #[test]
+    fn brick_holding_multiball_always_drops_it() {
+        for seed in 0..10 {
+            assert_eq!(
+                dropped_powerups(seed, Some(PowerUpKind::MultiBall)),
+                vec![PowerUpKind::MultiBall]
+            );
+            assert!(dropped_powerups(seed, None).is_empty());
+        }
+    }
+}
@@ -1,15 +1,26 @@
 use crate::game::{
+    ball::{self, BallComponent},
+    brick::{self, BrickComponent, Scores},
+    camera::{Camera, ScreenShake},
+    level::{GamePhase, LevelConfig, LevelState, Lives},
+    paddle::PlayerPaddleComponent,
     physics::{PhysicsState, RigidbodyComponent},
     transform::TransformComponent,
 };
 use gfx::{
     color::*,
+    font::Font,
     renderer::{Renderable, TextureId, Transparency},
     sprite::*,
     Point2f, Vector2f,
 };
 use specs::prelude::*;
 
+pub const HUD_LAYER: u8 = 10;
+pub const HUD_TEXT_SCALE: f32 = 0.5;
+pub const AIM_INDICATOR_LENGTH: f64 = 24.0;
+pub const AIM_INDICATOR_WIDTH: f32 = 1.0;
+
 #[derive(Default)]
 pub struct RenderState {
     commands: Vec<gfx::renderer::RenderCommand>,
@@ -48,7 +59,9 @@ impl RenderState {
         y: f32,
         origin: Point2f,
         scale: Vector2f,
+        rotation: f32,
         region: SpriteRegion,
+        flip: (bool, bool),
     ) {
         self.commands.push(gfx::renderer::RenderCommand {
             transparency: self.bound_transparency,
@@ -60,37 +73,33 @@ impl RenderState {
                 y,
                 origin,
                 scale,
+                rotation,
                 color: self.bound_color,
                 region,
+                flip_x: flip.0,
+                flip_y: flip.1,
             },
         });
     }
 
     pub fn text(&mut self, x: f32, y: f32, w: u32, h: u32, scale: f32, text: &str) {
-        let cols: u32 = 16;
-        for (i, c) in text.chars().enumerate() {
-            let ascii: u8 = c as u8;
-            let sprite_col: u32 = ascii as u32 % cols;
-            let sprite_row: u32 = ascii as u32 / cols;
-            self.commands.push(gfx::renderer::RenderCommand {
-                transparency: self.bound_transparency,
-                shader_program_id: 1,
-                tex_id: self.bound_texture_id,
-                layer: self.bound_layer,
-                data: Renderable::Sprite {
-                    x: x + (i as f32 * (w as f32 * scale)),
-                    y: y,
-                    origin: Point2f::origin(),
-                    scale: Vector2f::new(scale, scale),
-                    color: self.bound_color,
-                    region: SpriteRegion {
-                        x: sprite_col * w,
-                        y: sprite_row * h,
-                        w,
-                        h,
-                    },
-                },
-            });
+        let font = Font::new(self.bound_texture_id, w, h, 16);
+        self.draw_text(&font, x, y, scale, text);
+    }
+
+    // Draws text with the given font. This binds the font texture, but keeps the other bound state.
+    pub fn draw_text(&mut self, font: &Font, x: f32, y: f32, scale: f32, text: &str) {
+        self.bind_texture(font.texture_id);
+        for (glyph_x, glyph_y, region) in font.layout(x, y, scale, text) {
+            self.sprite(
+                glyph_x,
+                glyph_y,
+                Point2f::origin(),
+                Vector2f::new(scale, scale),
+                0.0,
+                region,
+                (false, false),
+            );
         }
     }
 
@@ -116,6 +125,59 @@ impl RenderState {
         });
     }
 
+    // An untextured quad, in the bound color
+    pub fn quad(&mut self, bl: (f32, f32), br: (f32, f32), tl: (f32, f32), tr: (f32, f32)) {
+        self.commands.push(gfx::renderer::RenderCommand {
+            transparency: self.bound_transparency,
+            shader_program_id: 0,
+            tex_id: 0,
+            layer: self.bound_layer,
+            data: Renderable::Quad {
+                bl,
+                br,
+                tl,
+                tr,
+                color: self.bound_color,
+            },
+        });
+    }
+
+    // A straight untextured line between two points
+    pub fn line(&mut self, from: (f32, f32), to: (f32, f32), width: f32) {
+        let length = ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt();
+        if length <= 0.0 {
+            return;
+        }
+
+        // Offset the ends sideways by half the width, to make a thin quad along the line
+        let half_width = width / 2.0;
+        let normal = (
+            -(to.1 - from.1) / length * half_width,
+            (to.0 - from.0) / length * half_width,
+        );
+
+        self.quad(
+            (from.0 - normal.0, from.1 - normal.1),
+            (from.0 + normal.0, from.1 + normal.1),
+            (to.0 - normal.0, to.1 - normal.1),
+            (to.0 + normal.0, to.1 + normal.1),
+        );
+    }
+
+    // The outline of an axis aligned rectangle, drawn as four untextured quads
+    pub fn rect_outline(&mut self, left: f32, top: f32, right: f32, bottom: f32, width: f32) {
+        let edges = [
+            (left, top, right, top + width),
+            (left, bottom - width, right, bottom),
+            (left, top, left + width, bottom),
+            (right - width, top, right, bottom),
+        ];
+
+        for (l, t, r, b) in edges.iter() {
+            self.quad((*l, *b), (*r, *b), (*l, *t), (*r, *t));
+        }
+    }
+
     pub fn clear_commands(&mut self) {
         self.bound_transparency = Transparency::default();
         self.bound_texture_id = 0;
@@ -136,6 +198,9 @@ pub struct SpriteComponent {
     pub spritesheet_tex_id: TextureId,
     pub layer: u8,
     pub transparency: Transparency,
+    // Mirror the sprite horizontally and/or vertically, in place
+    pub flip_x: bool,
+    pub flip_y: bool,
 }
 
 impl Component for SpriteComponent {
@@ -147,37 +212,356 @@ pub struct SpriteRenderSystem;
 
 impl<'a> System<'a> for SpriteRenderSystem {
     type SystemData = (
+        Entities<'a>,
         ReadExpect<'a, PhysicsState>,
+        ReadExpect<'a, Camera>,
+        ReadExpect<'a, ScreenShake>,
         Write<'a, RenderState>,
         ReadStorage<'a, TransformComponent>,
         ReadStorage<'a, SpriteComponent>,
         ReadStorage<'a, RigidbodyComponent>,
     );
 
-    fn run(&mut self, (physics, mut render, transforms, sprites, rigidbodies): Self::SystemData) {
-        for (transform, sprite, rigidbody) in (&transforms, &sprites, (&rigidbodies).maybe()).join()
-        {
-            let (x, y) = if let Some(_) = rigidbody {
-                let x = (transform.position.x * physics.lerp)
-                    + (transform.last_position.x * (1.0 - physics.lerp));
-                let y = (transform.position.y * physics.lerp)
-                    + (transform.last_position.y * (1.0 - physics.lerp));
-                (x, y)
+    fn run(
+        &mut self,
+        (
+            ents,
+            physics,
+            camera,
+            shake,
+            mut render,
+            transforms,
+            sprites,
+            rigidbodies,
+        ): Self::SystemData,
+    ) {
+        // Sort sprites by layer, so higher layers are always drawn on top.
+        // Sprites on the same layer are drawn in entity order, so overlapping ones don't flicker between frames.
+        let mut sorted_sprites: Vec<(Entity, &TransformComponent, &SpriteComponent, bool)> =
+            (&ents, &transforms, &sprites, (&rigidbodies).maybe())
+                .join()
+                .map(|(ent, transform, sprite, rigidbody)| {
+                    (ent, transform, sprite, rigidbody.is_some())
+                })
+                .collect();
+        sorted_sprites.sort_by_key(|(ent, _, sprite, _)| (sprite.layer, ent.id()));
+
+        for (_, transform, sprite, has_rigidbody) in sorted_sprites {
+            let position = if has_rigidbody {
+                transform.interpolated_position(physics.lerp)
             } else {
-                (transform.position.x, transform.position.y)
+                transform.position
             };
 
+            let (x, y) = camera.world_to_screen(position + shake.offset);
+
             render.bind_transparency(sprite.transparency);
             render.bind_texture(sprite.spritesheet_tex_id);
             render.bind_color(sprite.color);
             render.bind_layer(sprite.layer);
             render.sprite(
-                x as f32,
-                y as f32,
+                x,
+                y,
                 transform.origin,
-                transform.scale,
+                transform.scale * camera.scale(),
+                transform.rotation,
                 sprite.region,
+                (sprite.flip_x, sprite.flip_y),
+            );
+        }
+    }
+}
+
+// Draws the score and remaining balls in the top left corner, above everything else
+#[derive(Default)]
+pub struct HudRenderSystem;
+
+impl<'a> System<'a> for HudRenderSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Font>,
+        ReadExpect<'a, LevelConfig>,
+        Read<'a, LevelState>,
+        Read<'a, Scores>,
+        ReadExpect<'a, Lives>,
+        Read<'a, GamePhase>,
+        Write<'a, RenderState>,
+        ReadStorage<'a, BrickComponent>,
+        ReadStorage<'a, BallComponent>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            ents,
+            font,
+            config,
+            level,
+            scores,
+            lives,
+            phase,
+            mut render,
+            bricks,
+            balls,
+        ): Self::SystemData,
+    ) {
+        let line_height = font.glyph_height as f32 * HUD_TEXT_SCALE;
+
+        // The final score is highlighted once the game or level is over
+        render.bind_color(if *phase == GamePhase::Playing {
+            COLOR_WHITE
+        } else {
+            COLOR_GREEN
+        });
+        render.bind_layer(HUD_LAYER);
+        render.bind_transparency(Transparency::Transparent);
+        render.draw_text(
+            &font,
+            2.0,
+            2.0,
+            HUD_TEXT_SCALE,
+            &format!("Score: {}", level.score),
+        );
+
+        render.bind_color(COLOR_WHITE);
+        render.draw_text(
+            &font,
+            2.0,
+            2.0 + line_height,
+            HUD_TEXT_SCALE,
+            &format!("Balls: {}", lives.count),
+        );
+
+        // With more than one player, each player's share of the score goes underneath
+        if config.players > 1 {
+            for player_id in 0..config.players {
+                let y = 2.0 + ((player_id + 2) as f32 * line_height);
+                let text = format!("P{}: {}", player_id + 1, scores.get(player_id));
+                render.draw_text(&font, 2.0, y, HUD_TEXT_SCALE, &text);
+            }
+        }
+
+        // Bricks left and balls in play go in the top right corner, out of the way
+        let counters = [
+            format!("Bricks: {}", brick::count_breakable_bricks(&ents, &bricks)),
+            format!("In play: {}", ball::count_active_balls(&ents, &balls)),
+        ];
+        for (i, text) in counters.iter().enumerate() {
+            let width = text.len() as f32 * font.glyph_width as f32 * HUD_TEXT_SCALE;
+            let x = config.field_width as f32 - width - 2.0;
+            let y = 2.0 + (i as f32 * line_height);
+            render.draw_text(&font, x, y, HUD_TEXT_SCALE, text);
+        }
+    }
+}
+
+// Shows which way a held ball will go when it is launched
+#[derive(Default)]
+pub struct AimIndicatorRenderSystem;
+
+impl<'a> System<'a> for AimIndicatorRenderSystem {
+    type SystemData = (
+        ReadExpect<'a, Camera>,
+        Read<'a, GamePhase>,
+        Write<'a, RenderState>,
+        ReadStorage<'a, PlayerPaddleComponent>,
+    );
+
+    fn run(&mut self, (camera, phase, mut render, paddles): Self::SystemData) {
+        if *phase != GamePhase::Playing {
+            return;
+        }
+
+        render.bind_color(COLOR_WHITE.with_alpha(0.75));
+        render.bind_layer(HUD_LAYER - 1);
+        render.bind_transparency(Transparency::Transparent);
+        for paddle in paddles.join() {
+            if paddle.held_ball_ent.is_none() {
+                continue;
+            }
+
+            let direction = paddle.launch_velocity().normalize();
+            let from = paddle.held_ball_position;
+            let to = from + (direction * AIM_INDICATOR_LENGTH);
+            render.line(
+                camera.world_to_screen(from),
+                camera.world_to_screen(to),
+                AIM_INDICATOR_WIDTH * camera.scale(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        testing::{self, DT},
+        GameState, Vector2d,
+    };
+    use gfx::renderer::RenderCommand;
+
+    fn sprite_world() -> World {
+        let mut world = World::new();
+        world.register::<TransformComponent>();
+        world.register::<SpriteComponent>();
+        world.register::<RigidbodyComponent>();
+        world.insert(PhysicsState::new());
+        world.insert(Camera::new(320, 240));
+        world.insert(ScreenShake::new());
+        world.insert(RenderState::new());
+
+        world
+    }
+
+    // The sprite's region x tells the sprites apart
+    fn spawn_sprite(world: &mut World, layer: u8, id: u32) {
+        world
+            .create_entity()
+            .with(TransformComponent::new(
+                Vector2d::zeros(),
+                Point2f::origin(),
+                Vector2f::new(1.0, 1.0),
+            ))
+            .with(SpriteComponent {
+                color: COLOR_WHITE,
+                region: SpriteRegion {
+                    x: id,
+                    y: 0,
+                    w: 32,
+                    h: 32,
+                },
+                spritesheet_tex_id: 0,
+                layer,
+                transparency: Transparency::Opaque,
+                flip_x: false,
+                flip_y: false,
+            })
+            .build();
+    }
+
+    fn drawn_sprite(command: &RenderCommand) -> (u8, u32) {
+        match command.data {
+            Renderable::Sprite { region, .. } => (command.layer, region.x),
+            _ => panic!("Expected only sprites to be drawn!"),
+        }
+    }
+
+    #[test]
+    fn sprites_are_drawn_by_layer_then_entity() {
+        let mut world = sprite_world();
+        spawn_sprite(&mut world, 1, 0);
+        spawn_sprite(&mut world, 0, 1);
+        spawn_sprite(&mut world, 1, 2);
+        spawn_sprite(&mut world, 0, 3);
+
+        SpriteRenderSystem.run_now(&world);
+
+        let commands = world.write_resource::<RenderState>().commands();
+        let drawn: Vec<(u8, u32)> = commands.iter().map(drawn_sprite).collect();
+        assert_eq!(drawn, vec![(0, 1), (0, 3), (1, 0), (1, 2)]);
+    }
+
+    // Where everything in the last collected frame is drawn
+    fn drawn_positions(game: &GameState) -> Vec<(f32, f32)> {
+        game.world
+            .write_resource::<RenderState>()
+            .commands()
+            .iter()
+            .map(|command| match command.data {
+                Renderable::Sprite { x, y, .. } => (x, y),
+                Renderable::Quad { bl, .. } => bl,
+            })
+            .collect()
+    }
+
+    fn collect_frame(game: &mut GameState) {
+        game.world.write_resource::<RenderState>().clear_commands();
+        game.render_dispatcher.dispatch(&mut game.world);
+    }
+
+    #[test]
+    fn collected_frame_is_kept_until_the_next_one() {
+        let (mut game, _) = testing::empty_game();
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(160.0, 150.0),
+            Vector2d::new(0.0, -4.0),
+        );
+        game.tick(DT);
+        game.tick(DT);
+
+        collect_frame(&mut game);
+        let frame = drawn_positions(&game);
+        assert!(!frame.is_empty());
+
+        game.run_headless(10, DT);
+        assert_eq!(drawn_positions(&game), frame);
+
+        collect_frame(&mut game);
+        assert_ne!(drawn_positions(&game), frame);
+    }
+
+    // All the text the HUD drew, read back from the font glyphs it sent to the renderer, top to bottom
+    // and left to right
+    fn drawn_hud_text(world: &World) -> String {
+        let font = world.read_resource::<Font>();
+        let commands = world.write_resource::<RenderState>().commands();
+        let mut glyphs: Vec<(f32, f32, char)> = commands
+            .iter()
+            .filter(|command| command.layer == HUD_LAYER && command.tex_id == font.texture_id)
+            .filter_map(|command| match command.data {
+                Renderable::Sprite { x, y, region, .. } => {
+                    let ascii = (region.y / font.glyph_height) * font.columns
+                        + (region.x / font.glyph_width);
+                    Some((y, x, std::char::from_u32(ascii).unwrap()))
+                }
+                _ => None,
+            })
+            .collect();
+        glyphs.sort_by(|a, b| (a.0, a.1).partial_cmp(&(b.0, b.1)).unwrap());
+
+        glyphs.into_iter().map(|(_, _, c)| c).collect()
+    }
+
+    #[test]
+    fn hud_counts_only_breakable_bricks_and_every_ball() {
+        let (mut game, paddle_ent) = testing::empty_game();
+        for &x in &[40.0, 80.0] {
+            testing::spawn_brick(
+                &mut game.world,
+                Vector2d::new(x, 40.0),
+                BrickComponent::new(1, 100),
             );
         }
+        for &x in &[120.0, 160.0, 200.0] {
+            testing::spawn_brick(
+                &mut game.world,
+                Vector2d::new(x, 40.0),
+                BrickComponent::new_indestructible(),
+            );
+        }
+        testing::spawn_held_ball(&game.world, paddle_ent);
+        for &x in &[100.0, 220.0] {
+            testing::spawn_ball(
+                &game.world,
+                Vector2d::new(x, 120.0),
+                Vector2d::new(0.0, -4.0),
+            );
+        }
+        game.tick(DT);
+
+        HudRenderSystem.run_now(&game.world);
+
+        // The corner brick left by the empty level counts too
+        let text = drawn_hud_text(&game.world);
+        assert!(text.contains("Bricks: 3"), "{}", text);
+        assert!(text.contains("In play: 3"), "{}", text);
+
+        let ents = game.world.entities();
+        let bricks = game.world.read_storage::<BrickComponent>();
+        let balls = game.world.read_storage::<BallComponent>();
+        assert_eq!(brick::count_breakable_bricks(&ents, &bricks), 3);
+        assert_eq!(ball::count_active_balls(&ents, &balls), 3);
     }
 }
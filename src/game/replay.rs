@@ -0,0 +1,108 @@
+use gfx::input::InputState;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+// The input for every tick of a game, along with the seed it was played with. Since the simulation
+// runs at a fixed timestep, replaying the same input from the same seed plays out the same way.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub inputs: Vec<InputState>,
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Self {
+        Replay {
+            seed,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let source = ron::ser::to_string(self)?;
+        std::fs::write(path, source)?;
+
+        info!(target: "replay", "Saved replay of {} ticks to {}", self.inputs.len(), path);
+
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Replay, Box<dyn Error>> {
+        let source = std::fs::read_to_string(path)?;
+        let replay = ron::de::from_str(&source)?;
+
+        Ok(replay)
+    }
+}
+
+// Captures the input of every tick while recording
+#[derive(Default)]
+pub struct ReplayRecorder {
+    replay: Option<Replay>,
+}
+
+impl ReplayRecorder {
+    pub fn start(&mut self, seed: u64) {
+        self.replay = Some(Replay::new(seed));
+    }
+
+    // Stops recording, and returns everything recorded so far
+    pub fn stop(&mut self) -> Option<Replay> {
+        self.replay.take()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    pub fn record(&mut self, input: &InputState) {
+        if let Some(replay) = self.replay.as_mut() {
+            replay.inputs.push(input.clone());
+        }
+    }
+}
+
+// Feeds recorded input to the game instead of the live input, one tick at a time
+#[derive(Default)]
+pub struct ReplayPlayer {
+    replay: Option<Replay>,
+    tick: usize,
+}
+
+impl ReplayPlayer {
+    pub fn play(&mut self, replay: Replay) {
+        self.replay = Some(replay);
+        self.tick = 0;
+    }
+
+    pub fn stop(&mut self) {
+        self.replay = None;
+        self.tick = 0;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    // Input for the next tick. Playback stops once the recorded input runs out.
+    pub fn next_input(&mut self) -> Option<InputState> {
+        let input = self
+            .replay
+            .as_ref()
+            .and_then(|replay| replay.inputs.get(self.tick).cloned());
+
+        match input {
+            Some(_) => self.tick += 1,
+            None => {
+                if self.is_active() {
+                    info!(target: "replay", "[ReplayPlayer] Replay finished after {} ticks", self.tick);
+                }
+
+                self.stop();
+            }
+        }
+
+        input
+    }
+}
@@ -0,0 +1,17 @@
+use rand::{rngs::StdRng, SeedableRng};
+
+// Shared random number generator. Everything random in the simulation should pull from this,
+// so that a game created with the same seed plays out the same way.
+pub struct RngResource {
+    pub seed: u64,
+    pub rng: StdRng,
+}
+
+impl RngResource {
+    pub fn new(seed: u64) -> Self {
+        RngResource {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
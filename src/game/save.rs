@@ -0,0 +1,264 @@
+use crate::game::{
+    ball::{BallComponent, SpawnBallEvent, BALL_DEFAULT_RADIUS},
+    brick::{BrickComponent, Combo, Scores},
+    difficulty::DifficultyState,
+    level::{self, BrickDesc, GamePhase, LevelConfig, LevelState, Lives},
+    paddle::{PlayerId, PlayerPaddleComponent},
+    render::SpriteComponent,
+    transform::TransformComponent,
+    Vector2d,
+};
+use log::info;
+use serde::{Deserialize, Serialize};
+use shrev::EventChannel;
+use specs::prelude::*;
+use std::error::Error;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PaddleSave {
+    pub player_id: PlayerId,
+    pub x: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BallSave {
+    pub position: (f64, f64),
+    pub velocity: (f64, f64),
+    // Player whose paddle is holding the ball, if any. Held balls are put back on that paddle when loaded.
+    pub held_by: Option<PlayerId>,
+    #[serde(default = "default_ball_radius")]
+    pub radius: f64,
+    // Player that gets the points for bricks the ball breaks
+    #[serde(default)]
+    pub owner: Option<PlayerId>,
+}
+
+fn default_ball_radius() -> f64 {
+    BALL_DEFAULT_RADIUS
+}
+
+// Everything needed to rebuild a game in progress. Physics bodies and colliders aren't saved,
+// since they are recreated from the components when the entities are spawned again.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SaveData {
+    pub level: u32,
+    pub score: u32,
+    // Points scored by each player
+    #[serde(default)]
+    pub scores: Scores,
+    pub lives: u32,
+    pub layout_path: Option<String>,
+    pub paddles: Vec<PaddleSave>,
+    pub bricks: Vec<BrickDesc>,
+    pub balls: Vec<BallSave>,
+}
+
+impl SaveData {
+    pub fn from_world(world: &World) -> Self {
+        let level = world.read_resource::<LevelState>();
+        let lives = world.read_resource::<Lives>();
+        let transforms = world.read_storage::<TransformComponent>();
+        let sprites = world.read_storage::<SpriteComponent>();
+        let bricks = world.read_storage::<BrickComponent>();
+        let balls = world.read_storage::<BallComponent>();
+        let paddles = world.read_storage::<PlayerPaddleComponent>();
+
+        let paddle_saves = (&transforms, &paddles)
+            .join()
+            .map(|(transform, paddle)| PaddleSave {
+                player_id: paddle.player_id,
+                x: transform.position.x,
+            })
+            .collect();
+
+        let brick_saves = (&transforms, &sprites, &bricks)
+            .join()
+            .map(|(transform, sprite, brick)| BrickDesc {
+                position: (transform.position.x, transform.position.y),
+                hp: brick.hp,
+                region: (
+                    sprite.region.x,
+                    sprite.region.y,
+                    sprite.region.w,
+                    sprite.region.h,
+                ),
+                points: brick.points,
+                damage_regions: brick
+                    .damage_regions
+                    .iter()
+                    .map(|region| (region.x, region.y, region.w, region.h))
+                    .collect(),
+                indestructible: brick.indestructible,
+                explosive: brick.explosive,
+                contains_powerup: brick.contains_powerup,
+            })
+            .collect();
+
+        let ball_saves = (&transforms, &balls)
+            .join()
+            .map(|(transform, ball)| BallSave {
+                position: (transform.position.x, transform.position.y),
+                velocity: (ball.velocity.linear.x, ball.velocity.linear.y),
+                held_by: ball
+                    .holding_paddle_ent
+                    .and_then(|ent| paddles.get(ent))
+                    .map(|paddle| paddle.player_id),
+                radius: ball.radius,
+                owner: ball.owner,
+            })
+            .collect();
+
+        SaveData {
+            level: level.level,
+            score: level.score,
+            scores: world.read_resource::<Scores>().clone(),
+            lives: lives.count,
+            layout_path: level.layout_path.clone(),
+            paddles: paddle_saves,
+            bricks: brick_saves,
+            balls: ball_saves,
+        }
+    }
+}
+
+pub fn save_game(world: &World, path: &str) -> Result<(), Box<dyn Error>> {
+    let save = SaveData::from_world(world);
+    let source = ron::ser::to_string_pretty(&save, ron::ser::PrettyConfig::default())?;
+    std::fs::write(path, source)?;
+
+    info!(target: "save", "Saved game to {}", path);
+
+    Ok(())
+}
+
+pub fn load_game(world: &mut World, path: &str) -> Result<(), Box<dyn Error>> {
+    let source = std::fs::read_to_string(path)?;
+    let save: SaveData = ron::de::from_str(&source)?;
+
+    world.delete_all();
+
+    // Paddles are spawned in player order, so each saved paddle can be found by its player id
+    let paddle_x = world.read_resource::<LevelConfig>().paddle_spawn_x(0);
+    let paddle_ents = level::spawn_arena(world, paddle_x);
+    let player_paddle_ent = paddle_ents[0];
+    {
+        let mut transforms = world.write_storage::<TransformComponent>();
+        for paddle in save.paddles.iter() {
+            if let Some(&ent) = paddle_ents.get(paddle.player_id as usize) {
+                if let Some(transform) = transforms.get_mut(ent) {
+                    transform.position.x = paddle.x;
+                }
+            }
+        }
+    }
+
+    for desc in save.bricks.iter() {
+        level::spawn_brick_from_desc(world, desc);
+    }
+
+    {
+        let mut spawn_ball_events = world.write_resource::<EventChannel<SpawnBallEvent>>();
+        for ball in save.balls.iter() {
+            spawn_ball_events.single_write(SpawnBallEvent {
+                position: Vector2d::new(ball.position.0, ball.position.1),
+                linear_velocity: Vector2d::new(ball.velocity.0, ball.velocity.1),
+                // Balls held by a player with no paddle in this game go to the first paddle
+                owning_paddle_ent: ball.held_by.map(|player_id| {
+                    paddle_ents
+                        .get(player_id as usize)
+                        .cloned()
+                        .unwrap_or(player_paddle_ent)
+                }),
+                radius: ball.radius,
+                owner: ball.owner,
+            });
+        }
+    }
+
+    {
+        let mut level = world.write_resource::<LevelState>();
+        level.reset(player_paddle_ent);
+        level.level = save.level;
+        level.score = save.score;
+        level.layout_path = save.layout_path;
+    }
+
+    world.write_resource::<Lives>().count = save.lives;
+    *world.write_resource::<GamePhase>() = GamePhase::Playing;
+    world.write_resource::<DifficultyState>().reset();
+    world.write_resource::<Combo>().reset();
+    *world.write_resource::<Scores>() = save.scores;
+
+    world.maintain();
+
+    info!(target: "save", "Loaded game from {}", path);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::{self, DT};
+
+    // Position and hp of every brick, in position order
+    fn bricks(world: &World) -> Vec<(f64, f64, i32)> {
+        let transforms = world.read_storage::<TransformComponent>();
+        let bricks = world.read_storage::<BrickComponent>();
+        let mut states: Vec<(f64, f64, i32)> = (&transforms, &bricks)
+            .join()
+            .map(|(transform, brick)| (transform.position.x, transform.position.y, brick.hp))
+            .collect();
+        states.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        states
+    }
+
+    #[test]
+    fn saved_game_loads_back_the_same() {
+        let (mut game, _) = testing::empty_game();
+        testing::spawn_brick(
+            &mut game.world,
+            Vector2d::new(64.0, 40.0),
+            BrickComponent::new(2, 100),
+        );
+        testing::spawn_brick(
+            &mut game.world,
+            Vector2d::new(128.0, 40.0),
+            BrickComponent::new(3, 100),
+        );
+        testing::spawn_ball(
+            &game.world,
+            Vector2d::new(100.0, 150.0),
+            Vector2d::new(1.0, -5.0),
+        );
+        game.tick(DT);
+        game.world.write_resource::<LevelState>().score = 1234;
+        game.world.write_resource::<Lives>().count = 2;
+
+        let path = testing::temp_path("round-trip.sav");
+        game.save(&path).unwrap();
+        let mut loaded = testing::headless_game(1);
+        loaded.load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        // Balls are spawned on the tick after loading
+        loaded.tick(DT);
+
+        assert_eq!(bricks(&loaded.world), bricks(&game.world));
+
+        let ball_ent = testing::balls(&game.world)[0];
+        let loaded_balls = testing::balls(&loaded.world);
+        assert_eq!(loaded_balls.len(), 1);
+        assert_eq!(
+            testing::position(&loaded.world, loaded_balls[0]),
+            testing::position(&game.world, ball_ent)
+        );
+        assert_eq!(
+            testing::ball_velocity(&loaded.world, loaded_balls[0]),
+            testing::ball_velocity(&game.world, ball_ent)
+        );
+
+        assert_eq!(loaded.world.read_resource::<LevelState>().score, 1234);
+        assert_eq!(loaded.world.read_resource::<Lives>().count, 2);
+    }
+}
@@ -0,0 +1,261 @@
+// Helpers for tests that run a whole game headlessly
+use crate::game::{
+    ball::{self, BallComponent, SpawnBallEvent},
+    brick::{self, BrickComponent},
+    level::{self, LevelConfig, LevelState},
+    physics::{CollisionEvent, CollisionType, PhysicsState, ProximityEvent, ProximityType},
+    powerup::{PowerUpComponent, PowerUpKind},
+    transform::TransformComponent,
+    GameState, Point2d, Vector2d,
+};
+use gfx::{
+    input::{InputState, VirtualKeyCode},
+    sprite::SpriteRegion,
+    window::DeltaTime,
+};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use ncollide2d::shape::{Ball, ShapeHandle};
+use nphysics2d::object::{
+    BodyPartHandle, ColliderDesc, DefaultBodySet, DefaultColliderHandle, DefaultColliderSet, Ground,
+};
+use shrev::EventChannel;
+use specs::prelude::*;
+use std::cell::RefCell;
+
+pub const FIELD_WIDTH: u32 = 320;
+pub const FIELD_HEIGHT: u32 = 240;
+pub const DT: DeltaTime = 1.0 / 60.0;
+
+// A seeded game, with the physics world stepping once per tick. The default level is loaded on the first tick.
+pub fn headless_game<'a, 'b>(seed: u64) -> GameState<'a, 'b> {
+    let game = GameState::new_seeded(FIELD_WIDTH, FIELD_HEIGHT, seed);
+    game.world.write_resource::<PhysicsState>().set_timestep(DT);
+
+    game
+}
+
+// Skips the level load, and spawns only the paddle and walls, with the paddle in the middle of the field.
+// One brick is left in the top right corner, so the level isn't won right away (which would freeze every ball).
+// Returns the paddle.
+pub fn empty_level(game: &mut GameState) -> Entity {
+    let world = &mut game.world;
+    let (paddle_x, corner) = {
+        let config = world.read_resource::<LevelConfig>();
+        (
+            config.paddle_spawn_x(0),
+            Vector2d::new(
+                config.field_width as f64 - config.brick_width,
+                level::LEVEL_BRICKS_Y_OFFSET,
+            ),
+        )
+    };
+
+    let paddle_ent = level::spawn_arena(world, paddle_x)[0];
+    spawn_brick(
+        world,
+        corner,
+        BrickComponent::new(brick::BRICK_DEFAULT_HP, brick::BRICK_DEFAULT_POINTS),
+    );
+    world.write_resource::<LevelState>().reset(paddle_ent);
+    world.maintain();
+
+    paddle_ent
+}
+
+// A game on the default seed with its level emptied out. Returns the game and the player's paddle.
+pub fn empty_game<'a, 'b>() -> (GameState<'a, 'b>, Entity) {
+    let mut game = headless_game(0);
+    let paddle_ent = empty_level(&mut game);
+
+    (game, paddle_ent)
+}
+
+pub fn spawn_brick(world: &mut World, position: Vector2d, brick: BrickComponent) -> Entity {
+    level::spawn_brick(
+        world,
+        position,
+        brick,
+        SpriteRegion {
+            x: 96,
+            y: 0,
+            w: brick::BRICK_SPRITE_WIDTH,
+            h: brick::BRICK_SPRITE_HEIGHT,
+        },
+    )
+}
+
+// Asks for a launched ball at the given position (in pixels), moving at the given velocity (in world units per second).
+// It is spawned at the end of the next tick.
+pub fn spawn_ball(world: &World, position: Vector2d, linear_velocity: Vector2d) {
+    world
+        .write_resource::<EventChannel<SpawnBallEvent>>()
+        .single_write(SpawnBallEvent {
+            position,
+            linear_velocity,
+            owning_paddle_ent: None,
+            radius: ball::BALL_DEFAULT_RADIUS,
+            owner: None,
+        });
+}
+
+// Asks for a ball held on the given paddle. It is spawned at the end of the next tick.
+pub fn spawn_held_ball(world: &World, paddle_ent: Entity) {
+    world
+        .write_resource::<EventChannel<SpawnBallEvent>>()
+        .single_write(SpawnBallEvent {
+            position: Vector2d::zeros(),
+            linear_velocity: Vector2d::zeros(),
+            owning_paddle_ent: Some(paddle_ent),
+            radius: ball::BALL_DEFAULT_RADIUS,
+            owner: None,
+        });
+}
+
+// Every ball in play, in entity order
+pub fn balls(world: &World) -> Vec<Entity> {
+    let ents = world.entities();
+    let balls = world.read_storage::<BallComponent>();
+    (&ents, &balls).join().map(|(ent, _)| ent).collect()
+}
+
+pub fn position(world: &World, ent: Entity) -> Vector2d {
+    world
+        .read_storage::<TransformComponent>()
+        .get(ent)
+        .unwrap()
+        .position
+}
+
+// Linear velocity of the given ball, in world units per second
+pub fn ball_velocity(world: &World, ball_ent: Entity) -> Vector2d {
+    world
+        .read_storage::<BallComponent>()
+        .get(ball_ent)
+        .unwrap()
+        .velocity
+        .linear
+}
+
+// A path in the temp directory, unique to this test run
+pub fn temp_path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("brickbonker-{}-{}", std::process::id(), name))
+        .to_string_lossy()
+        .into_owned()
+}
+
+// Input with the given keys pressed down on this tick
+pub fn pressed(keys: &[VirtualKeyCode]) -> InputState {
+    let mut input = InputState::new();
+    for &key in keys {
+        input.handle_key(key, true);
+    }
+
+    input
+}
+
+// Sends the events the physics world would for a contact starting between the two entities, without needing them
+// to actually touch. The normal points from a to b, and the point is in pixels.
+pub fn collide(
+    world: &World,
+    entity_a: Entity,
+    entity_b: Entity,
+    normal: Vector2d,
+    point: Point2d,
+) {
+    let handle = placeholder_collider_handle();
+    let mut events = world.write_resource::<EventChannel<CollisionEvent>>();
+    events.single_write(CollisionEvent {
+        entity_a: Some(entity_a),
+        collider_handle_a: handle,
+        entity_b: Some(entity_b),
+        collider_handle_b: handle,
+        normal: Some(normal),
+        collision_point: Some(point),
+        ty: CollisionType::Started,
+    });
+    events.single_write(CollisionEvent {
+        entity_a: Some(entity_b),
+        collider_handle_a: handle,
+        entity_b: Some(entity_a),
+        collider_handle_b: handle,
+        normal: Some(-normal),
+        collision_point: Some(point),
+        ty: CollisionType::Started,
+    });
+}
+
+// Has the paddle catch a power-up capsule of the given kind. It takes effect on the next tick.
+pub fn collect_powerup(world: &mut World, paddle_ent: Entity, kind: PowerUpKind) {
+    let capsule_ent = world
+        .create_entity()
+        .with(PowerUpComponent::new(kind))
+        .build();
+    let handle = placeholder_collider_handle();
+    world
+        .write_resource::<EventChannel<ProximityEvent>>()
+        .single_write(ProximityEvent {
+            entity_a: Some(capsule_ent),
+            collider_handle_a: handle,
+            entity_b: Some(paddle_ent),
+            collider_handle_b: handle,
+            ty: ProximityType::Entered,
+        });
+}
+
+// Made up events still need collider handles, even though nothing outside of the physics systems looks at them
+fn placeholder_collider_handle() -> DefaultColliderHandle {
+    let mut bodies = DefaultBodySet::<f64>::new();
+    let ground_handle = bodies.insert(Ground::new());
+    let mut colliders = DefaultColliderSet::new();
+    colliders.insert(
+        ColliderDesc::new(ShapeHandle::new(Ball::new(1.0))).build(BodyPartHandle(ground_handle, 0)),
+    )
+}
+
+pub struct CapturedLog {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+// Keeps each thread's log records to itself, so tests running side by side don't see each other's logs.
+// The physics systems are thread local, so their logs land on the thread of the test that ticked them.
+struct CapturingLogger;
+
+thread_local! {
+    static CAPTURED_LOGS: RefCell<Vec<CapturedLog>> = RefCell::new(Vec::new());
+}
+
+static LOGGER: CapturingLogger = CapturingLogger;
+
+impl Log for CapturingLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        CAPTURED_LOGS.with(|logs| {
+            logs.borrow_mut().push(CapturedLog {
+                level: record.level(),
+                target: record.target().to_string(),
+                message: record.args().to_string(),
+            })
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+// Starts capturing warnings and errors logged on this thread, throwing away anything captured so far
+pub fn capture_logs() {
+    // Only the first test to get here actually sets the logger
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(LevelFilter::Warn);
+    CAPTURED_LOGS.with(|logs| logs.borrow_mut().clear());
+}
+
+// Takes everything captured on this thread since the last call
+pub fn captured_logs() -> Vec<CapturedLog> {
+    CAPTURED_LOGS.with(|logs| logs.borrow_mut().drain(..).collect())
+}
@@ -0,0 +1,148 @@
+use crate::game::{
+    camera::{Camera, ScreenShake},
+    render::{RenderState, SpriteComponent},
+    transform::TransformComponent,
+    Vector2d,
+};
+use gfx::renderer::Transparency;
+use specs::prelude::*;
+use std::collections::VecDeque;
+
+pub const BALL_TRAIL_DEFAULT_LENGTH: usize = 6;
+pub const BALL_TRAIL_DEFAULT_START_ALPHA: f32 = 0.5;
+pub const BALL_TRAIL_DEFAULT_START_SCALE: f32 = 0.9;
+
+// Remembers where an entity has been over the last few ticks, so a fading trail can be drawn behind it
+pub struct BallTrailComponent {
+    // Oldest position first. This never holds more than `length` positions.
+    pub positions: VecDeque<Vector2d>,
+    pub length: usize,
+    // Alpha and scale of the newest ghost sprite. These fall off to 0 towards the oldest one.
+    pub start_alpha: f32,
+    pub start_scale: f32,
+}
+
+impl BallTrailComponent {
+    pub fn new(length: usize) -> Self {
+        BallTrailComponent {
+            positions: VecDeque::with_capacity(length),
+            length,
+            start_alpha: BALL_TRAIL_DEFAULT_START_ALPHA,
+            start_scale: BALL_TRAIL_DEFAULT_START_SCALE,
+        }
+    }
+
+    pub fn push(&mut self, position: Vector2d) {
+        if self.length == 0 {
+            return;
+        }
+
+        while self.positions.len() >= self.length {
+            self.positions.pop_front();
+        }
+
+        self.positions.push_back(position);
+    }
+}
+
+impl Component for BallTrailComponent {
+    type Storage = VecStorage<Self>;
+}
+
+pub struct BallTrailSystem;
+
+impl<'a> System<'a> for BallTrailSystem {
+    type SystemData = (
+        ReadStorage<'a, TransformComponent>,
+        WriteStorage<'a, BallTrailComponent>,
+    );
+
+    fn run(&mut self, (transforms, mut trails): Self::SystemData) {
+        for (transform, trail) in (&transforms, &mut trails).join() {
+            trail.push(transform.position);
+        }
+    }
+}
+
+// Draws the trails as ghost copies of the entity's sprite, which get fainter and smaller the older they are
+#[derive(Default)]
+pub struct BallTrailRenderSystem;
+
+impl<'a> System<'a> for BallTrailRenderSystem {
+    type SystemData = (
+        ReadExpect<'a, Camera>,
+        ReadExpect<'a, ScreenShake>,
+        Write<'a, RenderState>,
+        ReadStorage<'a, TransformComponent>,
+        ReadStorage<'a, SpriteComponent>,
+        ReadStorage<'a, BallTrailComponent>,
+    );
+
+    fn run(&mut self, (camera, shake, mut render, transforms, sprites, trails): Self::SystemData) {
+        for (transform, sprite, trail) in (&transforms, &sprites, &trails).join() {
+            let count = trail.positions.len();
+            for (i, position) in trail.positions.iter().enumerate() {
+                // 1.0 for the newest position, falling towards 0.0 for the oldest
+                let t = (i + 1) as f32 / (count + 1) as f32;
+
+                let (x, y) = camera.world_to_screen(*position + shake.offset);
+
+                // Drawn on the layer below the sprite, so the trail is always behind it
+                render.bind_transparency(Transparency::Transparent);
+                render.bind_texture(sprite.spritesheet_tex_id);
+                render.bind_color(sprite.color.with_alpha(trail.start_alpha * t));
+                render.bind_layer(sprite.layer.saturating_sub(1));
+                render.sprite(
+                    x,
+                    y,
+                    transform.origin,
+                    transform.scale * (trail.start_scale * t) * camera.scale(),
+                    transform.rotation,
+                    sprite.region,
+                    (sprite.flip_x, sprite.flip_y),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Point2f, Vector2f};
+
+    #[test]
+    fn trail_keeps_the_latest_positions_oldest_first() {
+        let mut world = World::new();
+        world.register::<TransformComponent>();
+        world.register::<BallTrailComponent>();
+        let ent = world
+            .create_entity()
+            .with(TransformComponent::new(
+                Vector2d::zeros(),
+                Point2f::origin(),
+                Vector2f::new(1.0, 1.0),
+            ))
+            .with(BallTrailComponent::new(3))
+            .build();
+
+        for i in 0..5 {
+            world
+                .write_storage::<TransformComponent>()
+                .get_mut(ent)
+                .unwrap()
+                .position = Vector2d::new(i as f64, 0.0);
+            BallTrailSystem.run_now(&world);
+        }
+
+        let trails = world.read_storage::<BallTrailComponent>();
+        let positions: Vec<f64> = trails
+            .get(ent)
+            .unwrap()
+            .positions
+            .iter()
+            .map(|position| position.x)
+            .collect();
+        assert_eq!(positions, vec![2.0, 3.0, 4.0]);
+    }
+}
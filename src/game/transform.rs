@@ -7,6 +7,8 @@ pub struct TransformComponent {
     pub last_position: Vector2d,
     pub origin: Point2f,
     pub scale: Vector2f,
+    // Rotation in radians, about the origin
+    pub rotation: f32,
 }
 
 impl Component for TransformComponent {
@@ -20,8 +22,15 @@ impl TransformComponent {
             last_position: position,
             origin,
             scale,
+            rotation: 0.0,
         }
     }
+
+    // Position blended between the last tick and the current one. An alpha of 0 is the last position, and 1 is the current.
+    pub fn interpolated_position(&self, alpha: f64) -> Vector2d {
+        let alpha = nalgebra::clamp(alpha, 0.0, 1.0);
+        (self.last_position * (1.0 - alpha)) + (self.position * alpha)
+    }
 }
 
 impl Default for TransformComponent {
@@ -31,6 +40,44 @@ impl Default for TransformComponent {
             last_position: Vector2d::zeros(),
             origin: Point2f::origin(),
             scale: Vector2f::new(1.0, 1.0),
+            rotation: 0.0,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolated_position_blends_between_ticks() {
+        let transform = TransformComponent {
+            position: Vector2d::new(10.0, 20.0),
+            last_position: Vector2d::new(0.0, 0.0),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            transform.interpolated_position(0.0),
+            Vector2d::new(0.0, 0.0)
+        );
+        assert_eq!(
+            transform.interpolated_position(0.25),
+            Vector2d::new(2.5, 5.0)
+        );
+        assert_eq!(
+            transform.interpolated_position(1.0),
+            Vector2d::new(10.0, 20.0)
+        );
+
+        // Alphas outside of the tick are clamped to it
+        assert_eq!(
+            transform.interpolated_position(-1.0),
+            Vector2d::new(0.0, 0.0)
+        );
+        assert_eq!(
+            transform.interpolated_position(2.0),
+            Vector2d::new(10.0, 20.0)
+        );
+    }
+}
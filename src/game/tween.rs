@@ -0,0 +1,140 @@
+use crate::game::render::SpriteComponent;
+use gfx::{color::Color, renderer::Transparency};
+use specs::prelude::*;
+
+// Blends a sprite's color from one color to another over a number of ticks
+pub struct ColorTweenComponent {
+    pub from: Color,
+    pub to: Color,
+    pub duration_ticks: u32,
+    pub elapsed_ticks: u32,
+    // If true, the entity is deleted once the tween finishes
+    pub despawn_on_finish: bool,
+}
+
+impl ColorTweenComponent {
+    pub fn new(from: Color, to: Color, duration_ticks: u32) -> Self {
+        ColorTweenComponent {
+            from,
+            to,
+            duration_ticks,
+            elapsed_ticks: 0,
+            despawn_on_finish: false,
+        }
+    }
+
+    pub fn with_despawn(mut self) -> Self {
+        self.despawn_on_finish = true;
+        self
+    }
+
+    pub fn current_color(&self) -> Color {
+        if self.duration_ticks == 0 {
+            return self.to;
+        }
+
+        let t = self.elapsed_ticks as f32 / self.duration_ticks as f32;
+        self.from.lerp(&self.to, t)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed_ticks >= self.duration_ticks
+    }
+}
+
+impl Component for ColorTweenComponent {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Default)]
+pub struct ColorTweenSystem;
+
+impl<'a> System<'a> for ColorTweenSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, SpriteComponent>,
+        WriteStorage<'a, ColorTweenComponent>,
+    );
+
+    fn run(&mut self, (ents, mut sprites, mut tweens): Self::SystemData) {
+        let mut finished_tweens: Vec<Entity> = Vec::new();
+        for (ent, sprite, tween) in (&ents, &mut sprites, &mut tweens).join() {
+            tween.elapsed_ticks = (tween.elapsed_ticks + 1).min(tween.duration_ticks);
+            sprite.color = tween.current_color();
+
+            // Translucent sprites need to be drawn with the transparent batches, after everything opaque
+            if sprite.color.alpha() < 1.0 {
+                sprite.transparency = Transparency::Transparent;
+            }
+
+            if tween.is_finished() {
+                finished_tweens.push(ent);
+
+                if tween.despawn_on_finish {
+                    ents.delete(ent).unwrap();
+                }
+            }
+        }
+
+        for ent in finished_tweens {
+            tweens.remove(ent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gfx::{color::COLOR_WHITE, sprite::SpriteRegion};
+
+    #[test]
+    fn fade_out_is_half_transparent_halfway_through() {
+        let mut world = World::new();
+        world.register::<SpriteComponent>();
+        world.register::<ColorTweenComponent>();
+        let ent = world
+            .create_entity()
+            .with(SpriteComponent {
+                color: COLOR_WHITE,
+                region: SpriteRegion {
+                    x: 0,
+                    y: 0,
+                    w: 32,
+                    h: 16,
+                },
+                spritesheet_tex_id: 0,
+                layer: 0,
+                transparency: Transparency::Opaque,
+                flip_x: false,
+                flip_y: false,
+            })
+            .with(ColorTweenComponent::new(
+                COLOR_WHITE,
+                COLOR_WHITE.with_alpha(0.0),
+                10,
+            ))
+            .build();
+
+        for _ in 0..5 {
+            ColorTweenSystem.run_now(&world);
+        }
+
+        {
+            let sprites = world.read_storage::<SpriteComponent>();
+            let sprite = sprites.get(ent).unwrap();
+            assert!((sprite.color.alpha() - 0.5).abs() < 1e-6);
+            assert_eq!(sprite.transparency, Transparency::Transparent);
+        }
+
+        for _ in 0..5 {
+            ColorTweenSystem.run_now(&world);
+        }
+
+        let sprites = world.read_storage::<SpriteComponent>();
+        assert_eq!(sprites.get(ent).unwrap().color.alpha(), 0.0);
+        assert!(world
+            .read_storage::<ColorTweenComponent>()
+            .get(ent)
+            .is_none());
+    }
+}
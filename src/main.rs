@@ -1,46 +1,86 @@
-mod game;
-
-use game::{
-    audio::{self, AudioAssetId, AudioAssetDb},
-    level::{self, LevelState},
+use brickbonker::game::{
+    assets::Assets,
+    audio::{AudioAssetDb, AudioAssetId},
+    bindings::{InputBindings, BINDINGS_PATH},
+    camera::Camera,
+    debug::DebugDraw,
+    level::{GamePhase, LevelConfig, LevelHotReload},
     physics::PhysicsState,
-    render::RenderState,
-    GameState,
+    render::{RenderState, HUD_LAYER},
+    replay::Replay,
+    GameState, Vector2d, REPLAY_PATH, SAVE_GAME_PATH,
 };
 
 use gfx::{
     color::*,
     image::*,
-    input::InputState,
+    input::{InputState, VirtualKeyCode},
     renderer::*,
     texture::*,
     window::{self, *},
 };
+use log::warn;
 use specs::prelude::*;
 
 fn main() {
+    // Warnings and errors are shown by default. Set RUST_LOG (e.g. RUST_LOG=physics=debug) for more.
+    env_logger::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
     let window_title: &str = "Brickbonker";
     let window_width: u32 = 320;
     let window_height: u32 = 240;
     let render_scale: f32 = 2.0;
-    let state = GameState::new(window_width, window_height);
+    let mut config = RunConfig::new(window_title, window_width, window_height, render_scale);
+
+    // Mouse control keeps the cursor in the window, unless asked not to
+    let grab_cursor = !std::env::args().any(|arg| arg == "--no-cursor-grab");
+
+    // Only scale by whole numbers, so the pixel art stays sharp at any window size
+    if std::env::args().any(|arg| arg == "--integer-scale") {
+        config.scaling_mode = ScalingMode::IntegerNearest;
+    }
+
+    // Stop the game while the window is in the background
+    config.pause_when_unfocused = std::env::args().any(|arg| arg == "--pause-unfocused");
+
+    // Two players can share the keyboard, with a paddle each
+    let players = if std::env::args().any(|arg| arg == "--two-player") {
+        2
+    } else {
+        1
+    };
+    let mut state = GameState::new_multiplayer(window_width, window_height, players);
+    state
+        .world
+        .write_resource::<PhysicsState>()
+        .set_timestep(config.target_dt());
+    state.load_level("level-1.ron");
+    state.show_main_menu();
+
+    // Reload level files as they are edited, for level design
+    if std::env::args().any(|arg| arg == "--hot-reload") {
+        state.world.write_resource::<LevelHotReload>().enabled = true;
+    }
+
+    // Custom key bindings are optional
+    if std::path::Path::new(BINDINGS_PATH).exists() {
+        match InputBindings::load(BINDINGS_PATH) {
+            Ok(bindings) => state.world.insert(bindings),
+            Err(e) => warn!(target: "main", "Failed to load input bindings! Using the defaults. Error: {}", e),
+        }
+    }
 
     window::run(
-        window_title,
-        window_width,
-        window_height,
-        render_scale,
+        config,
         state,
         move |game, renderer| {
-            import_texture(1, "res/textures/costanza.png", renderer);
-            import_texture(2, "res/textures/sprites.png", renderer);
-            import_texture(3, "res/textures/font.png", renderer);
-            import_texture(4, "res/textures/bg.png", renderer);
+            for (id, path) in game.world.read_resource::<Assets>().texture_paths() {
+                import_texture(id, path, renderer);
+            }
 
-            // Import audio assets (music and sound effects)
+            // Import sound effect assets. Music is streamed when a level starts.
             {
                 let mut audio_db = game.world.write_resource::<AudioAssetDb>();
-                audio_db.import(AudioAssetId::MusicBackground, "res/audio/tha-bounce-life.wav").unwrap();
                 audio_db.import(AudioAssetId::SfxBallBounce0, "res/audio/ball-bounce-0.wav").unwrap();
                 audio_db.import(AudioAssetId::SfxBallBounce1, "res/audio/ball-bounce-1.wav").unwrap();
                 audio_db.import(AudioAssetId::SfxBallWallHit0, "res/audio/ball-wall-hit-0.wav").unwrap();
@@ -48,64 +88,128 @@ fn main() {
                 audio_db.import(AudioAssetId::SfxBrickBreak0, "res/audio/brick-break-0.wav").unwrap();
                 audio_db.import(AudioAssetId::SfxBrickBreak1, "res/audio/brick-break-1.wav").unwrap();
                 audio_db.import(AudioAssetId::SfxBallDeath0, "res/audio/ball-death-0.wav").unwrap();
-
-                // Start playing the bg music right away
-                audio::play(AudioAssetId::MusicBackground, &audio_db, true);
             }
 
         },
-        move |game, _window, input, dt| {
+        move |game, window, input, dt| {
             game.world.insert::<InputState>(input.clone());
-            game.world.insert::<DeltaTime>(dt);
-
-            // Handle any level loads
-            let load_level_pending = game
-                .world
-                .read_resource::<LevelState>()
-                .load_level_event
-                .is_some();
-            if load_level_pending {
-                level::load_level(&mut game.world);
+
+            // Quick save and load
+            if input.was_pressed(VirtualKeyCode::F5) {
+                if let Err(e) = game.save(SAVE_GAME_PATH) {
+                    warn!(target: "main", "Failed to save game! Error: {}", e);
+                }
             }
 
-            game.world.write_resource::<RenderState>().clear_commands();
-            game.tick_dispatcher.dispatch(&mut game.world);
-            game.physics_dispatcher.dispatch(&mut game.world);
+            if input.was_pressed(VirtualKeyCode::F9) {
+                if let Err(e) = game.load(SAVE_GAME_PATH) {
+                    warn!(target: "main", "Failed to load game! Error: {}", e);
+                }
+            }
 
-            game.world.maintain();
+            // Start or stop recording a replay. Stopping saves it.
+            if input.was_pressed(VirtualKeyCode::F6) {
+                if game.is_recording() {
+                    if let Some(replay) = game.stop_recording() {
+                        if let Err(e) = replay.save(REPLAY_PATH) {
+                            warn!(target: "main", "Failed to save replay! Error: {}", e);
+                        }
+                    }
+                } else {
+                    game.start_recording();
+                }
+            }
+
+            // Play back the saved replay
+            if input.was_pressed(VirtualKeyCode::F7) {
+                match Replay::load(REPLAY_PATH) {
+                    Ok(replay) => game.play_replay(replay),
+                    Err(e) => warn!(target: "main", "Failed to load replay! Error: {}", e),
+                }
+            }
+
+            // Toggle the collider debug draw
+            if input.was_pressed(VirtualKeyCode::F3) {
+                let mut debug_draw = game.world.write_resource::<DebugDraw>();
+                debug_draw.enabled = !debug_draw.enabled;
+            }
+
+            // Starting, pausing and restarting are handled by the MenuSystem
+            game.tick(dt);
+
+            window.cursor_grabbed = grab_cursor && game.wants_cursor_grab(window.focused);
         },
         move |game, _ticks, lerp, window, renderer| {
+            // Fit the level to the current window size
+            {
+                let (render_width, render_height) = window.render_size();
+                let mut camera = game.world.write_resource::<Camera>();
+                camera.scaling_mode = window.scaling_mode;
+                camera.resize(render_width, render_height, window.pixels_per_render_unit());
+            }
+
+            // Sprites are collected every frame (rather than every tick) so they can be interpolated between ticks
             game.world.write_resource::<PhysicsState>().lerp = lerp;
+            game.world.write_resource::<RenderState>().clear_commands();
+            game.render_dispatcher.dispatch(&mut game.world);
+
+            let (font_tex_id, background_tex_id) = {
+                let assets = game.world.read_resource::<Assets>();
+                (assets.texture("font"), assets.texture("background"))
+            };
 
             let mut render = game.world.write_resource::<RenderState>();
 
-            // FPS text
-            let msg = format!("FPS: {}", window.fps);
+            // FPS and TPS text
+            let msg = format!("FPS: {} TPS: {}", window.fps, window.tps);
             render.bind_color(COLOR_WHITE);
-            render.bind_layer(0);
+            render.bind_layer(HUD_LAYER);
             render.bind_transparency(Transparency::Transparent);
-            render.bind_texture(3);
+            render.bind_texture(font_tex_id);
             let fps_text_x = window_width as f32 - (msg.len() as f32 * 4.0) - 2.0;
             render.text(fps_text_x, 2.0, 8, 16, 0.5, &msg);
 
-            let (score, balls, is_game_over) = {
-                let level = game.world.read_resource::<LevelState>();
-                (level.score, level.lives, level.lives == 0)
-            };
+            let phase = *game.world.read_resource::<GamePhase>();
+            let is_game_over = phase == GamePhase::GameOver;
+            let is_victory = phase == GamePhase::Victory;
+
+            if phase == GamePhase::MainMenu {
+                // Title text
+                let title_text_y = window_height as f32 - 22.0;
+                render.bind_color(COLOR_WHITE);
+                render.text(2.0, title_text_y, 8, 16, 0.75, window_title);
+
+                // Start text
+                let start_text_y = window_height as f32 - 10.0;
+                render.text(2.0, start_text_y, 8, 16, 0.5, "Press 'Space' to start.");
+            }
+
+            if phase == GamePhase::Paused {
+                // Paused text
+                let paused_text_y = window_height as f32 - 10.0;
+                render.bind_color(COLOR_WHITE);
+                render.text(2.0, paused_text_y, 8, 16, 0.5, &format!("Paused. Press 'P' to resume."));
+            }
+
+            if is_victory {
+                // Victory text
+                let victory_text_y = window_height as f32 - 22.0;
+                render.bind_color(COLOR_GREEN);
+                render.text(2.0, victory_text_y, 8, 16, 0.75, &format!("You Win!"));
+
+                // Restart text
+                let restart_text_y = window_height as f32 - 10.0;
+                render.bind_color(COLOR_WHITE);
+                render.text(
+                    2.0,
+                    restart_text_y,
+                    8,
+                    16,
+                    0.5,
+                    &format!("Press 'R' to start a new game."),
+                );
+            }
 
-            // Score text
-            let msg = format!("Score: {}", score);
-            render.bind_color(if is_game_over {
-                COLOR_GREEN
-            } else {
-                COLOR_WHITE
-            });
-            render.text(2.0, 2.0, 8, 16, 0.5, &msg);
-
-            // Balls text
-            let msg = format!("Balls: {}", balls);
-            render.bind_color(COLOR_WHITE);
-            render.text(2.0, 10.0, 8, 16, 0.5, &msg);
             if is_game_over {
                 // Game Over text
                 let game_over_text_y = window_height as f32 - 22.0;
@@ -121,7 +225,7 @@ fn main() {
                     8,
                     16,
                     0.5,
-                    &format!("Press 'R' to start a new game."),
+                    &format!("Press 'R' for a new game, or 'L' to retry."),
                 );
             }
 
@@ -129,8 +233,18 @@ fn main() {
             render.bind_color(COLOR_WHITE);
             render.bind_layer(0);
             render.bind_transparency(Transparency::Opaque);
-            render.bind_texture(4);
-            render.textured_quad((0.0, 400.0), (400.0, 400.0), (0.0, 0.0), (400.0, 0.0));
+            render.bind_texture(background_tex_id);
+            {
+                // Covers the whole play field
+                let camera = game.world.read_resource::<Camera>();
+                let config = game.world.read_resource::<LevelConfig>();
+                let (left, top) = camera.world_to_screen(Vector2d::new(0.0, 0.0));
+                let (right, bottom) = camera.world_to_screen(Vector2d::new(
+                    config.field_width as f64,
+                    config.field_height as f64,
+                ));
+                render.textured_quad((left, bottom), (right, bottom), (left, top), (right, top));
+            }
 
             // Process commands into batches and send to the renderer
             let batches = renderer.process_commands(render.commands());